@@ -1,3 +1,5 @@
 from .dynapai import *
+# not included in *, but pickled objects are loaded through it
+from .dynapai import _unpickle
 
 from .plots import plot
\ No newline at end of file
@@ -1,7 +1,7 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
-use numpy::Ix2;
-use numpy::ndarray::{Array, Ix1};
+use ndarray::{Array, ArrayView, Ix1, Ix2, s};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 
 use crate::prelude::*;
 
@@ -12,6 +12,11 @@ pub trait Aggregator<A: ActionType>: StateIterator<A> + Downcast + DynClone + Se
     fn u(&self, strategies: &Strategies<A>) -> Array<f64, Ix1> {
         Array::from_iter((0..strategies.n()).map(|i| self.u_i(i, strategies)))
     }
+    // gradient of u_i wrt player i's strategy, as an array of shape (t, nparams)
+    fn du_i(&self, i: usize, strategies: &Strategies<A>) -> Array<f64, Ix2>;
+    // factor applied to player i's payoffs from the next period onward,
+    // given the current state and actions
+    fn continuation(&self, i: usize, state: &dyn State<A>, actions: &A) -> f64;
 }
 
 clone_trait_object!(<A> Aggregator<A> where A: ActionType);
@@ -22,6 +27,33 @@ pub trait Discounter {
     fn gammas(&self) -> &Array<f64, Ix1>;
 }
 
+// adds a gradient wrt a single period's actions, given the gradient wrt the state,
+// to the gradient wrt the whole strategy (flattened over periods)
+fn add_period_grad(
+    grad: &mut Array<f64, Ix1>,
+    t: usize,
+    dactions: ArrayView<f64, Ix1>,
+    dstate: ArrayView<f64, Ix1>,
+    dstate_dx: &Array<f64, Ix2>,
+    scale: f64,
+) {
+    let nparams = dactions.len();
+    grad.slice_mut(s![t * nparams..(t + 1) * nparams]).scaled_add(scale, &dactions);
+    grad.scaled_add(scale, &dstate.dot(dstate_dx));
+}
+
+// updates the jacobian of the state wrt player i's strategy (flattened over periods)
+// given the jacobians of the one-period state transition
+fn advance_state_grad(
+    dstate_dx: &mut Array<f64, Ix2>,
+    t: usize,
+    (jac_state, jac_actions): (Array<f64, Ix2>, Array<f64, Ix2>),
+) {
+    let nparams = jac_actions.shape()[1];
+    *dstate_dx = jac_state.dot(dstate_dx);
+    dstate_dx.slice_mut(s![.., t * nparams..(t + 1) * nparams]).scaled_add(1., &jac_actions);
+}
+
 impl<A: ActionType + 'static, T: StateIterator<A> + Discounter + 'static> Aggregator<A> for T {
     fn n(&self) -> usize {
         self.state0().n()
@@ -54,6 +86,32 @@ impl<A: ActionType + 'static, T: StateIterator<A> + Discounter + 'static> Aggreg
         }
         u
     }
+    fn du_i(&self, i: usize, strategies: &Strategies<A>) -> Array<f64, Ix2> {
+        let actions_seq = strategies.actions();
+        let state = &mut self.state0().clone();
+        let gamma = self.gammas()[i];
+        let size = strategies.t() * A::nparams();
+        let mut dstate_dx = Array::zeros((state.belief(i).state_dim(), size));
+        let mut grad = Array::zeros(size);
+        for (t, actions) in actions_seq.iter().enumerate() {
+            let belief = state.belief(i);
+            add_period_grad(
+                &mut grad, t,
+                belief.du_i(i, actions).view(),
+                belief.du_i_dstate(i, actions).view(),
+                &dstate_dx,
+                gamma.powi(t.try_into().unwrap()),
+            );
+            if t != strategies.t() - 1 {
+                advance_state_grad(&mut dstate_dx, t, self.dadvance_state(state.as_ref(), i, actions));
+                self.advance_state(state, actions);
+            }
+        }
+        grad.into_shape((strategies.t(), A::nparams())).unwrap()
+    }
+    fn continuation(&self, i: usize, _state: &dyn State<A>, _actions: &A) -> f64 {
+        self.gammas()[i]
+    }
 }
 
 
@@ -64,18 +122,24 @@ pub struct FixedStateDiscounter<A: ActionType> {
 }
 
 impl<A: ActionType + 'static> FixedStateDiscounter<A> {
-    pub fn new(state: Box<dyn State<A>>, gammas: Array<f64, Ix1>) -> Result<Self, &'static str> {
+    pub fn new(state: Box<dyn State<A>>, gammas: Array<f64, Ix1>) -> Result<Self, DynapaiError> {
         if state.n() != gammas.len() {
-            return Err("When creating new FixedStateDiscounter: gammas must have length == n");
+            return Err(DynapaiError::DimensionMismatch("When creating new FixedStateDiscounter: gammas must have length == n".to_string()));
         }
         Ok(FixedStateDiscounter { state, gammas })
     }
 }
 
-impl<A: ActionType + Clone> StateIterator<A> for FixedStateDiscounter<A> {
+impl<A: ActionType + Clone + 'static> StateIterator<A> for FixedStateDiscounter<A> {
     fn state0(&self) -> &Box<dyn State<A>> {
         &self.state
     }
+
+    // state never changes
+    fn dadvance_state(&self, state: &dyn State<A>, i: usize, _actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        let dim = state.belief(i).state_dim();
+        (Array::eye(dim), Array::zeros((dim, A::nparams())))
+    }
 }
 
 impl<A: ActionType> Discounter for FixedStateDiscounter<A> {
@@ -92,15 +156,15 @@ pub struct DynStateDiscounter<A: ActionType> {
 }
 
 impl<A: ActionType + 'static> DynStateDiscounter<A> {
-    pub fn new(state0: Box<dyn State<A>>, gammas: Array<f64, Ix1>) -> Result<Self, &'static str> {
+    pub fn new(state0: Box<dyn State<A>>, gammas: Array<f64, Ix1>) -> Result<Self, DynapaiError> {
         if state0.n() != gammas.len() {
-            return Err("When creating new DynStateDiscounter: gammas must have length == n");
+            return Err(DynapaiError::DimensionMismatch("When creating new DynStateDiscounter: gammas must have length == n".to_string()));
         }
         Ok(DynStateDiscounter { state0, gammas })
     }
 }
 
-impl<A: ActionType + Clone> StateIterator<A> for DynStateDiscounter<A>
+impl<A: ActionType + Clone + 'static> StateIterator<A> for DynStateDiscounter<A>
 {
     fn state0(&self) -> &Box<dyn State<A>> {
         &self.state0
@@ -109,6 +173,10 @@ impl<A: ActionType + Clone> StateIterator<A> for DynStateDiscounter<A>
     fn advance_state(&self, state: &mut Box<dyn State<A>>, actions: &A) {
         state.mutate_on(actions);
     }
+
+    fn dadvance_state(&self, state: &dyn State<A>, i: usize, actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        state.dmutate_on(i, actions)
+    }
 }
 
 impl<A: ActionType> Discounter for DynStateDiscounter<A> {
@@ -131,12 +199,12 @@ impl<A, C> EndsOnContestWin<A, C>
 where A: ActionType + Clone + 'static,
       C: Discounter + StateIterator<A> + Clone + 'static
 {
-    pub fn new(child: C) -> Result<Self, &'static str> {
+    pub fn new(child: C) -> Result<Self, DynapaiError> {
         // check that the states given by the child have ModularPayoff beliefs
         let state0 = child.state0();
         for i in 0..state0.n() {
             if let None = state0.belief(i).downcast_ref::<ModularPayoff<A>>() {
-                return Err("The provided states should all contain ModularPayoff types")
+                return Err(DynapaiError::InvalidParameter("The provided states should all contain ModularPayoff types".to_string()))
             }
         }
         Ok(EndsOnContestWin { child, _phantom: std::marker::PhantomData })
@@ -166,7 +234,7 @@ where A: ActionType + Clone + 'static,
 }
 
 impl<A, C> StateIterator<A> for EndsOnContestWin<A, C>
-where A: ActionType + Clone,
+where A: ActionType + Clone + 'static,
       C: Discounter + StateIterator<A> + Clone
 {
     fn state0(&self) -> &Box<dyn State<A>> {
@@ -176,6 +244,10 @@ where A: ActionType + Clone,
     fn advance_state(&self, state: &mut Box<dyn State<A>>, actions: &A) {
         state.mutate_on(actions);
     }
+
+    fn dadvance_state(&self, state: &dyn State<A>, i: usize, actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        state.dmutate_on(i, actions)
+    }
 }
 
 impl<A, C> Aggregator<A> for EndsOnContestWin<A, C>
@@ -232,11 +304,184 @@ where A: ActionType + Clone + 'static,
         }
         u
     }
+    fn du_i(&self, i: usize, strategies: &Strategies<A>) -> Array<f64, Ix2> {
+        let actions_seq = strategies.actions();
+        let state = &mut self.state0().clone();
+        let gamma = self.child.gammas()[i];
+        let size = strategies.t() * A::nparams();
+        let mut proba = 1.;
+        let mut dproba: Array<f64, Ix1> = Array::zeros(size);
+        let mut dstate_dx = Array::zeros((state.belief(i).state_dim(), size));
+        let mut grad = Array::zeros(size);
+        for (t, actions) in actions_seq.iter().enumerate() {
+            let payoff_func = state.belief(i).downcast_ref::<ModularPayoff<A>>().expect(
+                "Belief should be ModularPayoff, but found something else"
+            );
+            let discount = gamma.powi(t.try_into().unwrap());
+            // d(proba * u) = dproba * u + proba * du
+            grad.scaled_add(discount * payoff_func.u_i(i, actions), &dproba);
+            add_period_grad(
+                &mut grad, t,
+                payoff_func.du_i(i, actions).view(),
+                payoff_func.du_i_dstate(i, actions).view(),
+                &dstate_dx,
+                discount * proba,
+            );
+            if t != strategies.t() - 1 {
+                // update proba
+                let (_, p) = payoff_func.prod_func.f(actions);
+                let win_proba = payoff_func.csf.q(p.view()).iter().sum::<f64>();
+                let (dwin_actions, dwin_state) = payoff_func.dwin_proba(i, actions);
+                let mut dwin: Array<f64, Ix1> = Array::zeros(size);
+                add_period_grad(&mut dwin, t, dwin_actions.view(), dwin_state.view(), &dstate_dx, 1.);
+                dproba = dproba * (1. - win_proba) - proba * dwin;
+                proba *= 1. - win_proba;
+                // update state
+                advance_state_grad(&mut dstate_dx, t, self.dadvance_state(state.as_ref(), i, actions));
+                self.advance_state(state, actions);
+            }
+        }
+        grad.into_shape((strategies.t(), A::nparams())).unwrap()
+    }
+    // game continues only if nobody wins this period
+    fn continuation(&self, i: usize, state: &dyn State<A>, actions: &A) -> f64 {
+        let payoff_func = state.belief(i).downcast_ref::<ModularPayoff<A>>().expect(
+            "Belief should be ModularPayoff, but found something else"
+        );
+        let (_, p) = payoff_func.prod_func.f(actions);
+        self.child.gammas()[i] * (1. - payoff_func.csf.q(p.view()).iter().sum::<f64>())
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    bound(
+        serialize = "Box<dyn State<A>>: Serialize",
+        deserialize = "Box<dyn State<A>>: Deserialize<'de>",
+    ),
+)]
+enum AggregatorSpec<A: ActionType + 'static> {
+    FixedStateDiscounter { state: Box<dyn State<A>>, gammas: Vec<f64> },
+    DynStateDiscounter { state0: Box<dyn State<A>>, gammas: Vec<f64> },
+    // child must be one of the discounters
+    EndsOnContestWin { child: Box<AggregatorSpec<A>> },
+}
+
+impl<A: ActionType + Clone + 'static> AggregatorSpec<A> {
+    fn from_discounter(agg: &dyn Aggregator<A>) -> Option<Self> {
+        if let Some(x) = agg.downcast_ref::<FixedStateDiscounter<A>>() {
+            Some(AggregatorSpec::FixedStateDiscounter { state: x.state.clone(), gammas: x.gammas.to_vec() })
+        }
+        else if let Some(x) = agg.downcast_ref::<DynStateDiscounter<A>>() {
+            Some(AggregatorSpec::DynStateDiscounter { state0: x.state0.clone(), gammas: x.gammas.to_vec() })
+        }
+        else {
+            None
+        }
+    }
 }
 
+impl<A: ActionType + Clone + 'static> Serialize for Box<dyn Aggregator<A>>
+where Box<dyn State<A>>: Serialize
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if let Some(spec) = AggregatorSpec::from_discounter(self.as_ref()) {
+            spec
+        }
+        else if let Some(x) = self.downcast_ref::<EndsOnContestWin<A, FixedStateDiscounter<A>>>() {
+            AggregatorSpec::EndsOnContestWin { child: Box::new(AggregatorSpec::FixedStateDiscounter {
+                state: x.child.state.clone(), gammas: x.child.gammas.to_vec(),
+            }) }
+        }
+        else if let Some(x) = self.downcast_ref::<EndsOnContestWin<A, DynStateDiscounter<A>>>() {
+            AggregatorSpec::EndsOnContestWin { child: Box::new(AggregatorSpec::DynStateDiscounter {
+                state0: x.child.state0.clone(), gammas: x.child.gammas.to_vec(),
+            }) }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this Aggregator"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de, A: ActionType + Clone + 'static> Deserialize<'de> for Box<dyn Aggregator<A>>
+where Box<dyn State<A>>: Deserialize<'de>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match AggregatorSpec::deserialize(deserializer)? {
+            AggregatorSpec::FixedStateDiscounter { state, gammas } => Box::new(
+                FixedStateDiscounter::new(state, Array::from_vec(gammas)).map_err(de::Error::custom)?
+            ),
+            AggregatorSpec::DynStateDiscounter { state0, gammas } => Box::new(
+                DynStateDiscounter::new(state0, Array::from_vec(gammas)).map_err(de::Error::custom)?
+            ),
+            AggregatorSpec::EndsOnContestWin { child } => match *child {
+                AggregatorSpec::FixedStateDiscounter { state, gammas } => Box::new(EndsOnContestWin::new(
+                    FixedStateDiscounter::new(state, Array::from_vec(gammas)).map_err(de::Error::custom)?
+                ).map_err(de::Error::custom)?),
+                AggregatorSpec::DynStateDiscounter { state0, gammas } => Box::new(EndsOnContestWin::new(
+                    DynStateDiscounter::new(state0, Array::from_vec(gammas)).map_err(de::Error::custom)?
+                ).map_err(de::Error::custom)?),
+                AggregatorSpec::EndsOnContestWin { .. } => return Err(
+                    de::Error::custom("EndsOnContestWin child must be FixedStateDiscounter or DynStateDiscounter")
+                ),
+            },
+        })
+    }
+}
 
 #[derive(Clone)]
 pub struct SolverResult<A: ActionType> {
     pub status: String,
     pub strategies: Option<Strategies<A>>,
+    pub trace: Option<SolverTrace<A>>,
+    pub verification: Option<Verification<A>>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    // checks du_i against finite differences of u_i over player i's whole strategy
+    fn check_grad<A: ActionType + Clone + 'static>(agg: &dyn Aggregator<A>) {
+        let strategies = strategies::<A>(3, 2);
+        for i in 0..2 {
+            let x = strategies.data().slice(s![.., i, ..]).to_owned();
+            let shape = x.dim();
+            let fd = fd_grad(Array::from_iter(x.iter().cloned()).view(), |x| {
+                let mut strategies = strategies.clone();
+                strategies.set_i(i, x.to_owned().into_shape(shape).unwrap());
+                agg.u_i(i, &strategies)
+            });
+            assert_close(&agg.du_i(i, &strategies), &fd, 1e-6);
+        }
+    }
+
+    #[test]
+    fn grads_match_finite_differences() {
+        let gammas = array![0.9, 0.8];
+        let common = |payoff: ModularPayoff<InvestActions>| -> Box<dyn State<InvestActions>> {
+            Box::new(CommonBeliefs(Box::new(payoff)))
+        };
+        check_grad(&FixedStateDiscounter::new(common(invest_payoff(Box::new(DefaultCSF))), gammas.clone()).unwrap());
+        check_grad(&DynStateDiscounter::new(common(invest_payoff(Box::new(DefaultCSF))), gammas.clone()).unwrap());
+
+        let mut other = invest_payoff(Box::new(DefaultCSF));
+        other.set_state(array![0.8, 1.2, 1., 1.].view());
+        let het = HetBeliefs::new(vec![Box::new(invest_payoff(Box::new(DefaultCSF))), Box::new(other)]).unwrap();
+        check_grad(&DynStateDiscounter::new(Box::new(het), gammas.clone()).unwrap());
+
+        let sharing: Box<dyn State<SharingActions>> = Box::new(CommonBeliefs(Box::new(sharing_payoff(Box::new(DefaultCSF)))));
+        check_grad(&DynStateDiscounter::new(sharing, gammas.clone()).unwrap());
+
+        let maybe_no_win = common(invest_payoff(Box::new(MaybeNoWinCSF::new(1.).unwrap())));
+        check_grad(&EndsOnContestWin::new(DynStateDiscounter::new(maybe_no_win, gammas).unwrap()).unwrap());
+    }
 }
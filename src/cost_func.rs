@@ -1,7 +1,10 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
-use numpy::{ndarray::{Array, Ix1, s}, Ix2};
+use ndarray::{Array, Ix1, Ix2, s};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 
+use crate::error::DynapaiError;
+use crate::serialize::{from_rows, to_rows};
 use crate::strategies::*;
 
 pub trait CostFunc<A: ActionType>: DynClone + Downcast + Send + Sync {
@@ -10,6 +13,8 @@ pub trait CostFunc<A: ActionType>: DynClone + Downcast + Send + Sync {
     fn c(&self, actions: &A) -> Array<f64, Ix1> {
         Array::from_iter((0..actions.n()).map(|i| self.c_i(i, actions)))
     }
+    // gradient of c_i wrt player i's actions
+    fn dc_i(&self, i: usize, actions: &A) -> Array<f64, Ix1>;
 
     fn n(&self) -> usize;
 }
@@ -23,12 +28,12 @@ pub trait FixedCost<A: ActionType>: CostFunc<A> {
     fn r_mut(&mut self) -> &mut Array<f64, Ix2>;
     fn new_unchecked(r: Array<f64, Ix2>) -> Self where Self: Sized;
     
-    fn new(r: Array<f64, Ix2>) -> Result<Self, String> where Self: Sized {
+    fn new(r: Array<f64, Ix2>) -> Result<Self, DynapaiError> where Self: Sized {
         if r.shape()[1] != Self::nparams() {
-            Err(format!(
+            Err(DynapaiError::DimensionMismatch(format!(
                 "Invalid number of params: {}, expected {}",
                 r.shape()[1], Self::nparams()
-            ))
+            )))
         } else {
             Ok(Self::new_unchecked(r))
         }
@@ -47,6 +52,10 @@ impl<A: ActionType, C: FixedCost<A>> CostFunc<A> for C {
         self.r().slice(s![i, ..]).dot(&actions.data().slice(s![i, ..]))
     }
 
+    fn dc_i(&self, i: usize, _actions: &A) -> Array<f64, Ix1> {
+        self.r().slice(s![i, ..]).to_owned()
+    }
+
     fn n(&self) -> usize {
         self.r().shape()[0]
     }
@@ -55,6 +64,12 @@ impl<A: ActionType, C: FixedCost<A>> CostFunc<A> for C {
 clone_trait_object!(<A> FixedCost<A> where A: ActionType);
 impl_downcast!(FixedCost<A> where A: ActionType);
 
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum CostFuncSpec {
+    FixedCost { r: Vec<Vec<f64>> },
+}
+
 macro_rules! impl_fixed_cost {
     ($name:ident, $a_type:ident) => {
         #[derive(Clone)]
@@ -74,6 +89,28 @@ macro_rules! impl_fixed_cost {
                 Self(r)
             }
         }
+
+        impl Serialize for Box<dyn CostFunc<$a_type>> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let spec = if let Some(x) = self.downcast_ref::<$name>() {
+                    CostFuncSpec::FixedCost { r: to_rows(x.r()) }
+                }
+                else {
+                    return Err(ser::Error::custom("Serialization not implemented for this CostFunc"));
+                };
+                spec.serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Box<dyn CostFunc<$a_type>> {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Ok(match CostFuncSpec::deserialize(deserializer)? {
+                    CostFuncSpec::FixedCost { r } => Box::new(
+                        $name::new(from_rows(r).map_err(de::Error::custom)?).map_err(de::Error::custom)?
+                    ),
+                })
+            }
+        }
     };
 }
 
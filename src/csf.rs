@@ -1,12 +1,20 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
-use numpy::ndarray::{Array, ArrayView, Ix1};
+use ndarray::{Array, ArrayView, Ix1};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
+
+use crate::error::DynapaiError;
 
 pub trait CSF: DynClone + Downcast + Send + Sync {
     fn q_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64;
     fn q(&self, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
         Array::from_iter((0..p.len()).map(|i| self.q_i(i, p)))
     }
+    // gradient of q_i wrt p
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1>;
+
+    // number of players for CSFs with per-player parameters; symmetric CSFs work for any number
+    fn n(&self) -> Option<usize> { None }
 }
 
 clone_trait_object!(CSF);
@@ -34,6 +42,16 @@ impl CSF for DefaultCSF {
             Array::from_iter(p.iter().map(|x| x / sum_p))
         }
     }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        let sum_p: f64 = p.iter().sum();
+        if sum_p == 0.0 {
+            return Array::zeros(p.len());
+        }
+        let mut dq = Array::from_elem(p.len(), -p[i] / sum_p.powi(2));
+        dq[i] += 1.0 / sum_p;
+        dq
+    }
 }
 
 
@@ -41,9 +59,9 @@ impl CSF for DefaultCSF {
 pub struct MaybeNoWinCSF { scale: f64 }
 
 impl MaybeNoWinCSF {
-    pub fn new(scale: f64) -> Result<Self, &'static str> {
+    pub fn new(scale: f64) -> Result<Self, DynapaiError> {
         if scale <= 0. {
-            return Err("scale must be positive");
+            return Err(DynapaiError::InvalidParameter("scale must be positive".to_string()));
         }
         Ok(Self { scale })
     }
@@ -62,4 +80,422 @@ impl CSF for MaybeNoWinCSF {
             self.scale * x / (1. + self.scale * sum_p)
         ))
     }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        let denom = 1. + self.scale * p.iter().sum::<f64>();
+        let mut dq = Array::from_elem(p.len(), -self.scale.powi(2) * p[i] / denom.powi(2));
+        dq[i] += self.scale / denom;
+        dq
+    }
+}
+
+
+// q_i = a_i / (c + sum_j a_j), where each player's weight a_j depends only on p_j;
+// da holds the derivatives of the weights, and c > 0 leaves a chance that nobody wins
+fn ratio_q(a: &Array<f64, Ix1>, c: f64) -> Array<f64, Ix1> {
+    let denom = c + a.sum();
+    if denom == 0.0 {
+        Array::zeros(a.len())
+    } else {
+        a / denom
+    }
+}
+
+fn ratio_dq_i(i: usize, a: &Array<f64, Ix1>, da: &Array<f64, Ix1>, c: f64) -> Array<f64, Ix1> {
+    let denom = c + a.sum();
+    if denom == 0.0 {
+        return Array::zeros(a.len());
+    }
+    let mut dq = da * (-a[i] / denom.powi(2));
+    dq[i] += da[i] / denom;
+    dq
+}
+
+fn check_positive(name: &str, x: f64) -> Result<(), DynapaiError> {
+    if x <= 0. {
+        return Err(DynapaiError::InvalidParameter(format!("{} must be positive", name)));
+    }
+    Ok(())
+}
+
+fn check_all_positive(name: &str, x: &Array<f64, Ix1>) -> Result<(), DynapaiError> {
+    if x.is_empty() || x.iter().any(|x| *x <= 0.) {
+        return Err(DynapaiError::InvalidParameter(format!("{} must be nonempty with all entries positive", name)));
+    }
+    Ok(())
+}
+
+
+// generalized Tullock CSF, q_i = p_i^r / sum_j p_j^r, where r is the decisiveness of the contest
+#[derive(Clone, Debug)]
+pub struct TullockCSF { r: f64 }
+
+impl TullockCSF {
+    pub fn new(r: f64) -> Result<Self, DynapaiError> {
+        check_positive("r", r)?;
+        Ok(Self { r })
+    }
+
+    fn weights(&self, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (p.mapv(|x| x.powf(self.r)), p.mapv(|x| self.r * x.powf(self.r - 1.)))
+    }
+}
+
+impl CSF for TullockCSF {
+    fn q_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.q(p)[i]
+    }
+
+    fn q(&self, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        ratio_q(&self.weights(p).0, 0.)
+    }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        let (a, da) = self.weights(p);
+        ratio_dq_i(i, &a, &da, 0.)
+    }
+}
+
+
+// Tullock CSF where nobody wins with probability 1 / (1 + scale * sum_j p_j^r);
+// with r = 1 this is MaybeNoWinCSF
+#[derive(Clone, Debug)]
+pub struct MaybeNoWinTullockCSF { r: f64, scale: f64 }
+
+impl MaybeNoWinTullockCSF {
+    pub fn new(r: f64, scale: f64) -> Result<Self, DynapaiError> {
+        check_positive("r", r)?;
+        check_positive("scale", scale)?;
+        Ok(Self { r, scale })
+    }
+
+    fn weights(&self, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (p.mapv(|x| self.scale * x.powf(self.r)), p.mapv(|x| self.scale * self.r * x.powf(self.r - 1.)))
+    }
+}
+
+impl CSF for MaybeNoWinTullockCSF {
+    fn q_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.q(p)[i]
+    }
+
+    fn q(&self, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        ratio_q(&self.weights(p).0, 1.)
+    }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        let (a, da) = self.weights(p);
+        ratio_dq_i(i, &a, &da, 1.)
+    }
+}
+
+
+// Tullock CSF with per-player weights, q_i = w_i p_i^r / sum_j w_j p_j^r;
+// w_i > 1 gives player i an advantage, like an incumbent's
+#[derive(Clone, Debug)]
+pub struct WeightedCSF { w: Array<f64, Ix1>, r: f64 }
+
+impl WeightedCSF {
+    pub fn new(w: Array<f64, Ix1>, r: f64) -> Result<Self, DynapaiError> {
+        check_all_positive("w", &w)?;
+        check_positive("r", r)?;
+        Ok(Self { w, r })
+    }
+
+    fn weights(&self, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (
+            &self.w * &p.mapv(|x| x.powf(self.r)),
+            &self.w * &p.mapv(|x| self.r * x.powf(self.r - 1.)),
+        )
+    }
+}
+
+impl CSF for WeightedCSF {
+    fn q_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.q(p)[i]
+    }
+
+    fn q(&self, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        ratio_q(&self.weights(p).0, 0.)
+    }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        let (a, da) = self.weights(p);
+        ratio_dq_i(i, &a, &da, 0.)
+    }
+
+    fn n(&self) -> Option<usize> {
+        Some(self.w.len())
+    }
+}
+
+
+// MaybeNoWinTullockCSF with a scale for each player, q_i = scale_i p_i^r / (1 + sum_j scale_j p_j^r)
+#[derive(Clone, Debug)]
+pub struct MaybeNoWinWeightedCSF { scale: Array<f64, Ix1>, r: f64 }
+
+impl MaybeNoWinWeightedCSF {
+    pub fn new(scale: Array<f64, Ix1>, r: f64) -> Result<Self, DynapaiError> {
+        check_all_positive("scale", &scale)?;
+        check_positive("r", r)?;
+        Ok(Self { scale, r })
+    }
+
+    fn weights(&self, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (
+            &self.scale * &p.mapv(|x| x.powf(self.r)),
+            &self.scale * &p.mapv(|x| self.r * x.powf(self.r - 1.)),
+        )
+    }
+}
+
+impl CSF for MaybeNoWinWeightedCSF {
+    fn q_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.q(p)[i]
+    }
+
+    fn q(&self, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        ratio_q(&self.weights(p).0, 1.)
+    }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        let (a, da) = self.weights(p);
+        ratio_dq_i(i, &a, &da, 1.)
+    }
+
+    fn n(&self) -> Option<usize> {
+        Some(self.scale.len())
+    }
+}
+
+
+// q_i = exp(k p_i) / (c + sum_j exp(k p_j)), computed relative to the largest exponent so it doesn't overflow;
+// c = 0 for the logit CSF, and 1 / scale when nobody might win
+fn logit_q(k: f64, c: f64, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+    let z = p.mapv(|x| k * x);
+    let z_max = z.fold(if c > 0. { c.ln() } else { f64::NEG_INFINITY }, |m, x| m.max(*x));
+    let a = z.mapv(|x| (x - z_max).exp());
+    let rest = if c > 0. { (c.ln() - z_max).exp() } else { 0. };
+    &a / (rest + a.sum())
+}
+
+// dq_i / dp_j = k q_i (1{i = j} - q_j) for both forms
+fn logit_dq_i(k: f64, i: usize, q: Array<f64, Ix1>) -> Array<f64, Ix1> {
+    let mut dq = &q * (-k * q[i]);
+    dq[i] += k * q[i];
+    dq
+}
+
+
+// logit (difference-form) CSF, q_i = exp(k p_i) / sum_j exp(k p_j)
+#[derive(Clone, Debug)]
+pub struct LogitCSF { k: f64 }
+
+impl LogitCSF {
+    pub fn new(k: f64) -> Result<Self, DynapaiError> {
+        check_positive("k", k)?;
+        Ok(Self { k })
+    }
+}
+
+impl CSF for LogitCSF {
+    fn q_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.q(p)[i]
+    }
+
+    fn q(&self, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        logit_q(self.k, 0., p)
+    }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        logit_dq_i(self.k, i, self.q(p))
+    }
+}
+
+
+// logit CSF where nobody wins with probability 1 / (1 + scale * sum_j exp(k p_j))
+#[derive(Clone, Debug)]
+pub struct MaybeNoWinLogitCSF { k: f64, scale: f64 }
+
+impl MaybeNoWinLogitCSF {
+    pub fn new(k: f64, scale: f64) -> Result<Self, DynapaiError> {
+        check_positive("k", k)?;
+        check_positive("scale", scale)?;
+        Ok(Self { k, scale })
+    }
+}
+
+impl CSF for MaybeNoWinLogitCSF {
+    fn q_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.q(p)[i]
+    }
+
+    fn q(&self, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        logit_q(self.k, 1. / self.scale, p)
+    }
+
+    fn dq_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        logit_dq_i(self.k, i, self.q(p))
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum CSFSpec {
+    #[serde(rename = "DefaultCSF")]
+    Default,
+    #[serde(rename = "MaybeNoWinCSF")]
+    MaybeNoWin { scale: f64 },
+    #[serde(rename = "TullockCSF")]
+    Tullock { r: f64 },
+    #[serde(rename = "MaybeNoWinTullockCSF")]
+    MaybeNoWinTullock { r: f64, scale: f64 },
+    #[serde(rename = "LogitCSF")]
+    Logit { k: f64 },
+    #[serde(rename = "MaybeNoWinLogitCSF")]
+    MaybeNoWinLogit { k: f64, scale: f64 },
+    #[serde(rename = "WeightedCSF")]
+    Weighted { w: Vec<f64>, r: f64 },
+    #[serde(rename = "MaybeNoWinWeightedCSF")]
+    MaybeNoWinWeighted { scale: Vec<f64>, r: f64 },
+}
+
+impl Serialize for Box<dyn CSF> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if self.downcast_ref::<DefaultCSF>().is_some() {
+            CSFSpec::Default
+        }
+        else if let Some(x) = self.downcast_ref::<MaybeNoWinCSF>() {
+            CSFSpec::MaybeNoWin { scale: x.scale }
+        }
+        else if let Some(x) = self.downcast_ref::<TullockCSF>() {
+            CSFSpec::Tullock { r: x.r }
+        }
+        else if let Some(x) = self.downcast_ref::<MaybeNoWinTullockCSF>() {
+            CSFSpec::MaybeNoWinTullock { r: x.r, scale: x.scale }
+        }
+        else if let Some(x) = self.downcast_ref::<LogitCSF>() {
+            CSFSpec::Logit { k: x.k }
+        }
+        else if let Some(x) = self.downcast_ref::<MaybeNoWinLogitCSF>() {
+            CSFSpec::MaybeNoWinLogit { k: x.k, scale: x.scale }
+        }
+        else if let Some(x) = self.downcast_ref::<WeightedCSF>() {
+            CSFSpec::Weighted { w: x.w.to_vec(), r: x.r }
+        }
+        else if let Some(x) = self.downcast_ref::<MaybeNoWinWeightedCSF>() {
+            CSFSpec::MaybeNoWinWeighted { scale: x.scale.to_vec(), r: x.r }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this CSF"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn CSF> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match CSFSpec::deserialize(deserializer)? {
+            CSFSpec::Default => Box::new(DefaultCSF),
+            CSFSpec::MaybeNoWin { scale } => Box::new(MaybeNoWinCSF::new(scale).map_err(de::Error::custom)?),
+            CSFSpec::Tullock { r } => Box::new(TullockCSF::new(r).map_err(de::Error::custom)?),
+            CSFSpec::MaybeNoWinTullock { r, scale } => Box::new(MaybeNoWinTullockCSF::new(r, scale).map_err(de::Error::custom)?),
+            CSFSpec::Logit { k } => Box::new(LogitCSF::new(k).map_err(de::Error::custom)?),
+            CSFSpec::MaybeNoWinLogit { k, scale } => Box::new(MaybeNoWinLogitCSF::new(k, scale).map_err(de::Error::custom)?),
+            CSFSpec::Weighted { w, r } => Box::new(WeightedCSF::new(Array::from(w), r).map_err(de::Error::custom)?),
+            CSFSpec::MaybeNoWinWeighted { scale, r } => Box::new(
+                MaybeNoWinWeightedCSF::new(Array::from(scale), r).map_err(de::Error::custom)?
+            ),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::test_utils::*;
+
+    // CSFs where somebody always wins
+    fn always_win() -> Vec<Box<dyn CSF>> {
+        vec![
+            Box::new(DefaultCSF),
+            Box::new(TullockCSF::new(2.).unwrap()),
+            Box::new(WeightedCSF::new(array![1., 2., 0.5], 1.5).unwrap()),
+            Box::new(LogitCSF::new(0.7).unwrap()),
+        ]
+    }
+
+    // CSFs where nobody might win
+    fn maybe_no_win() -> Vec<Box<dyn CSF>> {
+        vec![
+            Box::new(MaybeNoWinCSF::new(0.5).unwrap()),
+            Box::new(MaybeNoWinTullockCSF::new(2., 0.5).unwrap()),
+            Box::new(MaybeNoWinWeightedCSF::new(array![1., 2., 0.5], 1.5).unwrap()),
+            Box::new(MaybeNoWinLogitCSF::new(0.7, 0.5).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn probabilities_add_up() {
+        let p = array![0.5, 2., 1.];
+        for csf in always_win() {
+            let q = csf.q(p.view());
+            assert!(q.iter().all(|x| *x > 0.));
+            assert_close([&q.sum()], [&1.], 1e-12);
+        }
+        for csf in maybe_no_win() {
+            let q = csf.q(p.view());
+            assert!(q.iter().all(|x| *x > 0.));
+            assert!(q.sum() < 1.);
+        }
+    }
+
+    #[test]
+    fn grads_match_finite_differences() {
+        let p = array![0.5, 2., 1.];
+        for csf in always_win().into_iter().chain(maybe_no_win()) {
+            let q = csf.q(p.view());
+            for i in 0..3 {
+                assert_close([&csf.q_i(i, p.view())], [&q[i]], 1e-12);
+                assert_close(&csf.dq_i(i, p.view()), &fd_grad(p.view(), |p| csf.q_i(i, p)), 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn logit_does_not_overflow() {
+        let p = array![1000., 999.];
+        let csf = LogitCSF::new(1.).unwrap();
+        let q = csf.q(p.view());
+        assert_close(&q, &[1. / (1. + (-1f64).exp()), 1. / (1. + 1f64.exp())], 1e-12);
+        assert!(csf.dq_i(0, p.view()).iter().all(|x| x.is_finite()));
+        // at that size the chance that nobody wins is negligible
+        let q = MaybeNoWinLogitCSF::new(1., 1.).unwrap().q(p.view());
+        assert!(q.iter().all(|x| x.is_finite()));
+        assert_close([&q.sum()], [&1.], 1e-12);
+        // and for very negative inputs nobody wins
+        let q = MaybeNoWinLogitCSF::new(1., 1.).unwrap().q((-p).view());
+        assert!(q.iter().all(|x| *x >= 0. && *x < 1e-300));
+    }
+
+    #[test]
+    fn modular_payoff_checks_csf_n() {
+        let res = ModularPayoff::<Actions>::new(
+            Box::new(DefaultProd::new(array![1., 1.5], array![0.5, 0.6], array![1.2, 0.8], array![0.4, 0.5]).unwrap()),
+            Box::new(WinnerOnlyRisk { theta: array![0.5, 0.7] }),
+            Box::new(WeightedCSF::new(array![1., 2., 0.5], 1.).unwrap()),
+            Box::new(LinearReward::default(2)),
+            Box::new(ConstantDisasterCost { d: array![1., 0.8] }),
+            Box::new(BasicFixedCost::new(array![[0.1, 0.1], [0.12, 0.08]]).unwrap()),
+        );
+        assert!(matches!(res, Err(DynapaiError::DimensionMismatch(_))));
+        // symmetric CSFs work for any number of players
+        basic_payoff(Box::new(TullockCSF::new(2.).unwrap()));
+        basic_payoff(Box::new(WeightedCSF::new(array![1., 2.], 1.).unwrap()));
+    }
 }
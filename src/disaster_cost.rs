@@ -1,12 +1,24 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{clone_trait_object, DynClone};
-use numpy::ndarray::{Array, ArrayView, Ix1};
+use ndarray::{Array, ArrayView, Ix1, Ix2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
+
+use crate::error::DynapaiError;
+use crate::serialize::{from_rows, to_rows};
 
 pub trait DisasterCost: DynClone + Downcast + Send + Sync {
-    fn d_i(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> f64;
-    fn d(&self, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
-        Array::from_iter((0..s.len()).map(|i| self.d_i(i, s, p)))
+    // d_ij, the cost to player i of a disaster caused by player j, the winner of the contest
+    fn d_ij(&self, i: usize, j: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> f64;
+    // costs to player i for each player that might cause a disaster
+    fn d(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        Array::from_iter((0..p.len()).map(|j| self.d_ij(i, j, s, p)))
     }
+    // gradients of d_ij wrt s and p
+    fn dd_ij(&self, i: usize, j: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>);
+    // cost to player i of the disaster when nobody wins the contest, which ModularPayoff counts as a disaster
+    fn d_none(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> f64;
+    // gradients of d_none wrt s and p
+    fn dd_none(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>);
 
     fn n(&self) -> usize;
 }
@@ -15,16 +27,30 @@ clone_trait_object!(DisasterCost);
 impl_downcast!(DisasterCost);
 
 
+// player i pays d_i whoever causes the disaster
 #[derive(Clone)]
 pub struct ConstantDisasterCost {
     pub d: Array<f64, Ix1>,
 }
 
 impl DisasterCost for ConstantDisasterCost {
-    fn d_i(&self, i: usize, _s: ArrayView<f64, Ix1>, _p: ArrayView<f64, Ix1>) -> f64 {
+    fn d_ij(&self, i: usize, _j: usize, _s: ArrayView<f64, Ix1>, _p: ArrayView<f64, Ix1>) -> f64 {
+        self.d[i]
+    }
+
+    fn dd_ij(&self, _i: usize, _j: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (Array::zeros(s.len()), Array::zeros(p.len()))
+    }
+
+    // also d_i, so a disaster costs the same whether or not anybody wins
+    fn d_none(&self, i: usize, _s: ArrayView<f64, Ix1>, _p: ArrayView<f64, Ix1>) -> f64 {
         self.d[i]
     }
 
+    fn dd_none(&self, _i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (Array::zeros(s.len()), Array::zeros(p.len()))
+    }
+
     fn n(&self) -> usize {
         self.d.len()
     }
@@ -37,3 +63,220 @@ impl ConstantDisasterCost {
         }
     }
 }
+
+
+// a full matrix of costs, d_ij for player i if player j causes the disaster,
+// and d_none_i for player i if nobody wins
+#[derive(Clone)]
+pub struct MatrixDisasterCost {
+    pub d: Array<f64, Ix2>,
+    pub d_none: Array<f64, Ix1>,
+}
+
+impl MatrixDisasterCost {
+    // d_none defaults to the diagonal of d, the cost of causing the disaster yourself
+    pub fn new(d: Array<f64, Ix2>, d_none: Option<Array<f64, Ix1>>) -> Result<Self, DynapaiError> {
+        if d.nrows() != d.ncols() {
+            return Err(DynapaiError::DimensionMismatch("When creating MatrixDisasterCost: d must be a square matrix".to_string()));
+        }
+        let d_none = d_none.unwrap_or_else(|| d.diag().to_owned());
+        if d_none.len() != d.nrows() {
+            return Err(DynapaiError::DimensionMismatch("When creating MatrixDisasterCost: d_none must have one entry per row of d".to_string()));
+        }
+        Ok(MatrixDisasterCost { d, d_none })
+    }
+}
+
+impl DisasterCost for MatrixDisasterCost {
+    fn d_ij(&self, i: usize, j: usize, _s: ArrayView<f64, Ix1>, _p: ArrayView<f64, Ix1>) -> f64 {
+        self.d[[i, j]]
+    }
+
+    fn dd_ij(&self, _i: usize, _j: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (Array::zeros(s.len()), Array::zeros(p.len()))
+    }
+
+    fn d_none(&self, i: usize, _s: ArrayView<f64, Ix1>, _p: ArrayView<f64, Ix1>) -> f64 {
+        self.d_none[i]
+    }
+
+    fn dd_none(&self, _i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (Array::zeros(s.len()), Array::zeros(p.len()))
+    }
+
+    fn n(&self) -> usize {
+        self.d.nrows()
+    }
+}
+
+
+// more capable systems cause bigger disasters, d_ij = d_i p_j^r;
+// with no system to scale it, the cost if nobody wins is d_none_i
+#[derive(Clone)]
+pub struct CapabilityDisasterCost {
+    d: Array<f64, Ix1>,
+    r: f64,
+    d_none: Array<f64, Ix1>,
+}
+
+impl CapabilityDisasterCost {
+    // d_none defaults to d
+    pub fn new(d: Array<f64, Ix1>, r: f64, d_none: Option<Array<f64, Ix1>>) -> Result<Self, DynapaiError> {
+        if r < 0. {
+            return Err(DynapaiError::InvalidParameter("When creating CapabilityDisasterCost: r must be nonnegative".to_string()));
+        }
+        let d_none = d_none.unwrap_or_else(|| d.clone());
+        if d_none.len() != d.len() {
+            return Err(DynapaiError::DimensionMismatch("When creating CapabilityDisasterCost: d_none must have the same length as d".to_string()));
+        }
+        Ok(CapabilityDisasterCost { d, r, d_none })
+    }
+}
+
+impl DisasterCost for CapabilityDisasterCost {
+    fn d_ij(&self, i: usize, j: usize, _s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> f64 {
+        self.d[i] * p[j].powf(self.r)
+    }
+
+    fn dd_ij(&self, i: usize, j: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let mut dp = Array::zeros(p.len());
+        // r = 0 is constant, even at p = 0
+        if self.r != 0. {
+            dp[j] = self.d[i] * self.r * p[j].powf(self.r - 1.);
+        }
+        (Array::zeros(s.len()), dp)
+    }
+
+    fn d_none(&self, i: usize, _s: ArrayView<f64, Ix1>, _p: ArrayView<f64, Ix1>) -> f64 {
+        self.d_none[i]
+    }
+
+    fn dd_none(&self, _i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (Array::zeros(s.len()), Array::zeros(p.len()))
+    }
+
+    fn n(&self) -> usize {
+        self.d.len()
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum DisasterCostSpec {
+    #[serde(rename = "ConstantDisasterCost")]
+    Constant { d: Vec<f64> },
+    // d_none is optional, with the same default as in the constructors
+    #[serde(rename = "MatrixDisasterCost")]
+    Matrix { d: Vec<Vec<f64>>, #[serde(default)] d_none: Option<Vec<f64>> },
+    #[serde(rename = "CapabilityDisasterCost")]
+    Capability { d: Vec<f64>, r: f64, #[serde(default)] d_none: Option<Vec<f64>> },
+}
+
+impl Serialize for Box<dyn DisasterCost> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if let Some(x) = self.downcast_ref::<ConstantDisasterCost>() {
+            DisasterCostSpec::Constant { d: x.d.to_vec() }
+        }
+        else if let Some(x) = self.downcast_ref::<MatrixDisasterCost>() {
+            DisasterCostSpec::Matrix { d: to_rows(&x.d), d_none: Some(x.d_none.to_vec()) }
+        }
+        else if let Some(x) = self.downcast_ref::<CapabilityDisasterCost>() {
+            DisasterCostSpec::Capability { d: x.d.to_vec(), r: x.r, d_none: Some(x.d_none.to_vec()) }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this DisasterCost"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn DisasterCost> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match DisasterCostSpec::deserialize(deserializer)? {
+            DisasterCostSpec::Constant { d } => Box::new(ConstantDisasterCost { d: Array::from(d) }),
+            DisasterCostSpec::Matrix { d, d_none } => Box::new(
+                MatrixDisasterCost::new(from_rows(d).map_err(de::Error::custom)?, d_none.map(Array::from)).map_err(de::Error::custom)?
+            ),
+            DisasterCostSpec::Capability { d, r, d_none } => Box::new(
+                CapabilityDisasterCost::new(Array::from(d), r, d_none.map(Array::from)).map_err(de::Error::custom)?
+            ),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::test_utils::*;
+
+    fn costs() -> Vec<Box<dyn DisasterCost>> {
+        vec![
+            Box::new(ConstantDisasterCost { d: array![1., 0.8] }),
+            Box::new(MatrixDisasterCost::new(array![[1., 2.], [0.5, 0.8]], Some(array![1.5, 0.3])).unwrap()),
+            Box::new(CapabilityDisasterCost::new(array![1., 0.8], 1.5, None).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn matrix_cost_must_be_square() {
+        assert!(matches!(MatrixDisasterCost::new(Array::zeros((2, 3)), None), Err(DynapaiError::DimensionMismatch(_))));
+        assert!(matches!(MatrixDisasterCost::new(Array::zeros((2, 2)), Some(array![1.])), Err(DynapaiError::DimensionMismatch(_))));
+        // d_none defaults to the diagonal
+        let cost = MatrixDisasterCost::new(array![[1., 2.], [0.5, 0.8]], None).unwrap();
+        assert_eq!(cost.d_none, array![1., 0.8]);
+    }
+
+    #[test]
+    fn capability_cost_needs_nonnegative_r() {
+        assert!(matches!(CapabilityDisasterCost::new(array![1., 0.8], -0.5, None), Err(DynapaiError::InvalidParameter(_))));
+        // r = 0 is constant, including at p = 0
+        let cost = CapabilityDisasterCost::new(array![1., 0.8], 0., None).unwrap();
+        let (s, p) = (array![0.5, 2.], array![0., 0.7]);
+        for j in 0..2 {
+            assert_eq!(cost.d_ij(1, j, s.view(), p.view()), 0.8);
+            let (ds, dp) = cost.dd_ij(1, j, s.view(), p.view());
+            assert_eq!(ds, Array::<f64, Ix1>::zeros(2));
+            assert_eq!(dp, Array::<f64, Ix1>::zeros(2));
+        }
+    }
+
+    #[test]
+    fn grads_match_finite_differences() {
+        let (s, p) = (array![0.5, 2.], array![1.5, 0.7]);
+        for cost in costs() {
+            for i in 0..2 {
+                for j in 0..2 {
+                    let (ds, dp) = cost.dd_ij(i, j, s.view(), p.view());
+                    assert_close(&ds, &fd_grad(s.view(), |s| cost.d_ij(i, j, s, p.view())), 1e-6);
+                    assert_close(&dp, &fd_grad(p.view(), |p| cost.d_ij(i, j, s.view(), p)), 1e-6);
+                }
+                let (ds, dp) = cost.dd_none(i, s.view(), p.view());
+                assert_close(&ds, &fd_grad(s.view(), |s| cost.d_none(i, s, p.view())), 1e-6);
+                assert_close(&dp, &fd_grad(p.view(), |p| cost.d_none(i, s.view(), p)), 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn payoff_weights_costs_by_winner() {
+        // s = xs, p = xp, sigma_j = s_j / (1 + s_j), q_j = p_j / sum p, and no rewards or costs,
+        // so u_i = -sum_j q_j (1 - sigma_j) d_ij
+        let payoff = ModularPayoff::<Actions>::new(
+            Box::new(DefaultProd::new(array![1., 1.], array![1., 1.], array![1., 1.], array![1., 1.]).unwrap()),
+            Box::new(WinnerOnlyRisk { theta: array![0., 0.] }),
+            Box::new(DefaultCSF),
+            Box::new(LinearReward::new(array![0., 0.], array![0., 0.], array![0., 0.], array![0., 0.]).unwrap()),
+            Box::new(MatrixDisasterCost::new(array![[1., 2.], [0.5, 0.8]], None).unwrap()),
+            Box::new(BasicFixedCost::new(array![[0., 0.], [0., 0.]]).unwrap()),
+        ).unwrap();
+        // sigma = [0.5, 0.75] and q = [0.25, 0.75]
+        let actions = Actions::from_array(array![[1., 1.], [3., 3.]]).unwrap();
+        // u_0 = -(0.25 * 0.5 * 1 + 0.75 * 0.25 * 2), u_1 = -(0.25 * 0.5 * 0.5 + 0.75 * 0.25 * 0.8)
+        assert_close(&payoff.u(&actions), &array![-0.5, -0.2125], 1e-12);
+    }
+}
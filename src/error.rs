@@ -0,0 +1,113 @@
+use std::fmt;
+
+
+// error type returned by the crate's fallible constructors, solvers, and file io;
+// each variant holds a message describing what went wrong
+#[derive(Clone, Debug, PartialEq)]
+pub enum DynapaiError {
+    // arrays or components whose sizes don't agree with each other
+    DimensionMismatch(String),
+    // a parameter outside its allowed range
+    InvalidParameter(String),
+    // an object of a different action type than the one expected
+    WrongActionType(String),
+    // an optimizer failed, or a solver couldn't produce a result
+    SolverFailure(String),
+    // a payoff evaluated to nan or infinity
+    NonFinitePayoff(String),
+    // reading, writing, or (de)serializing models and results
+    Serialization(String),
+    // stopped early by a SolverOptions callback
+    Cancelled(String),
+}
+
+impl fmt::Display for DynapaiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynapaiError::DimensionMismatch(msg) => write!(f, "Dimension mismatch: {}", msg),
+            DynapaiError::InvalidParameter(msg) => write!(f, "Invalid parameter: {}", msg),
+            DynapaiError::WrongActionType(msg) => write!(f, "Wrong action type: {}", msg),
+            DynapaiError::SolverFailure(msg) => write!(f, "Solver failure: {}", msg),
+            DynapaiError::NonFinitePayoff(msg) => write!(f, "Non-finite payoff: {}", msg),
+            DynapaiError::Serialization(msg) => write!(f, "Serialization error: {}", msg),
+            DynapaiError::Cancelled(msg) => write!(f, "Cancelled: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DynapaiError {}
+
+// errors coming out of argmin executors are solver failures,
+// unless they were raised as a DynapaiError from inside an objective
+impl From<argmin::core::Error> for DynapaiError {
+    fn from(e: argmin::core::Error) -> Self {
+        match e.downcast::<DynapaiError>() {
+            Ok(e) => e,
+            Err(e) => DynapaiError::SolverFailure(e.to_string()),
+        }
+    }
+}
+
+impl From<ndarray::ShapeError> for DynapaiError {
+    fn from(e: ndarray::ShapeError) -> Self {
+        DynapaiError::DimensionMismatch(e.to_string())
+    }
+}
+
+// checks that all payoffs are finite, naming where they came from in the error
+pub fn check_finite<'a, I>(payoffs: I, context: &str) -> Result<(), DynapaiError>
+where I: IntoIterator<Item = &'a f64>
+{
+    match payoffs.into_iter().position(|u| !u.is_finite()) {
+        Some(i) => Err(DynapaiError::NonFinitePayoff(format!("{}: payoff of player {} is not finite", context, i))),
+        None => Ok(()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array, array};
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn messages_name_the_kind_of_error() {
+        let e = DynapaiError::InvalidParameter("theta must be positive".to_string());
+        assert_eq!(e.to_string(), "Invalid parameter: theta must be positive");
+        let e = DynapaiError::Cancelled("cancelled on iteration 3".to_string());
+        assert_eq!(e.to_string(), "Cancelled: cancelled on iteration 3");
+    }
+
+    #[test]
+    fn argmin_errors_keep_dynapai_errors() {
+        let inner = DynapaiError::NonFinitePayoff("player 0".to_string());
+        assert_eq!(DynapaiError::from(argmin::core::Error::new(inner.clone())), inner);
+        assert_eq!(
+            DynapaiError::from(argmin::core::Error::msg("line search failed")),
+            DynapaiError::SolverFailure("line search failed".to_string())
+        );
+    }
+
+    #[test]
+    fn shape_errors_are_dimension_mismatches() {
+        let e: DynapaiError = Array::from_vec(vec![1., 2., 3.]).into_shape((2, 2)).unwrap_err().into();
+        assert!(matches!(e, DynapaiError::DimensionMismatch(_)));
+    }
+
+    #[test]
+    fn constructors_return_errors_instead_of_panicking() {
+        let res = DefaultProd::new(array![1., 1.], array![0.5], array![1., 1.], array![0.5, 0.5]);
+        assert!(matches!(res, Err(DynapaiError::DimensionMismatch(_))));
+    }
+
+    #[test]
+    fn check_finite_names_the_player() {
+        assert_eq!(check_finite(&[1., 2.], "test"), Ok(()));
+        assert_eq!(
+            check_finite(&[1., f64::NAN], "test"),
+            Err(DynapaiError::NonFinitePayoff("test: payoff of player 1 is not finite".to_string()))
+        );
+    }
+}
@@ -1,39 +1,68 @@
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
 pub mod aggregator;
 pub mod cost_func;
 pub mod csf;
 pub mod disaster_cost;
+pub mod error;
+pub mod markov;
+pub mod mixed;
+pub mod multistart;
 pub mod payoff_func;
+pub mod planner;
 pub mod prod_func;
+pub mod report;
 pub mod reward_func;
 pub mod risk_func;
+pub mod saved;
+pub mod serialize;
 pub mod solve;
+pub mod stackelberg;
 pub mod state;
 pub mod strategies;
+pub mod verify;
 
+#[cfg(feature = "python")]
 pub mod pybindings;
 pub mod utils;
+#[cfg(test)]
+mod test_utils;
 
 pub mod prelude;
 
+#[cfg(feature = "python")]
 pub use pybindings as py;
 
 /// A Python module implemented in Rust.
+#[cfg(feature = "python")]
 #[pymodule]
-fn dynapai(_py: Python, m: &PyModule) -> PyResult<()> {
+fn dynapai(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<py::PyAggregator>()?;
     m.add_class::<py::PyActions>()?;
+    m.add_class::<py::PyCancelToken>()?;
     m.add_class::<py::PyCostFunc>()?;
     m.add_class::<py::PyCSF>()?;
+    m.add_class::<py::PyDisasterCost>()?;
+    m.add_class::<py::PyEquilibrium>()?;
     m.add_class::<py::PyProdFunc>()?;
+    m.add_class::<py::PyMarkovPolicy>()?;
+    m.add_class::<py::PyMixedSolverResult>()?;
+    m.add_class::<py::PyMultistartResult>()?;
     m.add_class::<py::PyPayoffFunc>()?;
+    m.add_class::<py::PyPlannerResult>()?;
     m.add_class::<py::PyRewardFunc>()?;
     m.add_class::<py::PyRiskFunc>()?;
+    m.add_class::<py::PySavedResult>()?;
     m.add_class::<py::PyScenario>()?;
     m.add_class::<py::PyState>()?;
     m.add_class::<py::PySolverOptions>()?;
     m.add_class::<py::PySolverResult>()?;
+    m.add_class::<py::PySolverTrace>()?;
+    m.add_class::<py::PyStackelbergResult>()?;
+    m.add_class::<py::PyVerification>()?;
     m.add_class::<py::PyStrategies>()?;
+    m.add_function(wrap_pyfunction!(py::unpickle, m)?)?;
+    py::add_exceptions(py, m)?;
     Ok(())
 }
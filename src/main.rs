@@ -0,0 +1,400 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use ndarray::Ix3;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use dynapai::prelude::*;
+
+// command-line runner for model files, in toml or json (chosen by file extension), of the form
+//
+//     atype = "invest"        # one of basic, invest, sharing
+//     t = 10                  # number of periods, for solve and sweep
+//
+//     [options]               # optional, any of the fields of OptionsConfig
+//     iters = 100
+//     method = "lbfgs"         # or "nelder_mead"
+//
+//     [aggregator]            # for solve and evaluate, as written by serialize::to_toml
+//     type = "DynStateDiscounter"
+//     ...
+//
+//     [[scenario]]            # for sweep, one table per aggregator
+//     type = "DynStateDiscounter"
+//     ...
+
+#[derive(Parser)]
+#[command(name = "dynapai", about = "Solve dynamic AI race models described in model files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Solve the model's aggregator, writing a saved result and summary.csv to OUT")]
+    Solve {
+        model: PathBuf,
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    #[command(about = "Solve each of the model's scenarios, writing saved results to OUT/<k> and summary.csv to OUT")]
+    Sweep {
+        model: PathBuf,
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    #[command(about = "Compute players' payoffs under the model's aggregator for strategies in an npy file of shape (t, n, nparams)")]
+    Evaluate {
+        model: PathBuf,
+        strategies: PathBuf,
+        #[arg(short, long, help = "csv file to write, prints to stdout if not given")]
+        out: Option<PathBuf>,
+    },
+    #[command(about = "Check whether a saved result is an equilibrium")]
+    Verify {
+        result: PathBuf,
+        #[arg(long, default_value_t = VerifyOptions::default().starts)]
+        starts: usize,
+        #[arg(long, default_value_t = VerifyOptions::default().spread)]
+        spread: f64,
+        #[arg(short, long, help = "csv file to write, prints to stdout if not given")]
+        out: Option<PathBuf>,
+    },
+}
+
+
+#[derive(Deserialize)]
+struct Header {
+    atype: String,
+}
+
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "Box<dyn Aggregator<A>>: Deserialize<'de>"))]
+struct Model<A: ActionType + Clone + 'static> {
+    t: Option<usize>,
+    #[serde(default)]
+    options: OptionsConfig,
+    aggregator: Option<Box<dyn Aggregator<A>>>,
+    #[serde(default)]
+    scenario: Vec<Box<dyn Aggregator<A>>>,
+}
+
+// solver options as given in model files, with the same defaults as the python SolverOptions
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct OptionsConfig {
+    iters: u64,
+    tol: f64,
+    method: SolverMethod,
+    update: UpdateMode,
+    damping: f64,
+    nm_options: NMOptions,
+    lbfgs_options: LBFGSOptions,
+    verify: Option<VerifyOptions>,
+    parallel: bool,
+    threads: Option<usize>,
+    // seeds the initial guess and the solver's randomness, so runs are reproducible
+    seed: Option<u64>,
+    // initial guesses are drawn from lognormal(init_mu, init_sigma)
+    init_mu: f64,
+    init_sigma: f64,
+}
+
+impl Default for OptionsConfig {
+    fn default() -> Self {
+        OptionsConfig {
+            iters: 200,
+            tol: 1e-6,
+            method: SolverMethod::NelderMead,
+            update: UpdateMode::Jacobi,
+            damping: 1.0,
+            nm_options: NMOptions::default(),
+            lbfgs_options: LBFGSOptions::default(),
+            verify: None,
+            parallel: true,
+            threads: None,
+            seed: None,
+            init_mu: INIT_MU,
+            init_sigma: INIT_SIGMA,
+        }
+    }
+}
+
+impl OptionsConfig {
+    // options for solving from a random guess with t periods
+    fn to_options<A: ActionType + Clone>(&self, t: usize) -> SolverOptions<A> {
+        SolverOptions {
+            init_guess: InitGuess::Random { t, seed: self.seed, mu: self.init_mu, sigma: self.init_sigma },
+            iters: self.iters,
+            tol: self.tol,
+            method: self.method,
+            update: self.update,
+            damping: self.damping,
+            nm_options: self.nm_options.clone(),
+            lbfgs_options: self.lbfgs_options.clone(),
+            trace: false,
+            verify: self.verify.clone(),
+            hist_size: 10,
+            mixed_samples: 100,
+            mixed_tol: MIXED_TOL,
+            parallel: self.parallel,
+            threads: self.threads,
+            seed: self.seed,
+            callback: None,
+        }
+    }
+}
+
+
+fn read_file<T: DeserializeOwned>(path: &Path) -> Result<T, DynapaiError> {
+    let s = fs::read_to_string(path).map_err(|e| DynapaiError::Serialization(format!("Error when reading {}: {}", path.display(), e)))?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => from_json(&s),
+        _ => from_toml(&s),
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    }
+    else {
+        s.to_string()
+    }
+}
+
+// writes to stdout if path is None
+fn write_csv(path: Option<&Path>, header: &[String], rows: &[Vec<String>]) -> Result<(), DynapaiError> {
+    let lines = std::iter::once(header).chain(rows.iter().map(|row| row.as_slice()))
+        .map(|row| row.iter().map(|s| csv_field(s)).collect::<Vec<_>>().join(",") + "\n")
+        .collect::<String>();
+    match path {
+        Some(path) => fs::write(path, lines).map_err(|e| DynapaiError::Serialization(format!("Error when writing {}: {}", path.display(), e))),
+        None => {
+            print!("{}", lines);
+            Ok(())
+        },
+    }
+}
+
+// one row of summary.csv; payoffs are empty if solving failed
+struct Summary {
+    status: String,
+    epsilon: Option<f64>,
+    payoffs: Vec<f64>,
+}
+
+fn write_summary(path: &Path, summaries: &[Summary]) -> Result<(), DynapaiError> {
+    let n = summaries.iter().map(|s| s.payoffs.len()).max().unwrap_or(0);
+    let header = ["scenario", "status", "epsilon"].iter().map(|s| s.to_string())
+        .chain((0..n).map(|i| format!("payoff_{}", i)))
+        .collect::<Vec<_>>();
+    let rows = summaries.iter().enumerate().map(|(k, s)|
+        [k.to_string(), s.status.clone(), s.epsilon.map_or(String::new(), |e| e.to_string())].into_iter()
+            .chain((0..n).map(|i| s.payoffs.get(i).map_or(String::new(), |u| u.to_string())))
+            .collect()
+    ).collect::<Vec<_>>();
+    write_csv(Some(&path.join("summary.csv")), &header, &rows)
+}
+
+fn missing(field: &str) -> DynapaiError {
+    DynapaiError::Serialization(format!("Model file has no {}", field))
+}
+
+// solves agg and saves the result to out
+fn solve_and_save<A>(agg: &dyn Aggregator<A>, options: &SolverOptions<A>, out: &Path) -> Result<Summary, DynapaiError>
+where A: ActionType + Clone + 'static, Box<dyn Aggregator<A>>: Serialize
+{
+    let res = solve(agg, options)?;
+    let strategies = res.strategies.ok_or_else(|| DynapaiError::SolverFailure(format!("No strategies found, status was {}", res.status)))?;
+    let saved = SavedResult::new(res.status, strategies, dyn_clone::clone_box(agg), options.clone())?;
+    saved.save(out)?;
+    Ok(Summary {
+        status: saved.status,
+        epsilon: res.verification.map(|v| v.epsilon()),
+        payoffs: saved.payoffs.to_vec(),
+    })
+}
+
+fn run<A>(command: Command) -> Result<(), DynapaiError>
+where A: ActionType + Clone + 'static, Box<dyn Aggregator<A>>: Serialize + DeserializeOwned
+{
+    match command {
+        Command::Solve { model, out } => {
+            let model: Model<A> = read_file(&model)?;
+            let agg = model.aggregator.ok_or_else(|| missing("aggregator"))?;
+            let t = model.t.ok_or_else(|| missing("t"))?;
+            let summary = solve_and_save(agg.as_ref(), &model.options.to_options(t), &out)?;
+            println!("{}", summary.status);
+            write_summary(&out, &[summary])
+        },
+        Command::Sweep { model, out } => {
+            let model: Model<A> = read_file(&model)?;
+            if model.scenario.is_empty() {
+                return Err(missing("scenarios"));
+            }
+            let t = model.t.ok_or_else(|| missing("t"))?;
+            let options = model.options.to_options(t);
+            // scenarios share one thread pool, rather than each solve creating its own
+            let summaries = with_threads(options.threads, || Ok(map_range(model.scenario.len(), options.parallel, |k| {
+                solve_and_save(model.scenario[k].as_ref(), &options, &out.join(k.to_string()))
+                    .unwrap_or_else(|e| Summary { status: e.to_string(), epsilon: None, payoffs: Vec::new() })
+            })))?;
+            for (k, summary) in summaries.iter().enumerate() {
+                println!("scenario {}: {}", k, summary.status);
+            }
+            write_summary(&out, &summaries)
+        },
+        Command::Evaluate { model, strategies, out } => {
+            let model: Model<A> = read_file(&model)?;
+            let agg = model.aggregator.ok_or_else(|| missing("aggregator"))?;
+            let strategies = Strategies::<A>::from_data(read_npy::<Ix3>(&strategies)?)?;
+            if strategies.n() != agg.n() {
+                return Err(DynapaiError::DimensionMismatch(format!("Strategies have {} players, but aggregator has {}", strategies.n(), agg.n())));
+            }
+            let rows = agg.u(&strategies).iter().enumerate()
+                .map(|(i, u)| vec![i.to_string(), u.to_string()])
+                .collect::<Vec<_>>();
+            write_csv(out.as_deref(), &["player".to_string(), "payoff".to_string()], &rows)
+        },
+        Command::Verify { result, starts, spread, out } => {
+            let saved = SavedResult::<A>::load(&result)?;
+            let verification = saved.verify(&VerifyOptions { starts, spread })?;
+            eprintln!("epsilon = {}", verification.epsilon());
+            let rows = verification.payoffs.iter().zip(verification.gains.iter()).enumerate()
+                .map(|(i, (u, gain))| vec![i.to_string(), u.to_string(), gain.to_string()])
+                .collect::<Vec<_>>();
+            write_csv(out.as_deref(), &["player".to_string(), "payoff".to_string(), "gain".to_string()], &rows)
+        },
+    }
+}
+
+fn main() {
+    let command = Cli::parse().command;
+    let atype = match &command {
+        Command::Solve { model, .. } | Command::Sweep { model, .. } | Command::Evaluate { model, .. } => {
+            read_file::<Header>(model).map(|h| h.atype)
+        },
+        Command::Verify { result, .. } => saved_atype(result),
+    };
+    let res = atype.and_then(|atype| match atype.as_str() {
+        "basic" => run::<Actions>(command),
+        "invest" => run::<InvestActions>(command),
+        "sharing" => run::<SharingActions>(command),
+        _ => Err(DynapaiError::InvalidParameter(format!("Invalid atype: {}, expected one of \"basic\", \"invest\", \"sharing\"", atype))),
+    });
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dynapai-cli-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn aggregator() -> Box<dyn Aggregator<Actions>> {
+        let payoff = ModularPayoff::new(
+            Box::new(DefaultProd::new(array![1., 1.5], array![0.5, 0.6], array![1.2, 0.8], array![0.4, 0.5]).unwrap()),
+            Box::new(WinnerOnlyRisk::new(2, 0.5).unwrap()),
+            Box::new(DefaultCSF),
+            Box::new(LinearReward::default(2)),
+            Box::new(ConstantDisasterCost::new(2, 1.)),
+            Box::new(BasicFixedCost::new(array![[0.1, 0.1], [0.12, 0.08]]).unwrap()),
+        ).unwrap();
+        Box::new(FixedStateDiscounter::new(Box::new(CommonBeliefs(Box::new(payoff))), array![0.9, 0.9]).unwrap())
+    }
+
+    fn read_csv(path: &Path) -> Vec<Vec<String>> {
+        fs::read_to_string(path).unwrap().lines()
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn solve_evaluate_and_verify() {
+        let dir = temp_dir("solve");
+        let agg = aggregator();
+        let model = serde_json::json!({
+            "atype": "basic",
+            "t": 1,
+            "options": { "method": "lbfgs", "seed": 0, "parallel": false, "verify": { "starts": 2 } },
+            "aggregator": agg,
+        });
+        // the same model as json and as toml
+        let json_path = dir.join("model.json");
+        fs::write(&json_path, to_json(&model).unwrap()).unwrap();
+        let toml_path = dir.join("model.toml");
+        fs::write(&toml_path, to_toml(&model).unwrap()).unwrap();
+        assert_eq!(read_file::<Header>(&toml_path).unwrap().atype, "basic");
+
+        for (k, path) in [json_path, toml_path].into_iter().enumerate() {
+            let out = dir.join(format!("out{}", k));
+            run::<Actions>(Command::Solve { model: path.clone(), out: out.clone() }).unwrap();
+            let summary = read_csv(&out.join("summary.csv"));
+            assert_eq!(summary[0], ["scenario", "status", "epsilon", "payoff_0", "payoff_1"]);
+            assert!(summary[1][1].starts_with("Exited"), "{:?}", summary);
+            assert!(summary[1][2].parse::<f64>().unwrap() < 1e-6);
+
+            let saved = SavedResult::<Actions>::load(&out).unwrap();
+            assert_eq!(saved.seed(), Some(0));
+            let evaluated = out.join("payoffs.csv");
+            run::<Actions>(Command::Evaluate {
+                model: path, strategies: out.join("strategies.npy"), out: Some(evaluated.clone()),
+            }).unwrap();
+            let rows = read_csv(&evaluated);
+            for i in 0..2 {
+                assert_eq!(rows[i + 1][1].parse::<f64>().unwrap(), saved.payoffs[i]);
+            }
+
+            let verified = out.join("verify.csv");
+            run::<Actions>(Command::Verify { result: out, starts: 2, spread: 1., out: Some(verified.clone()) }).unwrap();
+            let rows = read_csv(&verified);
+            assert_eq!(rows[0], ["player", "payoff", "gain"]);
+            assert!(rows[1..].iter().all(|row| row[2].parse::<f64>().unwrap() < 1e-6));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sweep_solves_every_scenario() {
+        let dir = temp_dir("sweep");
+        let model = serde_json::json!({
+            "atype": "basic",
+            "t": 1,
+            "options": { "method": "lbfgs", "seed": 0 },
+            "scenario": [aggregator(), aggregator()],
+        });
+        let path = dir.join("model.json");
+        fs::write(&path, to_json(&model).unwrap()).unwrap();
+        run::<Actions>(Command::Sweep { model: path, out: dir.join("out") }).unwrap();
+        let summary = read_csv(&dir.join("out").join("summary.csv"));
+        assert_eq!(summary.len(), 3);
+        assert!(dir.join("out").join("1").join("strategies.npy").exists());
+
+        // a model with no aggregator can't be solved
+        let path = dir.join("empty.json");
+        fs::write(&path, r#"{"atype": "basic", "t": 1}"#).unwrap();
+        assert!(run::<Actions>(Command::Solve { model: path, out: dir.join("empty") }).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,365 @@
+use ndarray::{Array, ArrayView, Axis, Ix1, Ix2, Ix3, s, stack};
+use argmin::core::{CostFunction, Executor};
+use argmin::solver::neldermead::NelderMead;
+use serde::{Deserialize, Deserializer, Serialize, de};
+
+use crate::prelude::*;
+
+
+// rectilinear grid over the state vector (see State::state_vec);
+// written as the list of coordinate arrays
+#[derive(Clone, Debug, Serialize)]
+#[serde(transparent)]
+pub struct StateGrid {
+    coords: Vec<Array<f64, Ix1>>,
+}
+
+impl StateGrid {
+    pub fn new(coords: Vec<Array<f64, Ix1>>) -> Result<Self, DynapaiError> {
+        if coords.iter().any(|c| c.is_empty()) {
+            return Err(DynapaiError::DimensionMismatch("When creating new StateGrid: each coordinate array must be nonempty".to_string()));
+        }
+        if coords.iter().any(|c| c.windows(2).into_iter().any(|w| w[1] <= w[0])) {
+            return Err(DynapaiError::InvalidParameter("When creating new StateGrid: coordinates must be strictly increasing".to_string()));
+        }
+        Ok(StateGrid { coords })
+    }
+
+    pub fn coords(&self) -> &Vec<Array<f64, Ix1>> {
+        &self.coords
+    }
+
+    pub fn dim(&self) -> usize {
+        self.coords.len()
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        self.coords.iter().map(|c| c.len()).collect()
+    }
+
+    pub fn size(&self) -> usize {
+        self.coords.iter().map(|c| c.len()).product()
+    }
+
+    // k-th grid point, with the last coordinate varying fastest
+    pub fn point(&self, k: usize) -> Array<f64, Ix1> {
+        let mut point = Array::zeros(self.dim());
+        let mut rem = k;
+        for (d, c) in self.coords.iter().enumerate().rev() {
+            point[d] = c[rem % c.len()];
+            rem /= c.len();
+        }
+        point
+    }
+
+    // multilinear interpolation of values (one row per grid point) at x;
+    // x is clamped to the bounds of the grid
+    pub fn interpolate(&self, values: &Array<f64, Ix2>, x: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        // for each dimension, the lower index of the surrounding cell and the weight on the upper index
+        let cells = self.coords.iter().zip(x.iter()).map(|(c, x)| {
+            if c.len() == 1 {
+                return (0, 0.);
+            }
+            let x = x.clamp(c[0], c[c.len() - 1]);
+            let j = c.iter().take(c.len() - 1).rposition(|y| *y <= x).unwrap_or(0);
+            (j, (x - c[j]) / (c[j + 1] - c[j]))
+        }).collect::<Vec<_>>();
+        let mut out = Array::zeros(values.shape()[1]);
+        for corner in 0..(1usize << self.dim()) {
+            let mut weight = 1.;
+            let mut k = 0;
+            for (d, (c, (j, w))) in self.coords.iter().zip(cells.iter()).enumerate() {
+                let upper = (corner >> d) & 1 == 1;
+                weight *= if upper { *w } else { 1. - w };
+                k = k * c.len() + if upper && c.len() > 1 { j + 1 } else { *j };
+            }
+            if weight > 0. {
+                out.scaled_add(weight, &values.row(k));
+            }
+        }
+        out
+    }
+}
+
+
+impl<'de> Deserialize<'de> for StateGrid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        StateGrid::new(Vec::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+
+// closed-loop policies found by backward induction
+#[derive(Clone, Serialize)]
+#[serde(bound = "")]
+pub struct MarkovPolicy<A: ActionType> {
+    pub grid: StateGrid,
+    // actions at each grid point in each period, each of shape (grid size, n, nparams)
+    pub policies: Vec<Array<f64, Ix3>>,
+    // value of the game from each grid point in each period, each of shape (grid size, n)
+    pub values: Vec<Array<f64, Ix2>>,
+    // number of stage games where best responses did not converge
+    pub unconverged: usize,
+    #[serde(skip)]
+    _phantom: std::marker::PhantomData<A>,
+}
+
+// the action type isn't written, so check that the policies have the right number of params for it
+impl<'de, A: ActionType> Deserialize<'de> for MarkovPolicy<A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            grid: StateGrid,
+            policies: Vec<Array<f64, Ix3>>,
+            values: Vec<Array<f64, Ix2>>,
+            unconverged: usize,
+        }
+        let Fields { grid, policies, values, unconverged } = Fields::deserialize(deserializer)?;
+        if policies.len() != values.len() {
+            return Err(de::Error::custom("policies and values must have the same number of periods"));
+        }
+        if policies.iter().any(|p| p.shape()[0] != grid.size() || p.shape()[2] != A::nparams()) {
+            return Err(de::Error::custom(format!("policies must have shape (grid size, n, {})", A::nparams())));
+        }
+        if values.iter().zip(policies.iter()).any(|(v, p)| v.shape()[0] != grid.size() || v.shape()[1] != p.shape()[1]) {
+            return Err(de::Error::custom("values must have shape (grid size, n)"));
+        }
+        Ok(MarkovPolicy { grid, policies, values, unconverged, _phantom: std::marker::PhantomData })
+    }
+}
+
+impl<A: ActionType + Clone + 'static> MarkovPolicy<A> {
+    pub fn t(&self) -> usize {
+        self.policies.len()
+    }
+
+    // actions at period t, interpolated at state x
+    pub fn policy(&self, t: usize, x: ArrayView<f64, Ix1>) -> Result<A, DynapaiError> {
+        let shape = self.policies[t].dim();
+        let flat = self.policies[t].to_shape((shape.0, shape.1 * shape.2)).unwrap().to_owned();
+        let actions = self.grid.interpolate(&flat, x);
+        A::from_array(actions.into_shape((shape.1, shape.2)).unwrap())
+    }
+
+    // value of the game from period t onward, interpolated at state x
+    pub fn value(&self, t: usize, x: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        self.grid.interpolate(&self.values[t], x)
+    }
+
+    // path of play from the aggregator's initial state when everyone follows the policy
+    pub fn simulate(&self, agg: &dyn Aggregator<A>) -> Result<Strategies<A>, DynapaiError> {
+        let mut state = agg.state0().clone();
+        let mut actions_seq = Vec::with_capacity(self.t());
+        for t in 0..self.t() {
+            let actions = self.policy(t, state.state_vec().view())?;
+            agg.advance_state(&mut state, &actions);
+            actions_seq.push(actions);
+        }
+        Ok(Strategies::from_actions(actions_seq))
+    }
+}
+
+
+// payoff to player i in a single period, plus discounted continuation value
+fn stage_u_i<A: ActionType + Clone + 'static>(
+    i: usize,
+    agg: &dyn Aggregator<A>,
+    state: &dyn State<A>,
+    next: Option<(&StateGrid, &Array<f64, Ix2>)>,
+    actions: &A,
+) -> f64 {
+    let u = state.belief(i).u_i(i, actions);
+    match next {
+        Some((grid, values)) => {
+            let mut next_state = dyn_clone::clone_box(state);
+            agg.advance_state(&mut next_state, actions);
+            let v = grid.interpolate(values, next_state.state_vec().view());
+            u + agg.continuation(i, state, actions) * v[i]
+        },
+        None => u,
+    }
+}
+
+fn with_row<A: ActionType>(actions: &A, i: usize, row: ArrayView<f64, Ix1>) -> A {
+    let mut data = actions.data().clone();
+    data.row_mut(i).assign(&row);
+    A::from_array(data).unwrap()
+}
+
+struct StageObjective<'a, A: ActionType + Clone> {
+    agg: &'a dyn Aggregator<A>,
+    state: &'a dyn State<A>,
+    next: Option<(&'a StateGrid, &'a Array<f64, Ix2>)>,
+    i: usize,
+    base_actions: &'a A,
+}
+
+impl<A: ActionType + Clone + 'static> CostFunction for StageObjective<'_, A> {
+    type Param = Vec<f64>;
+    type Output = f64;
+
+    fn cost(&self, params: &Self::Param) -> Result<Self::Output, argmin::core::Error> {
+        let row = Array::from_iter(params.iter().map(from_log));
+        let actions = with_row(self.base_actions, self.i, row.view());
+        Ok(-stage_u_i(self.i, self.agg, self.state, self.next, &actions))
+    }
+}
+
+// finds a nash equilibrium of a single period's game by iterated best response;
+// stage games are always solved with Nelder-Mead since interpolated values are not smooth
+fn solve_stage<A: ActionType + Clone + 'static>(
+    agg: &dyn Aggregator<A>,
+    state: &dyn State<A>,
+    next: Option<(&StateGrid, &Array<f64, Ix2>)>,
+    init: A,
+    options: &SolverOptions<A>,
+) -> Result<(A, Array<f64, Ix1>, bool), DynapaiError> {
+    let stage_u = |actions: &A| Array::from_iter(
+        (0..actions.n()).map(|i| stage_u_i(i, agg, state, next, actions))
+    );
+    let best_response = |i: usize, actions: &A| -> Result<Array<f64, Ix1>, DynapaiError> {
+        let obj = StageObjective { agg, state, next, i, base_actions: actions };
+        let simplex = create_simplex(
+            actions.data().slice(s![i..i + 1, ..]),
+            options.nm_options.init_simplex_size,
+        );
+        let solver = NelderMead::new(simplex).with_sd_tolerance(options.nm_options.tol)?;
+        let res = Executor::new(obj, solver)
+            .configure(|state| state.max_iters(options.nm_options.iters))
+            .run()?;
+        let row = Array::from_iter(res.state.best_param.unwrap().iter().map(from_log));
+        Ok(damp(actions.data().row(i), row, options.damping))
+    };
+    let mut actions = init;
+    let mut last_payoffs = stage_u(&actions);
+    for _ in 0..options.iters {
+        match options.update {
+            UpdateMode::Jacobi => {
+                let rows = (0..actions.n()).map(|i| best_response(i, &actions))
+                    .collect::<Result<Vec<_>, DynapaiError>>()?;
+                let views = rows.iter().map(|r| r.view()).collect::<Vec<_>>();
+                actions = A::from_array(stack(Axis(0), &views)?)?;
+            },
+            UpdateMode::GaussSeidel => {
+                for i in 0..actions.n() {
+                    let row = best_response(i, &actions)?;
+                    actions = with_row(&actions, i, row.view());
+                }
+            },
+        }
+        let payoffs = stage_u(&actions);
+        if isapprox_iters(
+            payoffs.iter().cloned(), last_payoffs.iter().cloned(),
+            options.tol, f64::EPSILON.sqrt()
+        ) {
+            return Ok((actions, payoffs, true));
+        }
+        last_payoffs = payoffs;
+    }
+    Ok((actions, last_payoffs, false))
+}
+
+// solves for a markov perfect equilibrium by backward induction over options.init_guess.t() periods,
+// solving the stage game at each point of the grid given the interpolated continuation values
+pub fn solve_markov<A>(
+    agg: &dyn Aggregator<A>,
+    grid: &StateGrid,
+    options: &SolverOptions<A>,
+) -> Result<MarkovPolicy<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    with_threads(options.threads, || solve_markov_pure(agg, grid, options))
+}
+
+fn solve_markov_pure<A>(
+    agg: &dyn Aggregator<A>,
+    grid: &StateGrid,
+    options: &SolverOptions<A>,
+) -> Result<MarkovPolicy<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    if grid.dim() != agg.state0().state_vec().len() {
+        return Err(DynapaiError::DimensionMismatch(format!(
+            "grid has dimension {} but state has dimension {}",
+            grid.dim(), agg.state0().state_vec().len()
+        )));
+    }
+    let t = options.init_guess.t();
+    let init = options.init_guess.to_fixed(agg.n())?;
+    let mut policies: Vec<Array<f64, Ix3>> = Vec::with_capacity(t);
+    let mut values: Vec<Array<f64, Ix2>> = Vec::with_capacity(t);
+    let mut unconverged = 0;
+    for period in (0..t).rev() {
+        // policies and values are built back to front, so the last entry is period + 1
+        let next = values.last().map(|v| (grid, v));
+        let results = map_range(grid.size(), options.parallel, |k| {
+            let mut state = agg.state0().clone();
+            state.set_state_vec(grid.point(k).view());
+            let start = match policies.last() {
+                Some(p) => A::from_array(p.slice(s![k, .., ..]).to_owned())?,
+                None => init.actions()[period].clone(),
+            };
+            solve_stage(agg, state.as_ref(), next, start, options)
+        }).into_iter().collect::<Result<Vec<_>, DynapaiError>>()?;
+        unconverged += results.iter().filter(|(_, _, converged)| !converged).count();
+        let action_views = results.iter().map(|(a, _, _)| a.data().view()).collect::<Vec<_>>();
+        let value_views = results.iter().map(|(_, v, _)| v.view()).collect::<Vec<_>>();
+        policies.push(stack(Axis(0), &action_views)?);
+        values.push(stack(Axis(0), &value_views)?);
+        // progress is counted in periods solved, with payoffs as the values at the initial state
+        options.progress((t - 1 - period) as u64, || {
+            grid.interpolate(values.last().unwrap(), agg.state0().state_vec().view())
+        })?;
+    }
+    policies.reverse();
+    values.reverse();
+    Ok(MarkovPolicy {
+        grid: grid.clone(),
+        policies,
+        values,
+        unconverged,
+        _phantom: std::marker::PhantomData,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn interpolation_is_exact_for_linear_values() {
+        let grid = StateGrid::new(vec![array![0., 1., 3.], array![-1., 1.]]).unwrap();
+        let f = |x: ArrayView<f64, Ix1>| array![1. + 2. * x[0] - 3. * x[1], x[0]];
+        let rows = (0..grid.size()).map(|k| f(grid.point(k).view())).collect::<Vec<_>>();
+        let values = stack(Axis(0), &rows.iter().map(|v| v.view()).collect::<Vec<_>>()).unwrap();
+        for x in [array![0.5, 0.], array![2., -0.5], array![3., 1.]] {
+            assert_close(&grid.interpolate(&values, x.view()), &f(x.view()), 1e-12);
+        }
+        // clamped to the edges of the grid
+        assert_close(&grid.interpolate(&values, array![5., -2.].view()), &f(array![3., -1.].view()), 1e-12);
+        assert!(StateGrid::new(vec![array![1., 0.]]).is_err());
+    }
+
+    #[test]
+    fn fixed_state_policy_repeats_stage_equilibrium() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let state = agg.state0().state_vec();
+        // the state never changes, so a single grid point is enough
+        let grid = StateGrid::new(state.iter().map(|x| array![*x]).collect()).unwrap();
+        let options = SolverOptions { method: SolverMethod::NelderMead, ..solver_options(2) };
+        let policy = solve_markov(&agg, &grid, &options).unwrap();
+        assert_eq!(policy.unconverged, 0);
+
+        let stage = solve(&agg, &solver_options(1)).unwrap().strategies.unwrap();
+        let path = policy.simulate(&agg).unwrap();
+        for actions in path.actions() {
+            assert_close(actions.data(), &stage.data(), 1e-3);
+        }
+        let u = agg.state0().belief(0).u(&path.actions()[0]);
+        assert_close(&policy.value(0, state.view()), &(&u + &(&agg.gammas * &u)), 1e-6);
+    }
+}
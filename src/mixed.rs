@@ -0,0 +1,242 @@
+use ndarray::{Array, ArrayView, Ix1, Ix2, s};
+use ndarray_rand::rand::{Rng, distributions::{Distribution, WeightedIndex}};
+use itertools::Itertools;
+
+use crate::prelude::*;
+
+
+// a probability distribution over a single player's pure strategies
+#[derive(Clone, Debug)]
+pub struct MixedStrategy {
+    // pure strategies, each of shape (t, nparams)
+    pub support: Vec<Array<f64, Ix2>>,
+    pub weights: Vec<f64>,
+}
+
+impl MixedStrategy {
+    pub fn pure(x: Array<f64, Ix2>) -> Self {
+        MixedStrategy { support: vec![x], weights: vec![1.] }
+    }
+
+    pub fn most_likely(&self) -> &Array<f64, Ix2> {
+        let k = self.weights.iter().position_max_by(|a, b| a.total_cmp(b)).unwrap();
+        &self.support[k]
+    }
+
+    // moves weight step onto x, adding it to the support unless it is already there (up to tol);
+    // if the support grows beyond max_size, the two closest strategies (in log space)
+    // are merged into their weighted geometric mean
+    fn update(&mut self, x: Array<f64, Ix2>, step: f64, tol: f64, max_size: usize) {
+        self.weights.iter_mut().for_each(|w| *w *= 1. - step);
+        match self.support.iter().position(|y| isapprox_iters(y.iter().cloned(), x.iter().cloned(), tol, tol)) {
+            Some(k) => self.weights[k] += step,
+            None => {
+                self.support.push(x);
+                self.weights.push(step);
+            }
+        }
+        if self.support.len() > max_size.max(1) {
+            let logs = self.support.iter().map(|y| y.mapv(|v| v.ln().max(MIN_LOG_ACTION))).collect::<Vec<_>>();
+            let (j, k) = (0..logs.len()).tuple_combinations()
+                .min_by(|(a, b), (c, d)| {
+                    let dist = |j: usize, k: usize| (&logs[j] - &logs[k]).mapv(|v| v * v).sum();
+                    dist(*a, *b).total_cmp(&dist(*c, *d))
+                })
+                .unwrap();
+            let (wj, wk) = (self.weights[j], self.weights[k]);
+            let merged = ((&logs[j] * wj + &logs[k] * wk) / (wj + wk)).mapv(f64::exp);
+            self.support[j] = merged;
+            self.weights[j] = wj + wk;
+            self.support.remove(k);
+            self.weights.remove(k);
+        }
+    }
+}
+
+
+#[derive(Clone)]
+pub struct MixedSolverResult<A: ActionType> {
+    pub status: String,
+    // one mixed strategy per player
+    pub strategies: Vec<MixedStrategy>,
+    // expected payoffs under the mixed strategies, estimated from the sampled profiles
+    pub payoffs: Array<f64, Ix1>,
+    // largest gain any player could get by switching to a best response, at each iteration
+    pub exploitability: Vec<f64>,
+    _phantom: std::marker::PhantomData<A>,
+}
+
+// pure strategy profiles drawn from the players' mixed strategies, with their probabilities;
+// every profile is enumerated if there are no more than n_samples of them
+fn sample_profiles<A, R>(
+    strategies: &[MixedStrategy], base: &Strategies<A>, n_samples: usize, rng: &mut R,
+) -> Result<(Vec<Strategies<A>>, Vec<f64>), DynapaiError>
+where A: ActionType + Clone, R: Rng
+{
+    let build = |idx: &[usize]| {
+        let mut profile = base.clone();
+        for (i, (mixed, k)) in strategies.iter().zip(idx.iter()).enumerate() {
+            profile.set_i(i, mixed.support[*k].clone());
+        }
+        profile
+    };
+    let n_profiles = strategies.iter().map(|m| m.support.len()).product::<usize>();
+    if n_profiles <= n_samples.max(1) {
+        Ok(strategies.iter().map(|m| 0..m.support.len())
+            .multi_cartesian_product()
+            .map(|idx| {
+                let proba = strategies.iter().zip(idx.iter()).map(|(m, k)| m.weights[*k]).product::<f64>();
+                (build(&idx), proba)
+            })
+            .unzip())
+    }
+    else {
+        let dists = strategies.iter().map(|m| WeightedIndex::new(&m.weights).map_err(|e|
+            DynapaiError::InvalidParameter(format!("Invalid mixed strategy weights {:?}: {}", m.weights, e))
+        )).collect::<Result<Vec<_>, _>>()?;
+        Ok((0..n_samples).map(|_| {
+            let idx = dists.iter().map(|d| d.sample(rng)).collect::<Vec<_>>();
+            (build(&idx), 1. / n_samples as f64)
+        }).unzip())
+    }
+}
+
+fn expected_u_i<A: ActionType + Clone + 'static>(
+    agg: &dyn Aggregator<A>, i: usize, x: ArrayView<f64, Ix2>, profiles: &[Strategies<A>], probas: &[f64],
+) -> f64 {
+    profiles.iter().zip(probas.iter()).map(|(profile, p)| {
+        let mut profile = profile.clone();
+        profile.set_i(i, x.to_owned());
+        p * agg.u_i(i, &profile)
+    }).sum()
+}
+
+// fictitious play: in each iteration, every player best responds to options.mixed_samples profiles
+// drawn from the others' current mixed strategies, and the best response gets weight 1 / (iteration + 2);
+// each player's support is limited to options.hist_size strategies,
+// and the solver exits once exploitability is at most options.mixed_tol * max_i |u_i|
+// (or sqrt(eps) when payoffs are all near zero)
+pub fn solve_mixed<A>(agg: &dyn Aggregator<A>, options: &SolverOptions<A>) -> Result<MixedSolverResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    with_threads(options.threads, || solve_mixed_pure(agg, options))
+}
+
+fn solve_mixed_pure<A>(agg: &dyn Aggregator<A>, options: &SolverOptions<A>) -> Result<MixedSolverResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let init = options.init_guess.to_fixed(agg.n())?;
+    let mut strategies = (0..agg.n()).map(|i|
+        MixedStrategy::pure(init.data().slice(s![.., i, ..]).to_owned())
+    ).collect::<Vec<_>>();
+    let mut payoffs = Array::zeros(agg.n());
+    let mut exploitability = Vec::new();
+    let mut rng = seeded_rng(options.seed, &[MIXED_STREAM]);
+    for iter in 0..options.iters {
+        let (profiles, probas) = sample_profiles(&strategies, &init, options.mixed_samples, &mut rng)?;
+        let results = map_range(agg.n(), options.parallel, |i| {
+            let (x, _) = best_response(i, &profiles, &probas, strategies[i].most_likely().view(), agg, options)?;
+            let best_u = expected_u_i(agg, i, x.view(), &profiles, &probas);
+            let current_u = strategies[i].support.iter().zip(strategies[i].weights.iter()).map(|(y, w)|
+                w * expected_u_i(agg, i, y.view(), &profiles, &probas)
+            ).sum::<f64>();
+            Ok((x, best_u, current_u))
+        }).into_iter().collect::<Result<Vec<_>, DynapaiError>>()?;
+        payoffs = Array::from_iter(results.iter().map(|(_, _, u)| *u));
+        check_finite(payoffs.iter(), &format!("On iteration {}", iter))?;
+        options.progress(iter, || payoffs.clone())?;
+        let eps = results.iter().fold(0., |acc, (_, best_u, u)| f64::max(acc, best_u - u));
+        exploitability.push(eps);
+        let scale = payoffs.iter().fold(0., |acc: f64, u| acc.max(u.abs()));
+        if eps <= f64::max(options.mixed_tol * scale, f64::EPSILON.sqrt()) {
+            return Ok(MixedSolverResult {
+                status: format!("Exited on iteration {}", iter),
+                strategies,
+                payoffs,
+                exploitability,
+                _phantom: std::marker::PhantomData,
+            });
+        }
+        let step = 1. / (iter as f64 + 2.);
+        for (mixed, (x, _, _)) in strategies.iter_mut().zip(results) {
+            mixed.update(x, step, options.tol, options.hist_size);
+        }
+    }
+    Ok(MixedSolverResult {
+        status: format!("Reached max iterations ({})", options.iters),
+        strategies,
+        payoffs,
+        exploitability,
+        _phantom: std::marker::PhantomData,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn profile_probabilities_sum_to_one() {
+        let base = strategies::<Actions>(1, 2);
+        let mixed = vec![
+            MixedStrategy { support: vec![array![[1., 1.]], array![[2., 2.]]], weights: vec![0.25, 0.75] },
+            MixedStrategy { support: vec![array![[1., 1.]], array![[2., 2.]], array![[3., 3.]]], weights: vec![0.5, 0.3, 0.2] },
+        ];
+        let mut rng = seeded_rng(Some(0), &[MIXED_STREAM]);
+        // enumerated
+        let (profiles, probas) = sample_profiles(&mixed, &base, 6, &mut rng).unwrap();
+        assert_eq!(profiles.len(), 6);
+        assert_close([&probas.iter().sum::<f64>()], [&1.], 1e-12);
+        // sampled
+        let (profiles, probas) = sample_profiles(&mixed, &base, 5, &mut rng).unwrap();
+        assert_eq!(profiles.len(), 5);
+        assert_close([&probas.iter().sum::<f64>()], [&1.], 1e-12);
+
+        let bad = vec![mixed[0].clone(), MixedStrategy { weights: vec![0., 0., 0.], ..mixed[1].clone() }];
+        assert!(matches!(sample_profiles(&bad, &base, 5, &mut rng), Err(DynapaiError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn mixed_strategies_are_distributions() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let options = SolverOptions { iters: 5, hist_size: 3, mixed_samples: 4, ..solver_options(1) };
+        let res = solve_mixed(&agg, &options).unwrap();
+        assert_eq!(res.strategies.len(), 2);
+        for mixed in res.strategies.iter() {
+            assert!(mixed.support.len() <= 3);
+            assert_eq!(mixed.support.len(), mixed.weights.len());
+            assert!(mixed.weights.iter().all(|w| *w >= 0.));
+            assert_close([&mixed.weights.iter().sum::<f64>()], [&1.], 1e-12);
+        }
+    }
+
+    // payoffs scaled by k, with the same best responses
+    fn scaled_agg(k: f64) -> FixedStateDiscounter<Actions> {
+        let payoff = ModularPayoff::new(
+            Box::new(DefaultProd::new(array![1., 1.5], array![0.5, 0.6], array![1.2, 0.8], array![0.4, 0.5]).unwrap()),
+            Box::new(WinnerOnlyRisk { theta: array![0.5, 0.7] }),
+            Box::new(DefaultCSF),
+            Box::new(LinearReward::new(k * array![1., 1.2], k * array![0.1, 0.], k * array![0.2, 0.1], k * array![0., 0.05]).unwrap()),
+            Box::new(ConstantDisasterCost { d: k * array![1., 0.8] }),
+            Box::new(BasicFixedCost::new(k * array![[0.1, 0.1], [0.12, 0.08]]).unwrap()),
+        ).unwrap();
+        FixedStateDiscounter::new(Box::new(CommonBeliefs(Box::new(payoff))), array![0.9, 0.9]).unwrap()
+    }
+
+    #[test]
+    fn stopping_rule_does_not_depend_on_payoff_scale() {
+        let options = SolverOptions { iters: 40, hist_size: 3, mixed_samples: 4, mixed_tol: 0.05, ..solver_options(1) };
+        let res = solve_mixed(&scaled_agg(1.), &options).unwrap();
+        let scaled = solve_mixed(&scaled_agg(1000.), &options).unwrap();
+        assert!(res.status.starts_with("Exited on iteration"), "{}", res.status);
+        assert_eq!(scaled.status, res.status);
+        let last = *res.exploitability.last().unwrap();
+        let max_u = res.payoffs.iter().fold(0., |acc: f64, u| acc.max(u.abs()));
+        assert!(last <= options.mixed_tol * max_u);
+        assert_close(&(1000. * Array::from(res.exploitability)), &Array::from(scaled.exploitability), 1e-6);
+    }
+}
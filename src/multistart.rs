@@ -0,0 +1,191 @@
+use ndarray::{Array, Ix1};
+
+use crate::prelude::*;
+
+
+// starts are drawn from options.init_guess's distribution, seeded by options.seed
+#[derive(Clone, Debug)]
+pub struct MultistartOptions {
+    pub starts: usize,
+    // tolerances for deciding that two solutions are the same equilibrium
+    pub payoff_tol: f64,
+    // relative tolerance on strategies, also used as an absolute tolerance for actions near zero
+    pub strategy_tol: f64,
+}
+
+impl Default for MultistartOptions {
+    fn default() -> Self {
+        MultistartOptions {
+            starts: 16,
+            payoff_tol: 1e-4,
+            strategy_tol: 1e-2,
+        }
+    }
+}
+
+// a distinct solution found by solve_multistart
+#[derive(Clone)]
+pub struct Equilibrium<A: ActionType> {
+    // result from the first start that landed in this basin
+    pub result: SolverResult<A>,
+    pub payoffs: Array<f64, Ix1>,
+    // indices of the starts that converged here
+    pub starts: Vec<usize>,
+}
+
+impl<A: ActionType> Equilibrium<A> {
+    pub fn count(&self) -> usize {
+        self.starts.len()
+    }
+}
+
+fn same_equilibrium<A: ActionType>(
+    a: &Strategies<A>, a_payoffs: &Array<f64, Ix1>,
+    b: &Strategies<A>, b_payoffs: &Array<f64, Ix1>,
+    options: &MultistartOptions,
+) -> bool {
+    isapprox_iters(
+        a_payoffs.iter().cloned(), b_payoffs.iter().cloned(),
+        options.payoff_tol, f64::EPSILON.sqrt()
+    ) && isapprox_iters(
+        a.data().iter().cloned(), b.data().iter().cloned(),
+        options.strategy_tol, options.strategy_tol
+    )
+}
+
+// start k draws player i's strategy from stream [MULTISTART_STREAM, k, i] of options.seed,
+// so runs with different seeds don't share starts
+fn random_start<A: ActionType + Clone>(
+    t: usize, n: usize, k: usize, options: &SolverOptions<A>,
+) -> Result<Strategies<A>, DynapaiError> {
+    let (mu, sigma) = options.init_guess.distribution();
+    Strategies::random_in_stream(t, n, mu, sigma, options.seed, &[MULTISTART_STREAM, k as u64])
+}
+
+#[derive(Clone)]
+pub struct MultistartResult<A: ActionType> {
+    // sorted by the number of starts that landed in each
+    pub equilibria: Vec<Equilibrium<A>>,
+    // starts whose solve failed, with the error, e.g. a non-finite payoff in a bad basin
+    pub failed: Vec<(usize, DynapaiError)>,
+}
+
+// runs solve from several random starts and groups the solutions into distinct equilibria;
+// a start that fails is recorded without stopping the others, unless the solve was cancelled
+pub fn solve_multistart<A>(
+    agg: &dyn Aggregator<A>,
+    options: &SolverOptions<A>,
+    multistart_options: &MultistartOptions,
+) -> Result<MultistartResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let t = options.init_guess.t();
+    let results = with_threads(options.threads, || Ok(map_range(multistart_options.starts, options.parallel, |k| {
+        let init = random_start(t, agg.n(), k, options)?;
+        let options = SolverOptions {
+            init_guess: InitGuess::Fixed(init),
+            ..options.clone()
+        };
+        solve(agg, &options)
+    })))?;
+
+    let mut equilibria: Vec<Equilibrium<A>> = Vec::new();
+    let mut failed = Vec::new();
+    for (k, result) in results.into_iter().enumerate() {
+        let result = match result {
+            Ok(result) => result,
+            Err(e @ DynapaiError::Cancelled(_)) => return Err(e),
+            Err(e) => {
+                failed.push((k, e));
+                continue;
+            },
+        };
+        let strat = match &result.strategies {
+            Some(s) => s,
+            None => continue,
+        };
+        let payoffs = agg.u(strat);
+        let found = equilibria.iter_mut().find(|eq| same_equilibrium(
+            eq.result.strategies.as_ref().unwrap(), &eq.payoffs,
+            strat, &payoffs,
+            multistart_options
+        ));
+        match found {
+            Some(eq) => eq.starts.push(k),
+            None => equilibria.push(Equilibrium { result, payoffs, starts: vec![k] }),
+        }
+    }
+    // stable sort keeps equally common equilibria in order of first appearance
+    equilibria.sort_by_key(|eq| std::cmp::Reverse(eq.count()));
+    Ok(MultistartResult { equilibria, failed })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn options() -> SolverOptions<Actions> {
+        SolverOptions { init_guess: InitGuess::Random { t: 1, seed: None, mu: -1., sigma: 0.5 }, ..solver_options(1) }
+    }
+
+    #[test]
+    fn starts_are_grouped_into_equilibria() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let multistart_options = MultistartOptions { starts: 4, ..MultistartOptions::default() };
+        // this model has a unique equilibrium, so every start should land in it
+        let res = solve_multistart(&agg, &options(), &multistart_options).unwrap();
+        assert_eq!(res.equilibria.len(), 1);
+        assert_eq!(res.equilibria[0].starts, vec![0, 1, 2, 3]);
+        assert!(res.failed.is_empty());
+
+        // strategies that differ by more than strategy_tol count as different equilibria
+        let strat = res.equilibria[0].result.strategies.clone().unwrap();
+        let payoffs = agg.u(&strat);
+        assert!(same_equilibrium(&strat, &payoffs, &strat, &payoffs, &multistart_options));
+        let other = Strategies::<Actions>::from_data(1.1 * &strat.data()).unwrap();
+        assert!(!same_equilibrium(&strat, &payoffs, &other, &payoffs, &multistart_options));
+    }
+
+    #[test]
+    fn starts_come_from_the_solver_seed() {
+        let draw = |seed: u64, k: usize| random_start(1, 2, k, &SolverOptions { seed: Some(seed), ..options() }).unwrap().data();
+        assert_eq!(draw(0, 1), draw(0, 1));
+        assert!(draw(0, 1) != draw(0, 2));
+        // consecutive seeds don't share starts
+        assert!(draw(1, 0) != draw(0, 1));
+        // and starts use the distribution of the initial guess
+        let wide = random_start::<Actions>(1, 2, 0, &SolverOptions { init_guess: InitGuess::Random { t: 1, seed: None, mu: 3., sigma: 0.1 }, ..options() })
+            .unwrap().data();
+        assert!(wide.iter().all(|x| *x > 10.));
+    }
+
+    #[test]
+    fn failed_starts_are_recorded() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let multistart_options = MultistartOptions { starts: 3, ..MultistartOptions::default() };
+        // starts run in order since options aren't parallel, so this fails the first one only
+        let calls = Arc::new(AtomicUsize::new(0));
+        let callback: SolverCallback = Arc::new(move |_, _| match calls.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(DynapaiError::NonFinitePayoff("in a bad basin".to_string())),
+            _ => Ok(()),
+        });
+        let options = SolverOptions { callback: Some(callback), ..options() };
+        let res = solve_multistart(&agg, &options, &multistart_options).unwrap();
+        assert_eq!(res.failed, vec![(0, DynapaiError::NonFinitePayoff("in a bad basin".to_string()))]);
+        assert_eq!(res.equilibria.len(), 1);
+        assert_eq!(res.equilibria[0].starts, vec![1, 2]);
+
+        // but cancelling stops the search
+        let callback: SolverCallback = Arc::new(|_, _| Err(DynapaiError::Cancelled("cancelled".to_string())));
+        let options = SolverOptions { callback: Some(callback), ..options };
+        assert_eq!(
+            solve_multistart(&agg, &options, &multistart_options).err(),
+            Some(DynapaiError::Cancelled("cancelled".to_string()))
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
-use numpy::ndarray::{Array, Ix1};
+use ndarray::{Array, ArrayView, Ix1, Ix2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 
 use crate::prelude::*;
 
@@ -10,6 +11,22 @@ pub trait PayoffFunc<A: ActionType>: DynClone + Downcast + MutatesOn<A> + Send +
     fn u(&self, actions: &A) -> Array<f64, Ix1> {
         Array::from_iter((0..actions.n()).map(|i| self.u_i(i, actions)))
     }
+    // gradient of u_i wrt player i's actions
+    fn du_i(&self, i: usize, actions: &A) -> Array<f64, Ix1>;
+
+    // for payoff functions that carry a state that is mutated between periods
+    fn state_dim(&self) -> usize { 0 }
+    // the state as a flat vector of length state_dim, and a way to overwrite it
+    fn state(&self) -> Array<f64, Ix1> { Array::zeros(0) }
+    fn set_state(&mut self, _state: ArrayView<f64, Ix1>) {}
+    // gradient of u_i wrt the state
+    fn du_i_dstate(&self, _i: usize, _actions: &A) -> Array<f64, Ix1> {
+        Array::zeros(self.state_dim())
+    }
+    // jacobians of the mutated state wrt the current state and wrt player i's actions
+    fn dmutate_on(&self, _i: usize, _actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        (Array::eye(self.state_dim()), Array::zeros((self.state_dim(), A::nparams())))
+    }
 }
 
 clone_trait_object!(<A> PayoffFunc<A> where A: ActionType);
@@ -36,14 +53,15 @@ impl<A: ActionType + Clone + 'static> ModularPayoff<A>
         reward_func: Box<dyn RewardFunc>,
         disaster_cost: Box<dyn DisasterCost>,
         cost_func: Box<dyn CostFunc<A>>
-    ) -> Result<ModularPayoff<A>, &'static str> {
+    ) -> Result<ModularPayoff<A>, DynapaiError> {
         let n = prod_func.n();
         if n != risk_func.n()
             || n != reward_func.n()
             || n != disaster_cost.n()
             || n != cost_func.n()
+            || csf.n().is_some_and(|csf_n| n != csf_n)
         {
-            return Err("When creating new ModularPayoff: All components must have the same n");
+            return Err(DynapaiError::DimensionMismatch("When creating new ModularPayoff: All components must have the same n".to_string()));
         }
         Ok(ModularPayoff {
             n,
@@ -55,6 +73,66 @@ impl<A: ActionType + Clone + 'static> ModularPayoff<A>
             cost_func,
         })
     }
+
+    // gradients of u_i (excluding the cost term) wrt s and p
+    fn du_i_dsp(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let sigmas = self.risk_func.sigma(s, p);
+        let qs = self.csf.q(p);
+        let rewards = self.reward_func.reward(i, p);
+        let drewards = self.reward_func.dreward(i, p);
+        let disaster_costs = self.disaster_cost.d(i, s, p);
+        let d_none = self.disaster_cost.d_none(i, s, p);
+
+        let mut ds = Array::zeros(s.len());
+        let mut dp = Array::zeros(p.len());
+        for j in 0..p.len() {
+            // when j wins, avoiding disaster is worth reward + d relative to disaster
+            let value = rewards[j] + disaster_costs[j];
+            let (dsigma_ds, dsigma_dp) = self.risk_func.dsigma_i(j, s, p);
+            let (dd_ds, dd_dp) = self.disaster_cost.dd_ij(i, j, s, p);
+            let dq = self.csf.dq_i(j, p);
+            ds.scaled_add(qs[j] * value, &dsigma_ds);
+            ds.scaled_add(-qs[j] * (1.0 - sigmas[j]), &dd_ds);
+            dp.scaled_add(qs[j] * value, &dsigma_dp);
+            // raising q_j also lowers the chance that nobody wins
+            dp.scaled_add(sigmas[j] * rewards[j] - (1.0 - sigmas[j]) * disaster_costs[j] + d_none, &dq);
+            dp.scaled_add(sigmas[j] * qs[j], &drewards.row(j));
+            dp.scaled_add(-qs[j] * (1.0 - sigmas[j]), &dd_dp);
+        }
+        let (dd_none_ds, dd_none_dp) = self.disaster_cost.dd_none(i, s, p);
+        ds.scaled_add(-(1.0 - qs.sum()), &dd_none_ds);
+        dp.scaled_add(-(1.0 - qs.sum()), &dd_none_dp);
+        (ds, dp)
+    }
+
+    // gradients of the probability that someone wins the contest
+    // wrt player i's actions and wrt the state
+    pub fn dwin_proba(&self, i: usize, actions: &A) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let (_, p) = self.prod_func.f(actions);
+        let dq_dp = (0..p.len()).fold(Array::<f64, Ix1>::zeros(p.len()), |acc, j| {
+            acc + self.csf.dq_i(j, p.view())
+        });
+        let (_, dp_i) = self.prod_func.df_i(i, actions);
+        let dactions = dq_dp[i] * dp_i;
+        let dstate = (0..p.len()).fold(Array::<f64, Ix1>::zeros(self.prod_func.state_dim()), |acc, k| {
+            let (_, dp_k) = self.prod_func.df_i_dstate(k, actions);
+            acc + dq_dp[k] * dp_k
+        });
+        (dactions, dstate)
+    }
+}
+
+// u_i excluding the cost term: for each winner j,
+// payoff given no disaster * proba no disaster minus cost given disaster * proba disaster;
+// if nobody wins, a disaster happens with cost d_none, so with a constant d_i this is
+// sum_j sigma_j q_j rho_ij - (1 - sum_j sigma_j q_j) d_i
+fn expected_payoff(
+    sigmas: &Array<f64, Ix1>, qs: &Array<f64, Ix1>,
+    rewards: &Array<f64, Ix1>, disaster_costs: &Array<f64, Ix1>, d_none: f64,
+) -> f64 {
+    (0..qs.len()).map(
+        |j| qs[j] * (sigmas[j] * rewards[j] - (1.0 - sigmas[j]) * disaster_costs[j])
+    ).sum::<f64>() - (1.0 - qs.sum()) * d_none
 }
 
 impl<A: ActionType + Clone> MutatesOn<A> for ModularPayoff<A> {
@@ -75,16 +153,9 @@ impl<A: ActionType + Clone + 'static> PayoffFunc<A> for ModularPayoff<A>
         let sigmas = self.risk_func.sigma(s.view(), p.view());
         let qs = self.csf.q(p.view());
         let rewards = self.reward_func.reward(i, p.view());
-        // payoff given no disaster * proba no disaster
-        let no_d = sigmas.iter().zip(qs.iter()).zip(rewards.iter()).map(
-            |((sigma, q), reward)| sigma * q * reward
-        ).sum::<f64>();
-        // cost given disaster * proba disaster
-        let yes_d = (1.0 - sigmas.iter().zip(qs.iter()).map(
-            |(sigma, q)| sigma * q
-        ).sum::<f64>()) * self.disaster_cost.d_i(i, s.view(), p.view());
-
-        no_d - yes_d - self.cost_func.c_i(i, actions)
+        let disaster_costs = self.disaster_cost.d(i, s.view(), p.view());
+        let d_none = self.disaster_cost.d_none(i, s.view(), p.view());
+        expected_payoff(&sigmas, &qs, &rewards, &disaster_costs, d_none) - self.cost_func.c_i(i, actions)
     }
 
     fn u(&self, actions: &A) -> Array<f64, Ix1> {
@@ -92,27 +163,181 @@ impl<A: ActionType + Clone + 'static> PayoffFunc<A> for ModularPayoff<A>
         let sigmas = self.risk_func.sigma(s.view(), p.view());
         let qs = self.csf.q(p.view());
 
-        let all_rewards = (0..p.len()).map(
-            |i| self.reward_func.reward(i, p.view())
-        );
+        let net_rewards = (0..p.len()).map(|i| expected_payoff(
+            &sigmas, &qs,
+            &self.reward_func.reward(i, p.view()),
+            &self.disaster_cost.d(i, s.view(), p.view()),
+            self.disaster_cost.d_none(i, s.view(), p.view()),
+        ));
+
+        let cost = self.cost_func.c(actions);
+
+        Array::from_iter(net_rewards.zip(cost.iter()).map(|(r, c)| r - c))
+    }
 
-        let no_d = all_rewards.map(
-            |rewards| sigmas.iter().zip(qs.iter()).zip(rewards.iter()).map(
-                |((sigma, q), reward)| sigma * q * reward
-            ).sum::<f64>()
-        );
+    fn du_i(&self, i: usize, actions: &A) -> Array<f64, Ix1> {
+        let (s, p) = self.prod_func.f(actions);
+        let (du_ds, du_dp) = self.du_i_dsp(i, s.view(), p.view());
+        let (ds_i, dp_i) = self.prod_func.df_i(i, actions);
+        du_ds[i] * ds_i + du_dp[i] * dp_i - self.cost_func.dc_i(i, actions)
+    }
 
-        let proba_d = 1.0 - sigmas.iter().zip(qs.iter()).map(
-            |(sigma, q)| sigma * q
-        ).sum::<f64>();
+    fn state_dim(&self) -> usize {
+        self.prod_func.state_dim()
+    }
 
-        let disaster_costs = self.disaster_cost.d(s.view(), p.view());
-        let yes_d = disaster_costs.iter().map(|d| d * proba_d);
+    fn state(&self) -> Array<f64, Ix1> {
+        self.prod_func.state()
+    }
 
-        let net_rewards = no_d.zip(yes_d).map(|(n, y)| n - y);
+    fn set_state(&mut self, state: ArrayView<f64, Ix1>) {
+        self.prod_func.set_state(state)
+    }
 
-        let cost = self.cost_func.c(actions);
+    fn du_i_dstate(&self, i: usize, actions: &A) -> Array<f64, Ix1> {
+        let (s, p) = self.prod_func.f(actions);
+        let (du_ds, du_dp) = self.du_i_dsp(i, s.view(), p.view());
+        (0..self.n).fold(Array::zeros(self.state_dim()), |acc, k| {
+            let (ds_k, dp_k) = self.prod_func.df_i_dstate(k, actions);
+            acc + du_ds[k] * ds_k + du_dp[k] * dp_k
+        })
+    }
 
-        Array::from_iter(net_rewards.zip(cost.iter()).map(|(r, c)| r - c))
+    fn dmutate_on(&self, i: usize, actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        self.prod_func.dmutate_on(i, actions)
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    bound(
+        serialize = "Box<dyn ProdFunc<A>>: Serialize, Box<dyn CostFunc<A>>: Serialize",
+        deserialize = "Box<dyn ProdFunc<A>>: Deserialize<'de>, Box<dyn CostFunc<A>>: Deserialize<'de>",
+    ),
+)]
+enum PayoffFuncSpec<A: ActionType + 'static> {
+    ModularPayoff {
+        prod_func: Box<dyn ProdFunc<A>>,
+        risk_func: Box<dyn RiskFunc>,
+        csf: Box<dyn CSF>,
+        reward_func: Box<dyn RewardFunc>,
+        disaster_cost: Box<dyn DisasterCost>,
+        cost_func: Box<dyn CostFunc<A>>,
+    },
+}
+
+impl<A: ActionType + Clone + 'static> Serialize for Box<dyn PayoffFunc<A>>
+where Box<dyn ProdFunc<A>>: Serialize, Box<dyn CostFunc<A>>: Serialize
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if let Some(x) = self.downcast_ref::<ModularPayoff<A>>() {
+            PayoffFuncSpec::ModularPayoff {
+                prod_func: x.prod_func.clone(),
+                risk_func: x.risk_func.clone(),
+                csf: x.csf.clone(),
+                reward_func: x.reward_func.clone(),
+                disaster_cost: x.disaster_cost.clone(),
+                cost_func: x.cost_func.clone(),
+            }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this PayoffFunc"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de, A: ActionType + Clone + 'static> Deserialize<'de> for Box<dyn PayoffFunc<A>>
+where Box<dyn ProdFunc<A>>: Deserialize<'de>, Box<dyn CostFunc<A>>: Deserialize<'de>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match PayoffFuncSpec::deserialize(deserializer)? {
+            PayoffFuncSpec::ModularPayoff { prod_func, risk_func, csf, reward_func, disaster_cost, cost_func } => Box::new(
+                ModularPayoff::new(prod_func, risk_func, csf, reward_func, disaster_cost, cost_func)
+                    .map_err(de::Error::custom)?
+            ),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    // checks du_i, du_i_dstate and dmutate_on against finite differences for both players
+    fn check_grads<A: ActionType + Clone + 'static>(payoff: ModularPayoff<A>) {
+        let actions = strategies::<A>(1, 2).into_actions().remove(0);
+        let state = payoff.state();
+        for i in 0..2 {
+            let x = actions.data().row(i).to_owned();
+            let with_x = |x: ArrayView<f64, Ix1>| {
+                let mut actions = actions.clone();
+                actions.data_mut().row_mut(i).assign(&x);
+                actions
+            };
+            let with_state = |state: ArrayView<f64, Ix1>| {
+                let mut payoff = payoff.clone();
+                payoff.set_state(state);
+                payoff
+            };
+
+            let fd = fd_grad(x.view(), |x| payoff.u_i(i, &with_x(x)));
+            assert_close(&payoff.du_i(i, &actions), &fd, 1e-6);
+            let fd = fd_grad(state.view(), |state| with_state(state).u_i(i, &actions));
+            assert_close(&payoff.du_i_dstate(i, &actions), &fd, 1e-6);
+
+            let (jac_state, jac_actions) = payoff.dmutate_on(i, &actions);
+            for k in 0..payoff.state_dim() {
+                let fd = fd_grad(state.view(), |state| {
+                    let mut payoff = with_state(state);
+                    payoff.mutate_on(&actions);
+                    payoff.state()[k]
+                });
+                assert_close(jac_state.row(k), &fd, 1e-6);
+                let fd = fd_grad(x.view(), |x| {
+                    let mut payoff = payoff.clone();
+                    payoff.mutate_on(&with_x(x));
+                    payoff.state()[k]
+                });
+                assert_close(jac_actions.row(k), &fd, 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn grads_match_finite_differences() {
+        check_grads(basic_payoff(Box::new(DefaultCSF)));
+        check_grads(invest_payoff(Box::new(DefaultCSF)));
+        check_grads(sharing_payoff(Box::new(DefaultCSF)));
+        check_grads(invest_payoff(Box::new(MaybeNoWinCSF::new(1.).unwrap())));
+        // with disaster costs that depend on p
+        let mut payoff = invest_payoff(Box::new(MaybeNoWinCSF::new(1.).unwrap()));
+        payoff.disaster_cost = Box::new(CapabilityDisasterCost::new(array![1., 0.8], 0.5, None).unwrap());
+        check_grads(payoff);
+    }
+
+    #[test]
+    fn nobody_winning_is_a_disaster() {
+        let payoff = basic_payoff(Box::new(MaybeNoWinCSF::new(1.).unwrap()));
+        let actions = strategies::<Actions>(1, 2).into_actions().remove(0);
+        let (s, p) = payoff.prod_func.f(&actions);
+        let sigmas = payoff.risk_func.sigma(s.view(), p.view());
+        let qs = payoff.csf.q(p.view());
+        assert!(qs.sum() < 0.9);
+        for (i, d_i) in [1., 0.8].into_iter().enumerate() {
+            // the original form, sum_j sigma_j q_j rho_ij - (1 - sum_j sigma_j q_j) d_i - c_i
+            let safe_win = (&sigmas * &qs).sum();
+            let expected = (&sigmas * &qs * payoff.reward_func.reward(i, p.view())).sum()
+                - (1. - safe_win) * d_i
+                - payoff.cost_func.c_i(i, &actions);
+            assert_close([&payoff.u_i(i, &actions)], [&expected], 1e-12);
+            assert_close([&payoff.u(&actions)[i]], [&expected], 1e-12);
+        }
     }
 }
@@ -0,0 +1,206 @@
+use ndarray::{Array, ArrayView, Ix1};
+use argmin::core::{CostFunction, Executor, Gradient};
+use argmin::solver::linesearch::MoreThuenteLineSearch;
+use argmin::solver::neldermead::NelderMead;
+use argmin::solver::quasinewton::LBFGS;
+
+use crate::prelude::*;
+
+
+// weighted sum of players' payoffs
+pub fn welfare<A: ActionType + Clone + 'static>(agg: &dyn Aggregator<A>, strategies: &Strategies<A>, weights: &Array<f64, Ix1>) -> f64 {
+    weights.dot(&agg.u(strategies))
+}
+
+#[derive(Clone)]
+pub struct PlannerResult<A: ActionType> {
+    pub status: String,
+    pub strategies: Strategies<A>,
+    // weights on each player's payoff in the planner's objective
+    pub weights: Array<f64, Ix1>,
+    pub welfare: f64,
+    pub payoffs: Array<f64, Ix1>,
+}
+
+impl<A: ActionType + Clone + 'static> PlannerResult<A> {
+    // ratio of optimal welfare to welfare in the given equilibrium, see price_of_anarchy
+    pub fn price_of_anarchy(&self, agg: &dyn Aggregator<A>, equilibrium: &Strategies<A>) -> Result<f64, DynapaiError> {
+        price_of_anarchy(self.welfare, welfare(agg, equilibrium, &self.weights))
+    }
+}
+
+// ratio of the planner's welfare to welfare in an equilibrium, an error unless equilibrium welfare is positive,
+// since the ratio means nothing otherwise (e.g. welfares -1 and -2 would give 0.5);
+// the planner's solver only finds a local optimum, so if the equilibrium does better,
+// the equilibrium itself is the best known welfare and the ratio is 1
+pub fn price_of_anarchy(planner_welfare: f64, equilibrium_welfare: f64) -> Result<f64, DynapaiError> {
+    if equilibrium_welfare.is_nan() || equilibrium_welfare <= 0. {
+        return Err(DynapaiError::InvalidParameter(format!(
+            "Price of anarchy needs positive equilibrium welfare, got {}", equilibrium_welfare
+        )));
+    }
+    Ok(f64::max(planner_welfare, equilibrium_welfare) / equilibrium_welfare)
+}
+
+
+// welfare as a function of every player's strategy, in log space
+struct PlannerObjective<'a, A: ActionType + Clone> {
+    agg: &'a dyn Aggregator<A>,
+    weights: &'a Array<f64, Ix1>,
+    t: usize,
+}
+
+impl<A: ActionType + Clone + 'static> PlannerObjective<'_, A> {
+    fn strategies_from_params(&self, params: &[f64]) -> Result<Strategies<A>, DynapaiError> {
+        Strategies::from_data(Array::from_shape_vec(
+            (self.t, self.agg.n(), A::nparams()),
+            params.iter().map(from_log).collect(),
+        )?)
+    }
+}
+
+impl<A: ActionType + Clone + 'static> CostFunction for PlannerObjective<'_, A> {
+    type Param = Vec<f64>;
+    type Output = f64;
+
+    fn cost(&self, params: &Self::Param) -> Result<Self::Output, argmin::core::Error> {
+        Ok(-welfare(self.agg, &self.strategies_from_params(params)?, self.weights))
+    }
+}
+
+// Aggregator::du_i only differentiates each player's payoff wrt their own strategy,
+// while welfare also depends on the effect on everyone else, so use central differences
+impl<A: ActionType + Clone + 'static> Gradient for PlannerObjective<'_, A> {
+    type Param = Vec<f64>;
+    type Gradient = Vec<f64>;
+
+    fn gradient(&self, params: &Self::Param) -> Result<Self::Gradient, argmin::core::Error> {
+        // cost only fails on params of the wrong length, which are never passed here
+        let grad = fd_grad(ArrayView::from(&params[..]), |x| self.cost(&x.to_vec()).unwrap_or(f64::NAN)).to_vec();
+        // the line search never terminates on nan, so fail early instead
+        if grad.iter().any(|g| !g.is_finite()) {
+            return Err(DynapaiError::NonFinitePayoff("gradient is not finite".to_string()).into());
+        }
+        Ok(grad)
+    }
+}
+
+// maximizes welfare jointly over all players' strategies, starting from strat
+fn improve<A>(
+    strat: &Strategies<A>,
+    agg: &dyn Aggregator<A>,
+    weights: &Array<f64, Ix1>,
+    options: &SolverOptions<A>,
+) -> Result<Strategies<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let obj = PlannerObjective { agg, weights, t: strat.t() };
+    let data = strat.data();
+    let params = match options.method {
+        SolverMethod::NelderMead => {
+            let init = data.to_shape((strat.t() * strat.n(), A::nparams()))?;
+            let solver = NelderMead::new(create_simplex(init.view(), options.nm_options.init_simplex_size))
+                .with_sd_tolerance(options.nm_options.tol)?;
+            Executor::new(obj, solver)
+                .configure(|state| state.max_iters(options.nm_options.iters))
+                .run()?.state.best_param
+        },
+        SolverMethod::LBFGS => {
+            let init = data.iter().map(|x| x.ln().max(MIN_LOG_ACTION)).collect::<Vec<_>>();
+            let solver = LBFGS::new(MoreThuenteLineSearch::new(), options.lbfgs_options.memory)
+                .with_tolerance_grad(options.lbfgs_options.tol_grad)?
+                .with_tolerance_cost(options.lbfgs_options.tol_cost)?;
+            Executor::new(obj, solver)
+                .configure(|state| state.param(init).max_iters(options.lbfgs_options.iters))
+                .run()?.state.best_param
+        },
+    };
+    PlannerObjective { agg, weights, t: strat.t() }.strategies_from_params(&params.unwrap())
+}
+
+// finds the strategies maximizing the weighted sum of payoffs, optimizing over all players' strategies
+// at once with options.method, restarting from the last solution until welfare stops changing
+// (up to options.tol); like the best response solvers, this only finds a local optimum
+pub fn solve_planner<A>(
+    agg: &dyn Aggregator<A>,
+    weights: &Array<f64, Ix1>,
+    options: &SolverOptions<A>,
+) -> Result<PlannerResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    if weights.len() != agg.n() {
+        return Err(DynapaiError::DimensionMismatch(format!(
+            "got {} weights for {} players", weights.len(), agg.n()
+        )));
+    }
+    let mut strat = options.init_guess.to_fixed(agg.n())?;
+    let mut last_welfare = welfare(agg, &strat, weights);
+    let mut status = format!("Reached max iterations ({})", options.iters);
+    for iter in 0..options.iters {
+        strat = improve(&strat, agg, weights, options)?;
+        let new_welfare = welfare(agg, &strat, weights);
+        options.progress(iter, || agg.u(&strat))?;
+        if isapprox(new_welfare, last_welfare, options.tol, f64::EPSILON.sqrt()) {
+            status = format!("Exited on iteration {}", iter);
+            break;
+        }
+        last_welfare = new_welfare;
+    }
+    let payoffs = agg.u(&strat);
+    check_finite(payoffs.iter(), "At planner's solution")?;
+    Ok(PlannerResult {
+        status,
+        welfare: weights.dot(&payoffs),
+        payoffs,
+        strategies: strat,
+        weights: weights.clone(),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn planner_beats_equilibrium() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let weights = array![1., 1.];
+        let equilibrium = solve(&agg, &solver_options(1)).unwrap().strategies.unwrap();
+        let planner = solve_planner(&agg, &weights, &solver_options(1)).unwrap();
+        assert!(planner.welfare >= welfare(&agg, &equilibrium, &weights));
+        assert!(planner.price_of_anarchy(&agg, &equilibrium).unwrap() >= 1.);
+
+        // stationary in every direction, including moving both players at once
+        let obj = PlannerObjective { agg: &agg, weights: &weights, t: 1 };
+        let params = planner.strategies.data().iter().map(|x| x.ln()).collect::<Vec<_>>();
+        assert!(obj.gradient(&params).unwrap().iter().all(|g| g.abs() < 1e-4));
+
+        let options = SolverOptions { method: SolverMethod::NelderMead, ..solver_options(1) };
+        let nm = solve_planner(&agg, &weights, &options).unwrap();
+        assert_close([&nm.welfare], [&planner.welfare], 1e-4);
+    }
+
+    #[test]
+    fn price_of_anarchy_is_at_least_one() {
+        assert_close([&price_of_anarchy(3., 2.).unwrap()], [&1.5], 1e-12);
+        assert_close([&price_of_anarchy(1., 2.).unwrap()], [&1.], 1e-12);
+    }
+
+    #[test]
+    fn price_of_anarchy_needs_positive_equilibrium_welfare() {
+        for (planner, equilibrium) in [(-1., -2.), (1., -1.), (1., 0.), (1., f64::NAN)] {
+            assert!(matches!(price_of_anarchy(planner, equilibrium), Err(DynapaiError::InvalidParameter(_))));
+        }
+        // spending this much makes every payoff negative
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let weights = array![1., 1.];
+        let equilibrium = Strategies::<Actions>::from_data(Array::from_elem((1, 2, 2), 10.)).unwrap();
+        assert!(welfare(&agg, &equilibrium, &weights) < 0.);
+        let planner = solve_planner(&agg, &weights, &solver_options(1)).unwrap();
+        assert!(matches!(planner.price_of_anarchy(&agg, &equilibrium), Err(DynapaiError::InvalidParameter(_))));
+    }
+}
@@ -2,12 +2,22 @@ pub use crate::aggregator::*;
 pub use crate::cost_func::*;
 pub use crate::csf::*;
 pub use crate::disaster_cost::*;
+pub use crate::error::*;
+pub use crate::markov::*;
+pub use crate::mixed::*;
+pub use crate::multistart::*;
 pub use crate::payoff_func::*;
+pub use crate::planner::*;
 pub use crate::prod_func::*;
+pub use crate::report::*;
 pub use crate::reward_func::*;
 pub use crate::risk_func::*;
+pub use crate::saved::*;
+pub use crate::serialize::*;
 pub use crate::solve::*;
+pub use crate::stackelberg::*;
 pub use crate::state::*;
 pub use crate::strategies::*;
+pub use crate::verify::*;
 
 pub use crate::utils::*;
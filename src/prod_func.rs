@@ -1,6 +1,7 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
-use numpy::ndarray::{Array, Ix1};
+use ndarray::{Array, ArrayView, Axis, Ix1, Ix2, concatenate, s};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 use std::fmt;
 
 use crate::prelude::*;
@@ -12,6 +13,23 @@ pub trait ProdFunc<A: ActionType>: DynClone + Downcast + MutatesOn<A> + Send + S
         let (s, p) = (0..actions.n()).map(|i| self.f_i(i, actions)).unzip();
         (Array::from_vec(s), Array::from_vec(p))
     }
+    // gradients of s_i and p_i wrt player i's actions
+    fn df_i(&self, i: usize, actions: &A) -> (Array<f64, Ix1>, Array<f64, Ix1>);
+
+    // some production functions carry state that is mutated between periods;
+    // the methods below describe how s, p, and that state respond to the state
+    fn state_dim(&self) -> usize { 0 }
+    // the state as a flat vector of length state_dim, and a way to overwrite it
+    fn state(&self) -> Array<f64, Ix1> { Array::zeros(0) }
+    fn set_state(&mut self, _state: ArrayView<f64, Ix1>) {}
+    // gradients of s_i and p_i wrt the state
+    fn df_i_dstate(&self, _i: usize, _actions: &A) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        (Array::zeros(self.state_dim()), Array::zeros(self.state_dim()))
+    }
+    // jacobians of the mutated state wrt the current state and wrt player i's actions
+    fn dmutate_on(&self, _i: usize, _actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        (Array::eye(self.state_dim()), Array::zeros((self.state_dim(), A::nparams())))
+    }
 
     fn n(&self) -> usize;
 }
@@ -30,10 +48,10 @@ pub struct DefaultProd {
 }
 
 impl DefaultProd {
-    pub fn new(a: Array<f64, Ix1>, alpha: Array<f64, Ix1>, b: Array<f64, Ix1>, beta: Array<f64, Ix1>) -> Result<DefaultProd, &'static str> {
+    pub fn new(a: Array<f64, Ix1>, alpha: Array<f64, Ix1>, b: Array<f64, Ix1>, beta: Array<f64, Ix1>) -> Result<DefaultProd, DynapaiError> {
         let n = a.len();
         if n != alpha.len() || n != b.len() || n != beta.len() {
-            return Err("When creating new DefaultProd: All input arrays must have the same length");
+            return Err(DynapaiError::DimensionMismatch("When creating new DefaultProd: All input arrays must have the same length".to_string()));
         }
         Ok(DefaultProd { n, a, alpha, b, beta })
     }
@@ -44,22 +62,112 @@ impl DefaultProd {
             self.b[i] * actions.xp()[i].powf(self.beta[i])
         )
     }
+
+    fn _df_i(&self, i: usize, actions: &dyn ActionType, nparams: usize) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let mut ds = Array::zeros(nparams);
+        let mut dp = Array::zeros(nparams);
+        ds[0] = self.a[i] * self.alpha[i] * actions.xs()[i].powf(self.alpha[i] - 1.);
+        dp[1] = self.b[i] * self.beta[i] * actions.xp()[i].powf(self.beta[i] - 1.);
+        (ds, dp)
+    }
+
+    // state is a followed by b
+    fn _state(&self) -> Array<f64, Ix1> {
+        concatenate![Axis(0), self.a, self.b]
+    }
+
+    fn _set_state(&mut self, state: ArrayView<f64, Ix1>) {
+        self.a.assign(&state.slice(s![..self.n]));
+        self.b.assign(&state.slice(s![self.n..]));
+    }
+
+    fn _df_i_dstate(&self, i: usize, actions: &dyn ActionType) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let mut ds = Array::zeros(2 * self.n);
+        let mut dp = Array::zeros(2 * self.n);
+        ds[i] = actions.xs()[i].powf(self.alpha[i]);
+        dp[self.n + i] = actions.xp()[i].powf(self.beta[i]);
+        (ds, dp)
+    }
+
+    fn _dmutate_on_basic(&self, _i: usize, _actions: &Actions) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        (Array::eye(2 * self.n), Array::zeros((2 * self.n, Actions::nparams())))
+    }
+
+    fn _dmutate_on_invest(&self, i: usize, _actions: &InvestActions) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        let mut dx = Array::zeros((2 * self.n, InvestActions::nparams()));
+        dx[[i, 2]] = 1.;
+        dx[[self.n + i, 3]] = 1.;
+        (Array::eye(2 * self.n), dx)
+    }
+
+    fn _dmutate_on_sharing(&self, i: usize, actions: &SharingActions) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        let n = self.n;
+        let mut dstate = Array::eye(2 * n);
+        let mut dx = Array::zeros((2 * n, SharingActions::nparams()));
+        dx[[i, 2]] = 1.;
+        dx[[n + i, 3]] = 1.;
+        // a and b have the same structure, so handle them in the same loop
+        for (offset, x, share, share_col) in [
+            (0, &self.a, actions.share_s(), 4),
+            (n, &self.b, actions.share_p(), 5),
+        ] {
+            for k in 0..n {
+                for j in 0..n {
+                    let gain = positive_bound(share[j]) * (x[j] - x[k]);
+                    if gain > 0. {
+                        dstate[[offset + k, offset + j]] += positive_bound(share[j]);
+                        dstate[[offset + k, offset + k]] -= positive_bound(share[j]);
+                        if j == i {
+                            dx[[offset + k, share_col]] += f64::exp(-share[j]) * (x[j] - x[k]);
+                        }
+                    }
+                }
+            }
+        }
+        (dstate, dx)
+    }
 }
 
 // need to do this silliness since MutatesOn<A> is not defined for all ProdFunc types
 macro_rules! default_prod_impl {
-    ($($a:ty),*) => {
+    ($($a:ty => $dmutate_on:ident),*) => {
         $(impl ProdFunc<$a> for DefaultProd {
             fn f_i(&self, i: usize, actions: &$a) -> (f64, f64) {
                 self._f_i(i, actions)
             }
 
+            fn df_i(&self, i: usize, actions: &$a) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+                self._df_i(i, actions, <$a>::nparams())
+            }
+
+            fn state_dim(&self) -> usize { 2 * self.n }
+
+            fn state(&self) -> Array<f64, Ix1> {
+                self._state()
+            }
+
+            fn set_state(&mut self, state: ArrayView<f64, Ix1>) {
+                self._set_state(state)
+            }
+
+            fn df_i_dstate(&self, i: usize, actions: &$a) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+                self._df_i_dstate(i, actions)
+            }
+
+            fn dmutate_on(&self, i: usize, actions: &$a) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+                self.$dmutate_on(i, actions)
+            }
+
             fn n(&self) -> usize { self.n }
         })*
     };
 }
 
-default_prod_impl!(Actions, InvestActions, SharingActions);
+default_prod_impl!(
+    Actions => _dmutate_on_basic,
+    InvestActions => _dmutate_on_invest,
+    SharingActions => _dmutate_on_sharing
+);
 
 impl MutatesOn<Actions> for DefaultProd {}
 
@@ -101,3 +209,39 @@ impl fmt::Display for DefaultProd {
         )
     }
 }
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum ProdFuncSpec {
+    DefaultProd { a: Vec<f64>, alpha: Vec<f64>, b: Vec<f64>, beta: Vec<f64> },
+}
+
+impl<A: ActionType + 'static> Serialize for Box<dyn ProdFunc<A>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if let Some(x) = (**self).as_any().downcast_ref::<DefaultProd>() {
+            ProdFuncSpec::DefaultProd {
+                a: x.a.to_vec(),
+                alpha: x.alpha.to_vec(),
+                b: x.b.to_vec(),
+                beta: x.beta.to_vec(),
+            }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this ProdFunc"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de, A: ActionType + 'static> Deserialize<'de> for Box<dyn ProdFunc<A>>
+where DefaultProd: ProdFunc<A>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match ProdFuncSpec::deserialize(deserializer)? {
+            ProdFuncSpec::DefaultProd { a, alpha, b, beta } => Box::new(DefaultProd::new(
+                Array::from(a), Array::from(alpha), Array::from(b), Array::from(beta),
+            ).map_err(de::Error::custom)?),
+        })
+    }
+}
@@ -1,131 +1,1295 @@
-use rayon::prelude::*;
-
 use crate::py::*;
-use crate::{init_rep, pycontainer, def_py_enum, unpack_py_enum, unpack_py_enum_expect};
+use crate::{impl_pickle, init_rep, pycontainer, def_py_enum, from_json_py_enum, unpack_py_enum, unpack_py_enum_expect};
 
 
+#[derive(Clone, Serialize, Deserialize)]
 #[pyclass(name = "SolverOptions")]
 pub struct PySolverOptions {
     pub iters: u64,
     pub tol: f64,
+    pub method: SolverMethod,
+    pub update: UpdateMode,
+    pub damping: f64,
     pub init_simplex_size: f64,
     pub nm_iters: u64,
     pub nm_tol: f64,
+    pub lbfgs_memory: usize,
+    pub lbfgs_iters: u64,
+    pub lbfgs_tol: f64,
     pub hist_size: usize,
     pub mixed_samples: usize,
+    pub mixed_tol: f64,
     pub parallel: bool,
+    pub threads: Option<usize>,
+    pub trace: bool,
+    pub verify: bool,
+    pub verify_starts: usize,
+    pub verify_spread: f64,
+    pub seed: Option<u64>,
+    pub init_mu: f64,
+    pub init_sigma: f64,
+    // called as progress(iter, payoffs) after each solver iteration
+    #[serde(skip)]
+    pub progress: Option<PyObject>,
+    #[serde(skip)]
+    pub cancel: Option<PyCancelToken>,
+    // set by run_solve for the duration of a solve
+    #[serde(skip)]
+    pub monitor: Option<SolveMonitor>,
+}
+
+// progress callbacks and cancel tokens belong to this process, so options holding them can't be pickled
+impl PyPickle for PySolverOptions {
+    fn to_state(&self) -> PyResult<String> {
+        if self.progress.is_some() || self.cancel.is_some() {
+            return Err(PyErr::from(DynapaiError::Serialization(
+                "Can't serialize SolverOptions with a progress callback or cancel token".to_string()
+            )));
+        }
+        py_to_json(self)
+    }
+
+    fn from_state(state: &str) -> PyResult<Self> {
+        py_from_json(state)
+    }
+}
+
+const DEFAULT_OPTIONS: PySolverOptions = PySolverOptions {
+    iters: 200,
+    tol: 1e-6,
+    method: SolverMethod::NelderMead,
+    update: UpdateMode::Jacobi,
+    damping: 1.0,
+    init_simplex_size: 0.1,
+    nm_iters: 200,
+    nm_tol: 1e-8,
+    lbfgs_memory: 10,
+    lbfgs_iters: 200,
+    lbfgs_tol: 1e-8,
+    hist_size: 10,
+    mixed_samples: 100,
+    mixed_tol: MIXED_TOL,
+    parallel: true,
+    threads: None,
+    trace: false,
+    verify: false,
+    verify_starts: 4,
+    verify_spread: 1.0,
+    seed: None,
+    init_mu: INIT_MU,
+    init_sigma: INIT_SIGMA,
+    progress: None,
+    cancel: None,
+    monitor: None,
+};
+
+// unit enum variants go by their serde names, the same as in model files
+fn from_name<'de, T: Deserialize<'de>>(name: &'de str) -> Result<T, DynapaiError> {
+    T::deserialize(serde::de::IntoDeserializer::into_deserializer(name))
+        .map_err(|e: serde::de::value::Error| DynapaiError::InvalidParameter(e.to_string()))
+}
+
+fn to_name<T: Serialize>(x: &T) -> String {
+    serde_json::to_value(x).ok().and_then(|v| v.as_str().map(str::to_string)).expect("unit variants serialize to strings")
+}
+
+impl PySolverOptions {
+    // sets one option from its keyword argument
+    fn set(&mut self, key: &str, value: &PyAny) -> PyResult<()> {
+        match key {
+            "iters" => self.iters = value.extract()?,
+            "tol" => self.tol = value.extract()?,
+            "method" => self.method = from_name(value.extract()?)?,
+            "update" => self.update = from_name(value.extract()?)?,
+            "damping" => self.damping = value.extract()?,
+            "init_simplex_size" => self.init_simplex_size = value.extract()?,
+            "nm_iters" => self.nm_iters = value.extract()?,
+            "nm_tol" => self.nm_tol = value.extract()?,
+            "lbfgs_memory" => self.lbfgs_memory = value.extract()?,
+            "lbfgs_iters" => self.lbfgs_iters = value.extract()?,
+            "lbfgs_tol" => self.lbfgs_tol = value.extract()?,
+            "hist_size" => self.hist_size = value.extract()?,
+            "mixed_samples" => self.mixed_samples = value.extract()?,
+            "mixed_tol" => self.mixed_tol = value.extract()?,
+            "parallel" => self.parallel = value.extract()?,
+            "threads" => self.threads = value.extract()?,
+            "trace" => self.trace = value.extract()?,
+            "verify" => self.verify = value.extract()?,
+            "verify_starts" => self.verify_starts = value.extract()?,
+            "verify_spread" => self.verify_spread = value.extract()?,
+            "seed" => self.seed = value.extract()?,
+            "init_mu" => self.init_mu = value.extract()?,
+            "init_sigma" => self.init_sigma = value.extract()?,
+            "progress" => self.progress = value.extract()?,
+            "cancel" => self.cancel = value.extract()?,
+            _ => return Err(PyErr::new::<PyTypeError, _>(format!(
+                "SolverOptions() got an unexpected keyword argument '{}'", key
+            ))),
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PySolverOptions {
+    // all options are keyword-only, and default to DEFAULT_OPTIONS
+    #[new]
+    #[args(kwargs = "**")]
+    fn new(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut options = DEFAULT_OPTIONS;
+        for (key, value) in kwargs.into_iter().flatten() {
+            options.set(key.extract()?, value)?;
+        }
+        if !(options.damping > 0. && options.damping <= 1.) {
+            return Err(PyErr::from(DynapaiError::InvalidParameter(format!("damping must be in (0, 1], got {}", options.damping))));
+        }
+        Ok(options)
+    }
+
+    fn __str__(&self) -> String {
+        let (update, method) = (to_name(&self.update), to_name(&self.method));
+        match self.method {
+            SolverMethod::NelderMead => format!(
+                "SolverOptions:\niters = {}\ntol = {}\nupdate = {}\ndamping = {}\nmethod = {}\ninit_simplex_size = {}\nnm_iters = {}\nnm_tol = {}",
+                self.iters, self.tol, update, self.damping, method, self.init_simplex_size, self.nm_iters, self.nm_tol
+            ),
+            SolverMethod::LBFGS => format!(
+                "SolverOptions:\niters = {}\ntol = {}\nupdate = {}\ndamping = {}\nmethod = {}\nlbfgs_memory = {}\nlbfgs_iters = {}\nlbfgs_tol = {}",
+                self.iters, self.tol, update, self.damping, method, self.lbfgs_memory, self.lbfgs_iters, self.lbfgs_tol
+            ),
+        }
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "SolverTrace")]
+pub struct PySolverTrace {
+    pub strategies: Array<f64, Ix4>,
+    pub payoffs: Array<f64, Ix2>,
+    pub max_change: Array<f64, Ix1>,
+    pub iters: Array<u64, Ix2>,
+    pub termination: Vec<Vec<String>>,
+}
+
+impl PySolverTrace {
+    pub fn from_trace<A: ActionType + Clone>(trace: &SolverTrace<A>) -> Self {
+        let iters = trace.iters();
+        let (t, n, nparams) = match trace.strategies.first() {
+            Some(s) => s.data().dim(),
+            None => (0, 0, 0),
+        };
+        Self {
+            strategies: Array::from_shape_vec(
+                (iters, t, n, nparams),
+                trace.strategies.iter().flat_map(|s| s.data().into_iter()).collect()
+            ).unwrap(),
+            payoffs: Array::from_shape_vec(
+                (iters, n),
+                trace.payoffs.iter().flat_map(|u| u.iter().cloned()).collect()
+            ).unwrap(),
+            max_change: Array::from(trace.max_change.clone()),
+            iters: Array::from_shape_vec(
+                (iters, n),
+                trace.best_responses.iter().flat_map(|infos| infos.iter().map(|x| x.iters)).collect()
+            ).unwrap(),
+            termination: trace.best_responses.iter().map(
+                |infos| infos.iter().map(|x| x.termination.clone()).collect()
+            ).collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl PySolverTrace {
+    // shape (iters, t, n, nparams)
+    #[getter]
+    pub fn strategies<'py>(&self, py: Python<'py>) -> &'py PyArray4<f64> {
+        self.strategies.to_pyarray(py)
+    }
+
+    // shape (iters, n)
+    #[getter]
+    pub fn payoffs<'py>(&self, py: Python<'py>) -> &'py PyArray2<f64> {
+        self.payoffs.to_pyarray(py)
+    }
+
+    #[getter]
+    pub fn max_change<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.max_change.to_pyarray(py)
+    }
+
+    // iterations used to find each player's best response, shape (iters, n)
+    #[getter]
+    pub fn iters<'py>(&self, py: Python<'py>) -> &'py PyArray2<u64> {
+        self.iters.to_pyarray(py)
+    }
+
+    #[getter]
+    pub fn termination(&self) -> Vec<Vec<String>> {
+        self.termination.clone()
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.max_change.len()
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("SolverTrace: {} iterations, max_change = {:.4e}", self.__len__(), self.max_change)
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "Verification")]
+pub struct PyVerification {
+    pub strategies: Vec<PyActions>,
+    pub payoffs: Array<f64, Ix1>,
+    pub gains: Array<f64, Ix1>,
+    pub deviations: Array<f64, Ix3>,
+}
+
+impl PyVerification {
+    pub fn from_verification<A: ActionType + Clone>(verification: &Verification<A>, strategies: &Strategies<A>) -> Self {
+        let views = verification.deviations.iter().map(|x| x.view()).collect::<Vec<_>>();
+        Self {
+            strategies: strategies.actions().iter().map(|a|
+                PyActions::from_data(a.data().clone()).unwrap()
+            ).collect(),
+            payoffs: verification.payoffs.clone(),
+            gains: verification.gains.clone(),
+            deviations: stack(Axis(0), &views).unwrap(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyVerification {
+    #[getter]
+    pub fn payoffs<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.payoffs.to_pyarray(py)
+    }
+
+    // largest gain each player can get from a unilateral deviation
+    #[getter]
+    pub fn gains<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.gains.to_pyarray(py)
+    }
+
+    #[getter]
+    pub fn epsilon(&self) -> f64 {
+        self.gains.iter().cloned().fold(0., f64::max)
+    }
+
+    // best deviation for each player, shape (n, t, nparams)
+    #[getter]
+    pub fn deviations<'py>(&self, py: Python<'py>) -> &'py PyArray3<f64> {
+        self.deviations.to_pyarray(py)
+    }
+
+    // strategies where player i plays their best deviation
+    pub fn deviation(&self, i: usize) -> PyResult<Vec<PyActions>> {
+        if i >= self.gains.len() {
+            return Err(PyErr::from(DynapaiError::InvalidParameter(format!("player index {} out of range", i))));
+        }
+        self.strategies.iter().zip(self.deviations.slice(s![i, .., ..]).outer_iter()).map(|(a, x)| {
+            let mut data = a.data().clone();
+            data.row_mut(i).assign(&x);
+            PyActions::from_data(data).map_err(PyErr::from)
+        }).collect()
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("Verification: epsilon = {:.4e}, gains = {:.4e}", self.epsilon(), self.gains)
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "SolverResult")]
+pub struct PySolverResult {
+    pub status: String,
+    pub strategies: Option<Vec<PyActions>>,
+    pub trace: Option<PySolverTrace>,
+    pub verification: Option<PyVerification>,
+}
+pycontainer!(PySolverResult(strategies: Option<Vec<PyActions>>));
+impl_pickle!(serde: PySolverTrace, PyVerification, PySolverResult);
+
+impl PySolverResult {
+    pub fn from_result(res: PyResult<PySolverResult>) -> Self {
+        match res {
+            Ok(res) => res,
+            Err(e) => PySolverResult {
+                status: format!("Error while solving: {}", e),
+                strategies: None,
+                trace: None,
+                verification: None,
+            }
+        }
+    }
+
+    pub fn from_solver_result<A: ActionType + Clone>(res: SolverResult<A>) -> Self {
+        let verification = match (&res.verification, &res.strategies) {
+            (Some(v), Some(strategies)) => Some(PyVerification::from_verification(v, strategies)),
+            _ => None,
+        };
+        PySolverResult {
+            status: res.status,
+            verification,
+            strategies: res.strategies.map(|strategies| strategies.into_actions().into_iter().map(|a|
+                PyActions::from_data(a.data().clone()).unwrap()
+            ).collect()),
+            trace: res.trace.as_ref().map(PySolverTrace::from_trace),
+        }
+    }
+}
+
+fn report_dict<'py>(py: Python<'py>, report: PeriodReport) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    dict.set_item("s", report.s.into_pyarray(py))?;
+    dict.set_item("p", report.p.into_pyarray(py))?;
+    dict.set_item("sigma", report.sigma.into_pyarray(py))?;
+    dict.set_item("q", report.q.into_pyarray(py))?;
+    dict.set_item("reward", report.reward.into_pyarray(py))?;
+    dict.set_item("disaster_cost", report.disaster_cost.into_pyarray(py))?;
+    dict.set_item("cost", report.cost.into_pyarray(py))?;
+    dict.set_item("payoff", report.payoff.into_pyarray(py))?;
+    dict.set_item("discount", report.discount.into_pyarray(py))?;
+    dict.set_item("discounted_payoff", report.discounted_payoff.into_pyarray(py))?;
+    dict.set_item("disaster_proba", report.disaster_proba.into_pyarray(py))?;
+    Ok(dict)
+}
+
+#[pymethods]
+impl PySolverResult {
+    #[new]
+    pub fn new(status: String, strategies: Option<Vec<PyActions>>) -> Self {
+        Self{ status, strategies, trace: None, verification: None }
+    }
+
+    #[getter]
+    pub fn verification(&self) -> PyResult<PyVerification> {
+        match &self.verification {
+            Some(verification) => Ok(verification.clone()),
+            None => Err(value_error("no verification recorded, solve with SolverOptions(verify = True) to record one"))
+        }
+    }
+
+    #[getter]
+    pub fn trace(&self) -> PyResult<PySolverTrace> {
+        match &self.trace {
+            Some(trace) => Ok(trace.clone()),
+            None => Err(value_error("no trace recorded, solve with SolverOptions(trace = True) to record one"))
+        }
+    }
+
+    #[getter]
+    pub fn optimum(&self) -> PyResult<Vec<PyActions>> {
+        match self.get() {
+            Some(s) => Ok(s.clone()),
+            None => Err(value_error(format!("no optimum found, status was {}", self.status)))
+        }
+    }
+
+    // per-period breakdown of the optimum, see Aggregator.report
+    pub fn report<'py>(&self, py: Python<'py>, aggregator: &PyAggregator) -> PyResult<&'py PyDict> {
+        aggregator.report(py, self.optimum()?)
+    }
+
+    // saves the optimum with the aggregator and options used to find it, see SavedResult
+    #[args(init = "None", seed = "None")]
+    pub fn save(
+        &self,
+        path: &str,
+        aggregator: &PyAggregator,
+        options: &PySolverOptions,
+        init: Option<Vec<PyActions>>,
+        seed: Option<u64>,
+    ) -> PyResult<()> {
+        PySavedResult::new(self, aggregator, options, init, seed)?.save(path)
+    }
+
+    pub fn __str__(&self) -> String {
+        let s_string = match self.get() {
+            Some(s) => s.iter().enumerate().map(|(t, a)|
+                    format!("t = {}, {}", t, a.__str__())
+                ).collect::<Vec<_>>().join("\n"),
+            None => "None".to_string()
+        };
+        format!("SolverResult:\nstatus: {}\nstrategies:\n{}", self.status, s_string)
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+def_py_enum!(SavedResultContainer(SavedResult));
+
+#[derive(Clone)]
+#[pyclass(name = "SavedResult")]
+pub struct PySavedResult(pub SavedResultContainer);
+pycontainer!(PySavedResult(SavedResultContainer));
+
+impl PyPickle for PySavedResult {
+    fn to_state(&self) -> PyResult<String> {
+        unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            py_to_json(saved)
+        }
+    }
+
+    fn from_state(state: &str) -> PyResult<Self> {
+        Ok(Self(from_json_py_enum!(state; SavedResult<_> => SavedResultContainer; |x| x)?))
+    }
+}
+
+impl PySavedResult {
+    fn new(
+        result: &PySolverResult,
+        aggregator: &PyAggregator,
+        options: &PySolverOptions,
+        init: Option<Vec<PyActions>>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let pystrategies = PyStrategies::from_actions_list(result.optimum()?)?;
+        // the seed is kept in the options, for both the initial guess and the solver
+        let options = &PySolverOptions { seed: seed.or(options.seed), ..options.clone() };
+        let mut saved = unpack_py_enum! {
+            [AggregatorContainer, StrategyContainer](aggregator, strategies) = aggregator.get(), pystrategies.get();
+            SavedResult::new(
+                result.status.clone(),
+                strategies.clone(),
+                aggregator.clone(),
+                expand_options(random_init(strategies.t(), options), options),
+            ).map_err(PyErr::from)? => SavedResultContainer
+        };
+        // payoffs were evaluated just now, so any python component errors show up here
+        check_callable(())?;
+        if let Some(init) = init {
+            let init = PyStrategies::from_actions_list(init)?;
+            unpack_py_enum! {
+                [SavedResultContainer, StrategyContainer](saved, init) = &mut saved, init.get();
+                saved.options.init_guess = InitGuess::Fixed(init.clone())
+            }
+        }
+        Ok(Self(saved))
+    }
+}
+
+#[pymethods]
+impl PySavedResult {
+    // the action type is read from the saved result.json
+    #[staticmethod]
+    pub fn load(path: &str) -> PyResult<Self> {
+        let path = std::path::Path::new(path);
+        match saved_atype(path)?.as_str() {
+            "basic" => Ok(Self(SavedResultContainer::Basic(SavedResult::load(path)?))),
+            "invest" => Ok(Self(SavedResultContainer::Invest(SavedResult::load(path)?))),
+            "sharing" => Ok(Self(SavedResultContainer::Sharing(SavedResult::load(path)?))),
+            atype => Err(PyErr::from(DynapaiError::InvalidParameter(format!(
+                "Invalid atype: {}, expected one of \"basic\", \"invest\", \"sharing\"", atype
+            )))),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> PyResult<()> {
+        unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            saved.save(std::path::Path::new(path)).map_err(PyErr::from)
+        }
+    }
+
+    #[getter]
+    pub fn status(&self) -> String {
+        unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            saved.status.clone()
+        }
+    }
+
+    // can be passed as init to Aggregator.solve to warm start from this result
+    #[getter]
+    pub fn strategies(&self) -> Vec<PyActions> {
+        PyStrategies(unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            saved.strategies.clone() => StrategyContainer
+        }).to_actions_list()
+    }
+
+    #[getter]
+    pub fn payoffs<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            saved.payoffs.to_pyarray(py)
+        }
+    }
+
+    #[getter]
+    pub fn aggregator(&self) -> PyAggregator {
+        let aggregator = unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            saved.aggregator.clone() => AggregatorContainer
+        };
+        let end_on_win = unpack_py_enum! {
+            [AggregatorContainer](aggregator) = &aggregator;
+            downcast_to_eocwin(aggregator).is_some()
+        };
+        PyAggregator { aggregator, end_on_win }
+    }
+
+    #[getter]
+    pub fn options(&self) -> PySolverOptions {
+        unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            from_options(&saved.options)
+        }
+    }
+
+    #[getter]
+    pub fn seed(&self) -> Option<u64> {
+        unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            saved.seed()
+        }
+    }
+
+    // checks the saved strategies again, using the saved aggregator and options
+    #[args(options = "&DEFAULT_OPTIONS")]
+    pub fn verify(&self, options: &PySolverOptions) -> PyResult<PyVerification> {
+        run_solve(options, |options| unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            match saved.verify(&verify_options(options)) {
+                Ok(v) => Ok(PyVerification::from_verification(&v, &saved.strategies)),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("SavedResult:\nstatus: {}\nseed: {:?}\npayoffs: {}", self.status(), self.seed(), unpack_py_enum! {
+            [SavedResultContainer](saved) = self.get();
+            &saved.payoffs
+        })
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "MixedSolverResult")]
+pub struct PyMixedSolverResult {
+    pub status: String,
+    pub support: Vec<Vec<Array<f64, Ix2>>>,
+    pub weights: Vec<Array<f64, Ix1>>,
+    pub payoffs: Array<f64, Ix1>,
+    pub exploitability: Array<f64, Ix1>,
+}
+
+impl_pickle!(serde: PyMixedSolverResult, PyEquilibrium, PyMultistartResult, PyPlannerResult, PyStackelbergResult);
+
+impl PyMixedSolverResult {
+    pub fn from_mixed_result<A: ActionType>(res: MixedSolverResult<A>) -> Self {
+        let (support, weights) = res.strategies.into_iter().map(|m|
+            (m.support, Array::from(m.weights))
+        ).unzip();
+        PyMixedSolverResult {
+            status: res.status,
+            support,
+            weights,
+            payoffs: res.payoffs,
+            exploitability: Array::from(res.exploitability),
+        }
+    }
+}
+
+#[pymethods]
+impl PyMixedSolverResult {
+    #[getter]
+    pub fn status(&self) -> String {
+        self.status.clone()
+    }
+
+    // pure strategies each player mixes over, each of shape (t, nparams)
+    #[getter]
+    pub fn support<'py>(&self, py: Python<'py>) -> Vec<Vec<&'py PyArray2<f64>>> {
+        self.support.iter().map(|s| s.iter().map(|x| x.to_pyarray(py)).collect()).collect()
+    }
+
+    // probabilities of the strategies in each player's support
+    #[getter]
+    pub fn weights<'py>(&self, py: Python<'py>) -> Vec<&'py PyArray1<f64>> {
+        self.weights.iter().map(|w| w.to_pyarray(py)).collect()
+    }
+
+    #[getter]
+    pub fn payoffs<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.payoffs.to_pyarray(py)
+    }
+
+    // largest gain from deviating to a best response, at each iteration
+    #[getter]
+    pub fn exploitability<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.exploitability.to_pyarray(py)
+    }
+
+    pub fn __str__(&self) -> String {
+        format!(
+            "MixedSolverResult:\nstatus: {}\nsupport sizes: {:?}\nexploitability: {:.4e}",
+            self.status,
+            self.support.iter().map(|s| s.len()).collect::<Vec<_>>(),
+            self.exploitability.last().cloned().unwrap_or(f64::NAN),
+        )
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "Equilibrium")]
+pub struct PyEquilibrium {
+    pub result: PySolverResult,
+    pub payoffs: Array<f64, Ix1>,
+    pub starts: Vec<usize>,
+}
+
+impl PyEquilibrium {
+    pub fn from_equilibrium<A: ActionType + Clone>(eq: Equilibrium<A>) -> Self {
+        PyEquilibrium {
+            payoffs: eq.payoffs,
+            starts: eq.starts,
+            result: PySolverResult::from_solver_result(eq.result),
+        }
+    }
+}
+
+#[pymethods]
+impl PyEquilibrium {
+    #[getter]
+    pub fn result(&self) -> PySolverResult {
+        self.result.clone()
+    }
+
+    #[getter]
+    pub fn strategies(&self) -> PyResult<Vec<PyActions>> {
+        self.result.optimum()
+    }
+
+    #[getter]
+    pub fn payoffs<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.payoffs.to_pyarray(py)
+    }
+
+    // indices of the starts that converged to this equilibrium
+    #[getter]
+    pub fn starts(&self) -> Vec<usize> {
+        self.starts.clone()
+    }
+
+    #[getter]
+    pub fn count(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("Equilibrium: count = {}, payoffs = {:.4}", self.count(), self.payoffs)
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "MultistartResult")]
+pub struct PyMultistartResult {
+    pub equilibria: Vec<PyEquilibrium>,
+    // failed starts with their error messages
+    pub failed: Vec<(usize, String)>,
+}
+
+impl PyMultistartResult {
+    pub fn from_multistart_result<A: ActionType + Clone>(res: MultistartResult<A>) -> Self {
+        PyMultistartResult {
+            equilibria: res.equilibria.into_iter().map(PyEquilibrium::from_equilibrium).collect(),
+            failed: res.failed.into_iter().map(|(k, e)| (k, e.to_string())).collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyMultistartResult {
+    // sorted by the number of starts that converged to each
+    #[getter]
+    pub fn equilibria(&self) -> Vec<PyEquilibrium> {
+        self.equilibria.clone()
+    }
+
+    // (start, error message) for each start whose solve failed
+    #[getter]
+    pub fn failed(&self) -> Vec<(usize, String)> {
+        self.failed.clone()
+    }
+
+    pub fn __str__(&self) -> String {
+        let mut out = format!("MultistartResult: {} equilibria, {} failed starts", self.equilibria.len(), self.failed.len());
+        for eq in self.equilibria.iter() {
+            out.push_str(&format!("\n{}", eq.__str__()));
+        }
+        out
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "PlannerResult")]
+pub struct PyPlannerResult {
+    pub status: String,
+    pub strategies: Vec<PyActions>,
+    pub weights: Array<f64, Ix1>,
+    pub welfare: f64,
+    pub payoffs: Array<f64, Ix1>,
+}
+
+impl PyPlannerResult {
+    pub fn from_planner_result<A: ActionType + Clone>(res: PlannerResult<A>) -> Self {
+        PyPlannerResult {
+            status: res.status,
+            strategies: res.strategies.into_actions().into_iter().map(|a|
+                PyActions::from_data(a.data().clone()).unwrap()
+            ).collect(),
+            weights: res.weights,
+            welfare: res.welfare,
+            payoffs: res.payoffs,
+        }
+    }
+}
+
+#[pymethods]
+impl PyPlannerResult {
+    #[getter]
+    pub fn status(&self) -> String {
+        self.status.clone()
+    }
+
+    #[getter]
+    pub fn strategies(&self) -> Vec<PyActions> {
+        self.strategies.clone()
+    }
+
+    #[getter]
+    pub fn weights<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.weights.to_pyarray(py)
+    }
+
+    #[getter]
+    pub fn welfare(&self) -> f64 {
+        self.welfare
+    }
+
+    #[getter]
+    pub fn payoffs<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.payoffs.to_pyarray(py)
+    }
+
+    pub fn __str__(&self) -> String {
+        format!(
+            "PlannerResult:\nstatus: {}\nwelfare: {:.4}\npayoffs: {:.4}",
+            self.status, self.welfare, self.payoffs
+        )
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+#[pyclass(name = "StackelbergResult")]
+pub struct PyStackelbergResult {
+    pub status: String,
+    pub strategies: Vec<PyActions>,
+    pub leaders: Vec<usize>,
+    pub payoffs: Array<f64, Ix1>,
+    pub unconverged: usize,
 }
 
-const DEFAULT_OPTIONS: PySolverOptions = PySolverOptions {
-    iters: 200,
-    tol: 1e-6,
-    init_simplex_size: 0.1,
-    nm_iters: 200,
-    nm_tol: 1e-8,
-    hist_size: 10,
-    mixed_samples: 100,
-    parallel: true,
-};
+impl PyStackelbergResult {
+    pub fn from_stackelberg_result<A: ActionType + Clone>(res: StackelbergResult<A>) -> Self {
+        PyStackelbergResult {
+            status: res.status,
+            strategies: res.strategies.into_actions().into_iter().map(|a|
+                PyActions::from_data(a.data().clone()).unwrap()
+            ).collect(),
+            leaders: res.leaders,
+            payoffs: res.payoffs,
+            unconverged: res.unconverged,
+        }
+    }
+}
 
 #[pymethods]
-impl PySolverOptions {
-    #[new]
-    #[args(
-        iters = "DEFAULT_OPTIONS.iters",
-        tol = "DEFAULT_OPTIONS.tol",
-        init_simplex_size = "DEFAULT_OPTIONS.init_simplex_size",
-        nm_iters = "DEFAULT_OPTIONS.nm_iters",
-        nm_tol = "DEFAULT_OPTIONS.nm_tol",
-        hist_size = "DEFAULT_OPTIONS.hist_size",
-        mixed_samples = "DEFAULT_OPTIONS.mixed_samples",
-        parallel = "DEFAULT_OPTIONS.parallel"
-    )]
-    fn new(
-        iters: u64,
-        tol: f64,
-        init_simplex_size: f64,
-        nm_iters: u64,
-        nm_tol: f64,
-        hist_size: usize,
-        mixed_samples: usize,
-        parallel: bool,
-    ) -> Self {
-        PySolverOptions { iters, tol, init_simplex_size, nm_iters, nm_tol, hist_size, mixed_samples, parallel }
+impl PyStackelbergResult {
+    #[getter]
+    pub fn status(&self) -> String {
+        self.status.clone()
     }
 
-    fn __str__(&self) -> String {
+    // leaders' commitments together with the followers' responses
+    #[getter]
+    pub fn strategies(&self) -> Vec<PyActions> {
+        self.strategies.clone()
+    }
+
+    #[getter]
+    pub fn leaders(&self) -> Vec<usize> {
+        self.leaders.clone()
+    }
+
+    #[getter]
+    pub fn payoffs<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        self.payoffs.to_pyarray(py)
+    }
+
+    // number of follower responses that did not converge
+    #[getter]
+    pub fn unconverged(&self) -> usize {
+        self.unconverged
+    }
+
+    pub fn __str__(&self) -> String {
         format!(
-            "SolverOptions:\niters = {}\ntol = {}\ninit_simplex_size = {}\nnm_iters = {}\nnm_tol = {}",
-            self.iters, self.tol, self.init_simplex_size, self.nm_iters, self.nm_tol
+            "StackelbergResult:\nstatus: {}\nleaders: {:?}\npayoffs: {:.4}",
+            self.status, self.leaders, self.payoffs
         )
     }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
 
 
+def_py_enum!(MarkovPolicyContainer(MarkovPolicy));
+
 #[derive(Clone)]
-#[pyclass(name = "SolverResult")]
-pub struct PySolverResult {
-    pub status: String,
-    pub strategies: Option<Vec<PyActions>>,
+#[pyclass(name = "MarkovPolicy")]
+pub struct PyMarkovPolicy {
+    pub policy: MarkovPolicyContainer,
+    // path of play from the aggregator's initial state
+    pub strategies: Vec<PyActions>,
 }
-pycontainer!(PySolverResult(strategies: Option<Vec<PyActions>>));
+pycontainer!(PyMarkovPolicy(policy: MarkovPolicyContainer));
 
-impl PySolverResult {
-    pub fn from_result(res: PyResult<Vec<PyActions>>) -> Self {
-        match res {
-            Ok(strategies) => PySolverResult {
-                status: "success".to_string(),
-                strategies: Some(strategies),
-            },
-            Err(e) => PySolverResult {
-                status: format!("Error while solving: {}", e),
-                strategies: None,
-            }
+// strategies are kept since recomputing them needs the aggregator
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct MarkovPolicyState<A: ActionType> {
+    policy: MarkovPolicy<A>,
+    strategies: Vec<PyActions>,
+}
+
+impl PyPickle for PyMarkovPolicy {
+    fn to_state(&self) -> PyResult<String> {
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            py_to_json(&MarkovPolicyState { policy: policy.clone(), strategies: self.strategies.clone() })
+        }
+    }
+
+    fn from_state(state: &str) -> PyResult<Self> {
+        let mut strategies = Vec::new();
+        let policy = from_json_py_enum!(state; MarkovPolicyState<_> => MarkovPolicyContainer; |x| {
+            strategies = x.strategies;
+            x.policy
+        })?;
+        Ok(Self { policy, strategies })
+    }
+}
+
+impl PyMarkovPolicy {
+    fn check_input(&self, t: usize, state: &[f64]) -> PyResult<()> {
+        let (periods, dim) = unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            (policy.t(), policy.grid.dim())
+        };
+        if t >= periods {
+            return Err(PyErr::from(DynapaiError::InvalidParameter(format!("period {} out of range, policy has {} periods", t, periods))));
         }
+        if state.len() != dim {
+            return Err(PyErr::from(DynapaiError::DimensionMismatch(format!("state should have length {}, got {}", dim, state.len()))));
+        }
+        Ok(())
     }
 }
 
 #[pymethods]
-impl PySolverResult {
-    #[new]
-    pub fn new(status: String, strategies: Option<Vec<PyActions>>) -> Self {
-        Self{ status, strategies }
+impl PyMarkovPolicy {
+    #[getter]
+    pub fn t(&self) -> usize {
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            policy.t()
+        }
     }
 
+    // coordinates of the state grid, one array per state variable
     #[getter]
-    pub fn optimum(&self) -> PyResult<Vec<PyActions>> {
-        match self.get() {
-            Some(s) => Ok(s.clone()),
-            None => Err(value_error(format!("no optimum found, status was {}", self.status)))
+    pub fn grid<'py>(&self, py: Python<'py>) -> Vec<&'py PyArray1<f64>> {
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            policy.grid.coords().iter().map(|c| c.to_pyarray(py)).collect()
+        }
+    }
+
+    // actions on the grid in each period, each of shape (*grid shape, n, nparams)
+    #[getter]
+    pub fn policies<'py>(&self, py: Python<'py>) -> Vec<&'py PyArrayDyn<f64>> {
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            policy.policies.iter().map(|p| {
+                let mut shape = policy.grid.shape();
+                shape.extend_from_slice(&p.shape()[1..]);
+                p.to_shape(IxDyn(&shape)).unwrap().to_pyarray(py)
+            }).collect()
+        }
+    }
+
+    // values on the grid in each period, each of shape (*grid shape, n)
+    #[getter]
+    pub fn values<'py>(&self, py: Python<'py>) -> Vec<&'py PyArrayDyn<f64>> {
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            policy.values.iter().map(|v| {
+                let mut shape = policy.grid.shape();
+                shape.push(v.shape()[1]);
+                v.to_shape(IxDyn(&shape)).unwrap().to_pyarray(py)
+            }).collect()
+        }
+    }
+
+    #[getter]
+    pub fn unconverged(&self) -> usize {
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            policy.unconverged
+        }
+    }
+
+    #[getter]
+    pub fn strategies(&self) -> Vec<PyActions> {
+        self.strategies.clone()
+    }
+
+    // actions in period t, interpolated at the given state
+    pub fn policy(&self, t: usize, state: Vec<f64>) -> PyResult<PyActions> {
+        self.check_input(t, &state)?;
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            match policy.policy(t, Array::from(state).view()) {
+                Ok(actions) => PyActions::from_data(actions.data().clone()).map_err(PyErr::from),
+                Err(e) => Err(e.into()),
+            }
         }
     }
 
-    // todo: add method to get more detailed information about sigma, s, p, payoffs, etc.
+    // value of the game from period t onward, interpolated at the given state
+    pub fn value<'py>(&self, py: Python<'py>, t: usize, state: Vec<f64>) -> PyResult<&'py PyArray1<f64>> {
+        self.check_input(t, &state)?;
+        unpack_py_enum! {
+            [MarkovPolicyContainer](policy) = self.get();
+            Ok(policy.value(t, Array::from(state).view()).into_pyarray(py))
+        }
+    }
 
     pub fn __str__(&self) -> String {
-        let s_string = match self.get() {
-            Some(s) => s.iter().enumerate().map(|(t, a)|
-                    format!("t = {}, {}", t, a.__str__())
-                ).collect::<Vec<_>>().join("\n"),
-            None => "None".to_string()
-        };
-        format!("SolverResult:\nstatus: {}\nstrategies:\n{}", self.status, s_string)
+        format!("MarkovPolicy: t = {}, unconverged = {}", self.t(), self.unconverged())
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+fn verify_options(options: &PySolverOptions) -> VerifyOptions {
+    VerifyOptions {
+        starts: options.verify_starts,
+        spread: options.verify_spread,
     }
 }
 
+// random guess with t periods, drawn as set in options
+fn random_init<A: ActionType + Clone>(t: usize, options: &PySolverOptions) -> InitGuess<A> {
+    InitGuess::Random { t, seed: options.seed, mu: options.init_mu, sigma: options.init_sigma }
+}
+
 fn expand_options<A: ActionType + Clone>(init_guess: InitGuess<A>, options: &PySolverOptions) -> SolverOptions<A> {
     SolverOptions {
         init_guess: init_guess,
         iters: options.iters,
         tol: options.tol,
+        method: options.method,
+        update: options.update,
+        damping: options.damping,
         nm_options: NMOptions {
             init_simplex_size: options.init_simplex_size,
             iters: options.nm_iters,
             tol: options.nm_tol,
         },
+        lbfgs_options: LBFGSOptions {
+            memory: options.lbfgs_memory,
+            iters: options.lbfgs_iters,
+            tol_grad: options.lbfgs_tol,
+            ..LBFGSOptions::default()
+        },
+        trace: options.trace,
+        verify: if options.verify {
+            Some(verify_options(options))
+        } else {
+            None
+        },
+        hist_size: options.hist_size,
+        mixed_samples: options.mixed_samples,
+        mixed_tol: options.mixed_tol,
+        parallel: options.parallel,
+        threads: options.threads,
+        seed: options.seed,
+        callback: options.monitor.as_ref().map(SolveMonitor::callback),
+    }
+}
+
+// inverse of expand_options, dropping the initial guess unless it's random
+fn from_options<A: ActionType + Clone>(options: &SolverOptions<A>) -> PySolverOptions {
+    let verify = options.verify.clone().unwrap_or_default();
+    let (init_mu, init_sigma) = match &options.init_guess {
+        InitGuess::Random { mu, sigma, .. } => (*mu, *sigma),
+        InitGuess::Fixed(_) => (INIT_MU, INIT_SIGMA),
+    };
+    PySolverOptions {
+        iters: options.iters,
+        tol: options.tol,
+        method: options.method,
+        update: options.update,
+        damping: options.damping,
+        init_simplex_size: options.nm_options.init_simplex_size,
+        nm_iters: options.nm_options.iters,
+        nm_tol: options.nm_options.tol,
+        lbfgs_memory: options.lbfgs_options.memory,
+        lbfgs_iters: options.lbfgs_options.iters,
+        lbfgs_tol: options.lbfgs_options.tol_grad,
         hist_size: options.hist_size,
         mixed_samples: options.mixed_samples,
+        mixed_tol: options.mixed_tol,
         parallel: options.parallel,
+        threads: options.threads,
+        trace: options.trace,
+        verify: options.verify.is_some(),
+        verify_starts: verify.starts,
+        verify_spread: verify.spread,
+        seed: options.seed,
+        init_mu,
+        init_sigma,
+        progress: None,
+        cancel: None,
+        monitor: None,
     }
 }
 
@@ -134,27 +1298,17 @@ macro_rules! maybe_options {
     ($atype:ident, $t:ident, $init:ident, $pyoptions:ident) => {
         {
             let init_guess = if let Some(init) = &$init {
-                InitGuess::Fixed(
-                    Strategies::from_actions(
-                        init.iter().map(|x|
-                            if let ActionContainer::$atype(a) = x.get() {
-                                a.clone()
-                            }
-                            else {
-                                panic!("wrong action type")
-                            }
-                        ).collect()
-                    )
-                )
-                
+                init.iter().map(|x| unpack_py_enum_expect!(x.get() => ActionContainer::$atype).cloned())
+                    .collect::<PyResult<Vec<_>>>()
+                    .map(|actions| InitGuess::Fixed(Strategies::from_actions(actions)))
             }
             else if let Some(t) = $t {
-                InitGuess::Random(t)
+                Ok(random_init(t, &$pyoptions))
             }
             else {
-                return PySolverResult::new(format!("{}", value_error("must provide either init or t")), None);
+                Err(value_error("must provide either init or t"))
             };
-            expand_options(init_guess, &$pyoptions)
+            init_guess.map(|init_guess| expand_options(init_guess, &$pyoptions))
         }
     };
 }
@@ -162,14 +1316,9 @@ macro_rules! maybe_options {
 macro_rules! solve_with {
     ($aggregator:expr, $options:expr) => {
         {
-            match solve($aggregator, $options) {
-                Ok(strategies) => Ok(
-                    strategies.into_actions().into_iter().map(|a|
-                        PyActions::from_data(a.data().clone()).unwrap()
-                    ).collect()
-                ),
-                Err(e) => Err(value_error(format!("Error when solving: {}", e))),
-            }
+            solve($aggregator, $options)
+                .map(PySolverResult::from_solver_result)
+                .map_err(PyErr::from)
         }
     }
 }
@@ -187,6 +1336,7 @@ pub struct PyAggregator {
     pub end_on_win: bool,
 }
 pycontainer!(PyAggregator(aggregator: AggregatorContainer));
+impl_pickle!(json: PyAggregator);
 
 #[pymethods]
 impl PyAggregator {
@@ -195,17 +1345,11 @@ impl PyAggregator {
     pub fn new(state: &PyAny, gammas: Vec<f64>, end_on_win: bool) -> PyResult<Self> {
         Ok(Self {
             aggregator: unpack_py_enum! {
-                [StateContainer](state) = as_state(state).unpack();
+                [StateContainer](state) = as_state(state)?.unpack();
                 {
-                    let discounter = match DynStateDiscounter::new(state, Array::from(gammas)) {
-                        Ok(d) => d,
-                        Err(e) => return Err(value_error(format!("Error when creating aggregator: {}", e))),
-                    };
+                    let discounter = DynStateDiscounter::new(state, Array::from(gammas))?;
                     if end_on_win {
-                        match EndsOnContestWin::new(discounter) {
-                            Ok(a) => Box::new(a),
-                            Err(e) => return Err(value_error(format!("Error when creating aggregator: {}", e))),
-                        }
+                        Box::new(EndsOnContestWin::new(discounter)?)
                     }
                     else {
                         Box::new(discounter)
@@ -234,7 +1378,7 @@ impl PyAggregator {
         let pystrategies = PyStrategies::from_actions_list(strategies)?;
         unpack_py_enum! {
             [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
-            Ok(aggregator.u_i(i, &strategies))
+            check_callable(aggregator.u_i(i, &strategies))
         }
     }
 
@@ -242,7 +1386,15 @@ impl PyAggregator {
         let pystrategies = PyStrategies::from_actions_list(strategies)?;
         unpack_py_enum! {
             [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
-            Ok(aggregator.u(&strategies).into_pyarray(py))
+            Ok(check_callable(aggregator.u(&strategies))?.into_pyarray(py))
+        }
+    }
+
+    pub fn du_i<'py>(&self, py: Python<'py>, i: usize, strategies: Vec<PyActions>) -> PyResult<&'py PyArray2<f64>> {
+        let pystrategies = PyStrategies::from_actions_list(strategies)?;
+        unpack_py_enum! {
+            [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
+            Ok(check_callable(aggregator.du_i(i, strategies))?.into_pyarray(py))
         }
     }
 
@@ -255,33 +1407,234 @@ impl PyAggregator {
             [AggregatorContainer, StrategyContainer](agg_box, strategies) = self.get(), pystrategies.get();
             {
                 let aggregator = downcast_to_eocwin(agg_box).unwrap();
-                Ok(aggregator.probas(&strategies).into_pyarray(py))
+                Ok(check_callable(aggregator.probas(&strategies))?.into_pyarray(py))
             }
         }
     }
 
+    // per-period breakdown of payoffs along the path of play, as a dict of arrays of shape (t, n),
+    // where entry (t, i) is computed under player i's beliefs
+    pub fn report<'py>(&self, py: Python<'py>, strategies: Vec<PyActions>) -> PyResult<&'py PyDict> {
+        let pystrategies = PyStrategies::from_actions_list(strategies)?;
+        let report = unpack_py_enum! {
+            [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
+            report(aggregator.as_ref(), strategies)
+        };
+        report_dict(py, check_callable(report)?.map_err(PyErr::from)?)
+    }
+
     #[args(t = "None", init = "None", options = "&DEFAULT_OPTIONS")]
-    pub fn solve(&self, t: Option<usize>, init: Option<Vec<PyActions>>, options: &PySolverOptions) -> PySolverResult {
-        let res = match self.get() {
+    pub fn solve(&self, t: Option<usize>, init: Option<Vec<PyActions>>, options: &PySolverOptions) -> PyResult<PySolverResult> {
+        run_solve(options, |options| match self.get() {
             AggregatorContainer::Basic(aggregator) => {
-                let options = maybe_options!(Basic, t, init, options);
+                let options = maybe_options!(Basic, t, init, options)?;
                 solve_with!(aggregator.as_ref(), &options)
             },
             AggregatorContainer::Invest(aggregator) => {
-                let options = maybe_options!(Invest, t, init, options);
+                let options = maybe_options!(Invest, t, init, options)?;
                 solve_with!(aggregator.as_ref(), &options)
             },
             AggregatorContainer::Sharing(aggregator) => {
-                let options = maybe_options!(Sharing, t, init, options);
+                let options = maybe_options!(Sharing, t, init, options)?;
                 solve_with!(aggregator.as_ref(), &options)
             },
-        };
-        match res {
-            Ok(strategies) => PySolverResult::new("success".to_string(), Some(strategies)),
-            Err(e) => PySolverResult::new(format!("{}", e), None),
+        })
+    }
+
+    // mixed strategy equilibrium by fictitious play, see SolverOptions.hist_size, mixed_samples and mixed_tol
+    #[args(t = "None", init = "None", options = "&DEFAULT_OPTIONS")]
+    pub fn solve_mixed(&self, t: Option<usize>, init: Option<Vec<PyActions>>, options: &PySolverOptions) -> PyResult<PyMixedSolverResult> {
+        run_solve(options, |options| {
+            let res = match (init, t) {
+                (Some(init), _) => {
+                    let pystrategies = PyStrategies::from_actions_list(init)?;
+                    unpack_py_enum! {
+                        [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
+                        solve_mixed(aggregator.as_ref(), &expand_options(InitGuess::Fixed(strategies.clone()), options))
+                            .map(PyMixedSolverResult::from_mixed_result)
+                    }
+                },
+                (None, Some(t)) => unpack_py_enum! {
+                    [AggregatorContainer](aggregator) = self.get();
+                    solve_mixed(aggregator.as_ref(), &expand_options(random_init(t, options), options))
+                        .map(PyMixedSolverResult::from_mixed_result)
+                },
+                (None, None) => return Err(value_error("must provide either init or t")),
+            };
+            res.map_err(PyErr::from)
+        })
+    }
+
+    // strategies maximizing the weighted sum of payoffs (equal weights if none are given)
+    #[args(t = "None", init = "None", weights = "None", options = "&DEFAULT_OPTIONS")]
+    pub fn solve_planner(
+        &self,
+        t: Option<usize>,
+        init: Option<Vec<PyActions>>,
+        weights: Option<Vec<f64>>,
+        options: &PySolverOptions,
+    ) -> PyResult<PyPlannerResult> {
+        run_solve(options, |options| {
+            let weights = match weights {
+                Some(w) => Array::from(w),
+                None => Array::ones(unpack_py_enum! {
+                    [AggregatorContainer](aggregator) = self.get();
+                    aggregator.n()
+                }),
+            };
+            let res = match (init, t) {
+                (Some(init), _) => {
+                    let pystrategies = PyStrategies::from_actions_list(init)?;
+                    unpack_py_enum! {
+                        [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
+                        solve_planner(aggregator.as_ref(), &weights, &expand_options(InitGuess::Fixed(strategies.clone()), options))
+                            .map(PyPlannerResult::from_planner_result)
+                    }
+                },
+                (None, Some(t)) => unpack_py_enum! {
+                    [AggregatorContainer](aggregator) = self.get();
+                    solve_planner(aggregator.as_ref(), &weights, &expand_options(random_init(t, options), options))
+                        .map(PyPlannerResult::from_planner_result)
+                },
+                (None, None) => return Err(value_error("must provide either init or t")),
+            };
+            res.map_err(PyErr::from)
+        })
+    }
+
+    // leaders commit to their strategies first, anticipating the other players' nash response
+    #[args(t = "None", init = "None", options = "&DEFAULT_OPTIONS")]
+    pub fn solve_stackelberg(
+        &self,
+        leaders: Vec<usize>,
+        t: Option<usize>,
+        init: Option<Vec<PyActions>>,
+        options: &PySolverOptions,
+    ) -> PyResult<PyStackelbergResult> {
+        run_solve(options, |options| {
+            let res = match (init, t) {
+                (Some(init), _) => {
+                    let pystrategies = PyStrategies::from_actions_list(init)?;
+                    unpack_py_enum! {
+                        [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
+                        solve_stackelberg(aggregator.as_ref(), &leaders, &expand_options(InitGuess::Fixed(strategies.clone()), options))
+                            .map(PyStackelbergResult::from_stackelberg_result)
+                    }
+                },
+                (None, Some(t)) => unpack_py_enum! {
+                    [AggregatorContainer](aggregator) = self.get();
+                    solve_stackelberg(aggregator.as_ref(), &leaders, &expand_options(random_init(t, options), options))
+                        .map(PyStackelbergResult::from_stackelberg_result)
+                },
+                (None, None) => return Err(value_error("must provide either init or t")),
+            };
+            res.map_err(PyErr::from)
+        })
+    }
+
+    // ratio of the planner's welfare to welfare in the given equilibrium, using the planner's weights;
+    // 1 if the equilibrium does better than the (locally optimal) planner,
+    // and InvalidParameterError unless equilibrium welfare is positive
+    pub fn price_of_anarchy(&self, planner: &PyPlannerResult, equilibrium: Vec<PyActions>) -> PyResult<f64> {
+        let pystrategies = PyStrategies::from_actions_list(equilibrium)?;
+        unpack_py_enum! {
+            [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
+            {
+                if planner.weights.len() != aggregator.n() {
+                    return Err(PyErr::from(DynapaiError::DimensionMismatch(format!(
+                        "planner result has {} weights but aggregator has {} players",
+                        planner.weights.len(), aggregator.n()
+                    ))));
+                }
+                Ok(price_of_anarchy(planner.welfare, welfare(aggregator.as_ref(), strategies, &planner.weights))?)
+            }
         }
     }
 
+    #[args(options = "&DEFAULT_OPTIONS", starts = "None", spread = "None")]
+    pub fn verify(
+        &self,
+        strategies: Vec<PyActions>,
+        options: &PySolverOptions,
+        starts: Option<usize>,
+        spread: Option<f64>,
+    ) -> PyResult<PyVerification> {
+        let verify_options = VerifyOptions {
+            starts: starts.unwrap_or(options.verify_starts),
+            spread: spread.unwrap_or(options.verify_spread),
+        };
+        let pystrategies = PyStrategies::from_actions_list(strategies)?;
+        run_solve(options, |options| unpack_py_enum! {
+            [AggregatorContainer, StrategyContainer](aggregator, strategies) = self.get(), pystrategies.get();
+            {
+                let solver_options = expand_options(InitGuess::Fixed(strategies.clone()), options);
+                match verify(aggregator.as_ref(), strategies, &solver_options, &verify_options) {
+                    Ok(v) => Ok(PyVerification::from_verification(&v, strategies)),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        })
+    }
+
+    // solve from several random starts, drawn like options' initial guess (seed, init_mu and init_sigma),
+    // returning distinct equilibria; tol is the tolerance on payoffs and strategies
+    // used to decide if two solutions are the same
+    #[args(
+        starts = "16",
+        tol = "1e-3",
+        options = "&DEFAULT_OPTIONS"
+    )]
+    pub fn solve_multistart(
+        &self,
+        t: usize,
+        starts: usize,
+        tol: f64,
+        options: &PySolverOptions,
+    ) -> PyResult<PyMultistartResult> {
+        run_solve(options, |options| {
+            let multistart_options = MultistartOptions {
+                starts,
+                payoff_tol: tol,
+                strategy_tol: tol,
+            };
+            unpack_py_enum! {
+                [AggregatorContainer](aggregator) = self.get();
+                {
+                    let solver_options = expand_options(random_init(t, options), options);
+                    match solve_multistart(aggregator.as_ref(), &solver_options, &multistart_options) {
+                        Ok(res) => Ok(PyMultistartResult::from_multistart_result(res)),
+                        Err(e) => Err(e.into()),
+                    }
+                }
+            }
+        })
+    }
+
+    // solves for a markov perfect equilibrium by backward induction,
+    // with policies defined on a grid given as coordinates for each entry of State.state_vec;
+    // continuation values are clamped outside of the grid, so it should cover the states of interest
+    #[args(options = "&DEFAULT_OPTIONS")]
+    pub fn solve_markov(&self, grid: Vec<Vec<f64>>, t: usize, options: &PySolverOptions) -> PyResult<PyMarkovPolicy> {
+        run_solve(options, |options| {
+            let grid = StateGrid::new(grid.into_iter().map(Array::from).collect())?;
+            let policy = unpack_py_enum! {
+                [AggregatorContainer](aggregator) = self.get();
+                {
+                    let solver_options = expand_options(random_init(t, options), options);
+                    solve_markov(aggregator.as_ref(), &grid, &solver_options)?
+                } => MarkovPolicyContainer
+            };
+            let strategies = unpack_py_enum! {
+                [AggregatorContainer, MarkovPolicyContainer](aggregator, policy) = self.get(), &policy;
+                match policy.simulate(aggregator.as_ref()) {
+                    Ok(s) => s.actions().iter().map(|a| PyActions::from_data(a.data().clone()).unwrap()).collect(),
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            Ok(PyMarkovPolicy { policy, strategies })
+        })
+    }
+
     pub fn state0(&self) -> PyState {
         PyState {
             state: unpack_py_enum! {
@@ -292,10 +1645,10 @@ impl PyAggregator {
         }
     }
 
-    pub fn states(&self, strategies: Vec<PyActions>) -> Vec<PyState> {
+    pub fn states(&self, strategies: Vec<PyActions>) -> PyResult<Vec<PyState>> {
         let mut states = Vec::with_capacity(strategies.len());
         states.push(self.state0());
-        for actions in strategies[0..strategies.len() - 1].iter() {
+        for actions in strategies[0..strategies.len().saturating_sub(1)].iter() {
             let last_state = states.last().unwrap();
             states.push(
                 PyState {
@@ -311,7 +1664,7 @@ impl PyAggregator {
                 }
             );
         }
-        states
+        Ok(states)
     }
 
     #[getter]
@@ -319,9 +1672,46 @@ impl PyAggregator {
         format!("{}", self.get().object_type())
     }
 
+    pub fn to_json(&self) -> PyResult<String> {
+        unpack_py_enum! {
+            [AggregatorContainer](aggregator) = self.get();
+            py_to_json(aggregator)
+        }
+    }
+
+    #[staticmethod]
+    pub fn from_json(s: &str) -> PyResult<Self> {
+        let aggregator = from_json_py_enum!(s; Aggregator => AggregatorContainer; |x| x)?;
+        let end_on_win = unpack_py_enum! {
+            [AggregatorContainer](aggregator) = &aggregator;
+            downcast_to_eocwin(aggregator).is_some()
+        };
+        Ok(Self { aggregator, end_on_win })
+    }
+
     pub fn __str__(&self) -> String {
         format!("Aggregator: atype = {}, end_on_win = {}", self.atype(), self.end_on_win)
     }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
 
 
@@ -337,6 +1727,22 @@ pub enum ScenarioContainer {
 pub struct PyScenario(pub ScenarioContainer);
 pycontainer!(PyScenario(ScenarioContainer));
 
+type AggregatorList<A> = Vec<Box<dyn Aggregator<A>>>;
+
+impl PyPickle for PyScenario {
+    fn to_state(&self) -> PyResult<String> {
+        match self.get() {
+            ScenarioContainer::Basic(aggregators) => py_to_json(aggregators),
+            ScenarioContainer::Invest(aggregators) => py_to_json(aggregators),
+            ScenarioContainer::Sharing(aggregators) => py_to_json(aggregators),
+        }
+    }
+
+    fn from_state(state: &str) -> PyResult<Self> {
+        Ok(Self(from_json_py_enum!(state; AggregatorList<_> => ScenarioContainer; |x| x)?))
+    }
+}
+
 macro_rules! build_agg_with_type {
     ( $aggregators:expr ; $obj_type:ident ) => {
         {
@@ -377,35 +1783,57 @@ impl PyScenario {
     }
 
     #[args(t = "None", init = "None", options = "&DEFAULT_OPTIONS")]
-    pub fn solve(&self, t: Option<usize>, init: Option<Vec<PyActions>>, options: &PySolverOptions) -> Vec<PySolverResult> {
-        match self.get() {
-            ScenarioContainer::Basic(scenario) => {
-                scenario.par_iter().map(|aggregator| {
-                    let res = solve_with!(
-                        aggregator.as_ref(),
-                        &maybe_options!(Basic, t, init, options)
-                    );
-                    PySolverResult::from_result(res)
-                }).collect()
-            },
-            ScenarioContainer::Invest(scenario) => {
-                scenario.par_iter().map(|aggregator| {
-                    let res = solve_with!(
-                        aggregator.as_ref(),
-                        &maybe_options!(Invest, t, init, options)
-                    );
-                    PySolverResult::from_result(res)
-                }).collect()
-            }
-            ScenarioContainer::Sharing(scenario) => {
-                scenario.par_iter().map(|aggregator| {
-                    let res = solve_with!(
-                        aggregator.as_ref(),
-                        &maybe_options!(Sharing, t, init, options)
-                    );
-                    PySolverResult::from_result(res)
-                }).collect()
-            }
-        }
+    pub fn solve(&self, t: Option<usize>, init: Option<Vec<PyActions>>, options: &PySolverOptions) -> PyResult<Vec<PySolverResult>> {
+        let solve_all = |options: &PySolverOptions| match self.get() {
+            ScenarioContainer::Basic(scenario) => map_range(scenario.len(), options.parallel, |i| {
+                let res = maybe_options!(Basic, t, init, options)
+                    .and_then(|options| solve_with!(scenario[i].as_ref(), &options));
+                PySolverResult::from_result(res)
+            }),
+            ScenarioContainer::Invest(scenario) => map_range(scenario.len(), options.parallel, |i| {
+                let res = maybe_options!(Invest, t, init, options)
+                    .and_then(|options| solve_with!(scenario[i].as_ref(), &options));
+                PySolverResult::from_result(res)
+            }),
+            ScenarioContainer::Sharing(scenario) => map_range(scenario.len(), options.parallel, |i| {
+                let res = maybe_options!(Sharing, t, init, options)
+                    .and_then(|options| solve_with!(scenario[i].as_ref(), &options));
+                PySolverResult::from_result(res)
+            }),
+        };
+        // scenarios share one thread pool, rather than each solve creating its own
+        run_solve(options, |options| Ok(match with_threads(options.threads, || Ok(solve_all(options))) {
+            Ok(results) => results,
+            Err(e) => vec![PySolverResult::new(format!("Error when creating thread pool: {}", e), None)],
+        }))
+    }
+
+    pub fn __str__(&self) -> String {
+        let (atype, len) = match self.get() {
+            ScenarioContainer::Basic(aggregators) => (ObjectType::Basic, aggregators.len()),
+            ScenarioContainer::Invest(aggregators) => (ObjectType::Invest, aggregators.len()),
+            ScenarioContainer::Sharing(aggregators) => (ObjectType::Sharing, aggregators.len()),
+        };
+        format!("Scenario: atype = {}, {} aggregators", atype, len)
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
     }
 }
@@ -0,0 +1,429 @@
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+use ndarray::ArrayView1;
+use pyo3::exceptions::PyTypeError;
+
+use crate::py::*;
+
+// the component traits can't return errors, so the first exception raised by a python component
+// is kept in an error slot and the component returns nan in its place;
+// check_callable and run_solve raise it once control is back in python
+pub type ErrorSlot = Arc<Mutex<Option<PyErr>>>;
+
+thread_local! {
+    // the slot of the solve running on this thread, see run_solve;
+    // each thread starts with a slot of its own for evaluations called directly from python
+    static CALLABLE_ERROR: RefCell<ErrorSlot> = RefCell::default();
+}
+
+// makes components called on this thread record errors in slot
+pub fn install_error_slot(slot: ErrorSlot) {
+    CALLABLE_ERROR.with(|s| *s.borrow_mut() = slot);
+}
+
+fn record_error(e: PyErr) {
+    CALLABLE_ERROR.with(|s| {
+        s.borrow().lock().unwrap().get_or_insert(e);
+    });
+}
+
+fn take_callable_error() -> Option<PyErr> {
+    CALLABLE_ERROR.with(|s| s.borrow().lock().unwrap().take())
+}
+
+// raises the error from any python component that failed while computing x
+pub fn check_callable<T>(x: T) -> PyResult<T> {
+    match take_callable_error() {
+        Some(e) => Err(e),
+        None => Ok(x),
+    }
+}
+
+
+// a python function along with its gradient, if provided
+#[derive(Clone)]
+pub struct PyFn {
+    f: PyObject,
+    grad: Option<PyObject>,
+}
+
+impl PyFn {
+    // uses obj.name and obj.dname if obj has a method called name,
+    // otherwise obj itself is the function and grad its gradient
+    fn resolve(obj: &PyAny, name: &str, dname: &str, grad: Option<&PyAny>) -> PyResult<Self> {
+        let (f, grad) = if obj.hasattr(name)? {
+            let grad = if obj.hasattr(dname)? { Some(obj.getattr(dname)?) } else { grad };
+            (obj.getattr(name)?, grad)
+        }
+        else {
+            (obj, grad)
+        };
+        if !f.is_callable() {
+            return Err(PyTypeError::new_err(format!("expected a callable or an object with a {} method", name)));
+        }
+        if let Some(grad) = grad {
+            if !grad.is_callable() {
+                return Err(PyTypeError::new_err(format!("{} must be callable", dname)));
+            }
+        }
+        Ok(PyFn { f: f.into(), grad: grad.map(|g| g.into()) })
+    }
+
+    fn repr(&self) -> String {
+        Python::with_gil(|py| match self.f.as_ref(py).repr() {
+            Ok(r) => r.to_string(),
+            Err(_) => "?".to_string(),
+        })
+    }
+}
+
+fn value(res: PyResult<PyObject>) -> f64 {
+    Python::with_gil(|py| match res.and_then(|x| x.extract::<f64>(py)) {
+        Ok(x) => x,
+        Err(e) => {
+            record_error(e);
+            f64::NAN
+        },
+    })
+}
+
+fn to_vector(x: &PyAny, len: usize) -> PyResult<Array1<f64>> {
+    let x = Array::from(x.extract::<Vec<f64>>()?);
+    if x.len() != len {
+        return Err(DynapaiError::DimensionMismatch(format!(
+            "gradient from python has length {}, expected {}", x.len(), len
+        )).into());
+    }
+    Ok(x)
+}
+
+fn vector(res: PyResult<PyObject>, len: usize) -> Array1<f64> {
+    Python::with_gil(|py| match res.and_then(|x| to_vector(x.as_ref(py), len)) {
+        Ok(x) => x,
+        Err(e) => {
+            record_error(e);
+            Array::from_elem(len, f64::NAN)
+        },
+    })
+}
+
+fn vector_pair(res: PyResult<PyObject>, len: usize) -> (Array1<f64>, Array1<f64>) {
+    Python::with_gil(|py| {
+        let pair = res.and_then(|x| {
+            let (a, b): (&PyAny, &PyAny) = x.as_ref(py).extract()?;
+            Ok((to_vector(a, len)?, to_vector(b, len)?))
+        });
+        match pair {
+            Ok(pair) => pair,
+            Err(e) => {
+                record_error(e);
+                (Array::from_elem(len, f64::NAN), Array::from_elem(len, f64::NAN))
+            },
+        }
+    })
+}
+
+// actions as passed to python functions
+fn py_actions<A: ActionType>(py: Python, actions: &A) -> PyResult<PyObject> {
+    Ok(PyActions::from_data(actions.data().clone())?.into_py(py))
+}
+
+// gradient of f wrt row i of the actions' data
+fn fd_grad_actions<A, F>(i: usize, actions: &A, f: F) -> Array1<f64>
+where A: ActionType, F: Fn(&A) -> f64
+{
+    let data = actions.data();
+    fd_grad(data.row(i), |row| {
+        let mut data = data.clone();
+        data.row_mut(i).assign(&row);
+        match A::from_array(data) {
+            Ok(actions) => f(&actions),
+            Err(_) => f64::NAN,
+        }
+    })
+}
+
+
+// RiskFunc from sigma_i(i, s, p), with gradient dsigma_i(i, s, p) -> (ds, dp)
+#[derive(Clone)]
+pub struct CallableRisk {
+    n: usize,
+    sigma_i: PyFn,
+}
+
+impl CallableRisk {
+    pub fn new(obj: &PyAny, n: usize, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(CallableRisk { n, sigma_i: PyFn::resolve(obj, "sigma_i", "dsigma_i", grad)? })
+    }
+}
+
+impl RiskFunc for CallableRisk {
+    fn sigma_i(&self, i: usize, s: ArrayView1<f64>, p: ArrayView1<f64>) -> f64 {
+        value(Python::with_gil(|py| self.sigma_i.f.call1(py, (i, s.to_pyarray(py), p.to_pyarray(py)))))
+    }
+
+    fn dsigma_i(&self, i: usize, s: ArrayView1<f64>, p: ArrayView1<f64>) -> (Array1<f64>, Array1<f64>) {
+        match &self.sigma_i.grad {
+            Some(grad) => vector_pair(
+                Python::with_gil(|py| grad.call1(py, (i, s.to_pyarray(py), p.to_pyarray(py)))),
+                s.len(),
+            ),
+            None => (
+                fd_grad(s, |s| self.sigma_i(i, s, p)),
+                fd_grad(p, |p| self.sigma_i(i, s, p)),
+            ),
+        }
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+
+// CSF from q_i(i, p), with gradient dq_i(i, p)
+#[derive(Clone)]
+pub struct CallableCSF {
+    q_i: PyFn,
+}
+
+impl CallableCSF {
+    pub fn new(obj: &PyAny, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(CallableCSF { q_i: PyFn::resolve(obj, "q_i", "dq_i", grad)? })
+    }
+}
+
+impl CSF for CallableCSF {
+    fn q_i(&self, i: usize, p: ArrayView1<f64>) -> f64 {
+        value(Python::with_gil(|py| self.q_i.f.call1(py, (i, p.to_pyarray(py)))))
+    }
+
+    fn dq_i(&self, i: usize, p: ArrayView1<f64>) -> Array1<f64> {
+        match &self.q_i.grad {
+            Some(grad) => vector(Python::with_gil(|py| grad.call1(py, (i, p.to_pyarray(py)))), p.len()),
+            None => fd_grad(p, |p| self.q_i(i, p)),
+        }
+    }
+}
+
+
+// RewardFunc from win_i(i, p), player i's reward if it wins, and lose_i(j, p), every other player's reward if player j wins,
+// as in LinearReward, with gradients dwin_i and dlose_i; losers get nothing if there is no lose_i
+#[derive(Clone)]
+pub struct CallableReward {
+    n: usize,
+    win_i: PyFn,
+    lose_i: Option<PyFn>,
+}
+
+impl CallableReward {
+    pub fn new(
+        obj: &PyAny, n: usize, lose: Option<&PyAny>,
+        dwin: Option<&PyAny>, dlose: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        let win_i = PyFn::resolve(obj, "win_i", "dwin_i", dwin)?;
+        let lose_i = if obj.hasattr("lose_i")? {
+            Some(PyFn::resolve(obj, "lose_i", "dlose_i", dlose)?)
+        }
+        else {
+            lose.map(|lose| PyFn::resolve(lose, "lose_i", "dlose_i", dlose)).transpose()?
+        };
+        Ok(CallableReward { n, win_i, lose_i })
+    }
+
+    fn call(f: &PyFn, i: usize, p: ArrayView1<f64>) -> f64 {
+        value(Python::with_gil(|py| f.f.call1(py, (i, p.to_pyarray(py)))))
+    }
+
+    fn grad(f: &PyFn, i: usize, p: ArrayView1<f64>) -> Array1<f64> {
+        match &f.grad {
+            Some(grad) => vector(Python::with_gil(|py| grad.call1(py, (i, p.to_pyarray(py)))), p.len()),
+            None => fd_grad(p, |p| Self::call(f, i, p)),
+        }
+    }
+}
+
+impl RewardFunc for CallableReward {
+    fn reward_ij(&self, i: usize, j: usize, p: ArrayView1<f64>) -> f64 {
+        match (i == j, &self.lose_i) {
+            (true, _) => Self::call(&self.win_i, i, p),
+            (false, Some(f)) => Self::call(f, j, p),
+            (false, None) => 0.,
+        }
+    }
+
+    fn dreward_ij(&self, i: usize, j: usize, p: ArrayView1<f64>) -> Array1<f64> {
+        match (i == j, &self.lose_i) {
+            (true, _) => Self::grad(&self.win_i, i, p),
+            (false, Some(f)) => Self::grad(f, j, p),
+            (false, None) => Array::zeros(p.len()),
+        }
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+
+// DisasterCost from d_ij(i, j, s, p), the cost to player i if player j causes a disaster,
+// with gradient dd_ij(i, j, s, p) -> (ds, dp), and constant costs d_none if nobody wins
+#[derive(Clone)]
+pub struct CallableDisasterCost {
+    n: usize,
+    d_ij: PyFn,
+    d_none: Array1<f64>,
+}
+
+impl CallableDisasterCost {
+    pub fn new(obj: &PyAny, n: usize, d_none: Array1<f64>, grad: Option<&PyAny>) -> PyResult<Self> {
+        if d_none.len() != n {
+            return Err(DynapaiError::DimensionMismatch(format!("d_none has length {}, expected {}", d_none.len(), n)).into());
+        }
+        Ok(CallableDisasterCost { n, d_ij: PyFn::resolve(obj, "d_ij", "dd_ij", grad)?, d_none })
+    }
+}
+
+impl DisasterCost for CallableDisasterCost {
+    fn d_ij(&self, i: usize, j: usize, s: ArrayView1<f64>, p: ArrayView1<f64>) -> f64 {
+        value(Python::with_gil(|py| self.d_ij.f.call1(py, (i, j, s.to_pyarray(py), p.to_pyarray(py)))))
+    }
+
+    fn dd_ij(&self, i: usize, j: usize, s: ArrayView1<f64>, p: ArrayView1<f64>) -> (Array1<f64>, Array1<f64>) {
+        match &self.d_ij.grad {
+            Some(grad) => vector_pair(
+                Python::with_gil(|py| grad.call1(py, (i, j, s.to_pyarray(py), p.to_pyarray(py)))),
+                s.len(),
+            ),
+            None => (
+                fd_grad(s, |s| self.d_ij(i, j, s, p)),
+                fd_grad(p, |p| self.d_ij(i, j, s, p)),
+            ),
+        }
+    }
+
+    fn d_none(&self, i: usize, _s: ArrayView1<f64>, _p: ArrayView1<f64>) -> f64 {
+        self.d_none[i]
+    }
+
+    fn dd_none(&self, _i: usize, s: ArrayView1<f64>, p: ArrayView1<f64>) -> (Array1<f64>, Array1<f64>) {
+        (Array::zeros(s.len()), Array::zeros(p.len()))
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+
+// ProdFunc from f_i(i, actions) -> (s_i, p_i), with gradient df_i(i, actions) -> (ds, dp);
+// works with any action type, but carries no state, so investment doesn't change it
+#[derive(Clone)]
+pub struct CallableProd {
+    n: usize,
+    f_i: PyFn,
+}
+
+impl CallableProd {
+    pub fn new(obj: &PyAny, n: usize, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(CallableProd { n, f_i: PyFn::resolve(obj, "f_i", "df_i", grad)? })
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+}
+
+impl<A: ActionType> MutatesOn<A> for CallableProd {}
+
+impl<A: ActionType + Clone + 'static> ProdFunc<A> for CallableProd {
+    fn f_i(&self, i: usize, actions: &A) -> (f64, f64) {
+        Python::with_gil(|py| {
+            let res = py_actions(py, actions)
+                .and_then(|actions| self.f_i.f.call1(py, (i, actions)))
+                .and_then(|x| x.extract::<(f64, f64)>(py));
+            match res {
+                Ok(x) => x,
+                Err(e) => {
+                    record_error(e);
+                    (f64::NAN, f64::NAN)
+                },
+            }
+        })
+    }
+
+    fn df_i(&self, i: usize, actions: &A) -> (Array1<f64>, Array1<f64>) {
+        match &self.f_i.grad {
+            Some(grad) => vector_pair(
+                Python::with_gil(|py| py_actions(py, actions).and_then(|actions| grad.call1(py, (i, actions)))),
+                A::nparams(),
+            ),
+            None => (
+                fd_grad_actions(i, actions, |actions| self.f_i(i, actions).0),
+                fd_grad_actions(i, actions, |actions| self.f_i(i, actions).1),
+            ),
+        }
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+impl std::fmt::Display for CallableProd {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "CallableProd {{ f_i = {} }}", self.f_i.repr())
+    }
+}
+
+
+// CostFunc from c_i(i, actions), with gradient dc_i(i, actions) wrt player i's actions
+#[derive(Clone)]
+pub struct CallableCost {
+    n: usize,
+    c_i: PyFn,
+}
+
+impl CallableCost {
+    pub fn new(obj: &PyAny, n: usize, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(CallableCost { n, c_i: PyFn::resolve(obj, "c_i", "dc_i", grad)? })
+    }
+}
+
+impl CallableCost {
+    fn _c_i<A: ActionType>(&self, i: usize, actions: &A) -> f64 {
+        value(Python::with_gil(|py| py_actions(py, actions).and_then(|actions| self.c_i.f.call1(py, (i, actions)))))
+    }
+
+    fn _dc_i<A: ActionType>(&self, i: usize, actions: &A, nparams: usize) -> Array1<f64> {
+        match &self.c_i.grad {
+            Some(grad) => vector(
+                Python::with_gil(|py| py_actions(py, actions).and_then(|actions| grad.call1(py, (i, actions)))),
+                nparams,
+            ),
+            None => fd_grad_actions(i, actions, |actions| self._c_i(i, actions)),
+        }
+    }
+}
+
+// implemented per action type, since a blanket impl would overlap with the one for FixedCost
+macro_rules! callable_cost_impl {
+    ($($a:ty),*) => {
+        $(impl CostFunc<$a> for CallableCost {
+            fn c_i(&self, i: usize, actions: &$a) -> f64 {
+                self._c_i(i, actions)
+            }
+
+            fn dc_i(&self, i: usize, actions: &$a) -> Array1<f64> {
+                self._dc_i(i, actions, <$a>::nparams())
+            }
+
+            fn n(&self) -> usize {
+                self.n
+            }
+        })*
+    };
+}
+
+callable_cost_impl!(Actions, InvestActions, SharingActions);
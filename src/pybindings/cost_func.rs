@@ -1,5 +1,5 @@
 use crate::py::*;
-use crate::{def_py_enum, unpack_py_enum, pycontainer};
+use crate::{def_py_enum, from_json_py_enum, impl_pickle, unpack_py_enum, pycontainer};
 
 
 def_py_enum!(CostFuncContainer(Box<dyn CostFunc>));
@@ -11,6 +11,7 @@ pub struct PyCostFunc {
     pub class: &'static str,
 }
 pycontainer!(PyCostFunc(cost_func: CostFuncContainer));
+impl_pickle!(json: PyCostFunc);
 
 #[pymethods]
 impl PyCostFunc {
@@ -18,18 +19,12 @@ impl PyCostFunc {
     pub fn fixed_basic(r: Vec<f64>) -> PyResult<Self> {
         Ok(Self {
             cost_func: CostFuncContainer::Basic(
-                match BasicFixedCost::new(
-                    match Array::from_shape_vec(
+                Box::new(BasicFixedCost::new(
+                    Array::from_shape_vec(
                         (r.len(), 2),
                         vec![r; 2].concat()
-                    ) {
-                        Ok(r) => r,
-                        Err(e) => return Err(value_error(format!("{}", e)))
-                    }
-                ) {
-                    Ok(r) => Box::new(r),
-                    Err(e) => return Err(value_error(e))
-                }
+                    ).map_err(DynapaiError::from)?
+                )?)
             ),
             class: "FixedCost",
         })
@@ -39,18 +34,12 @@ impl PyCostFunc {
     pub fn fixed_invest(r: Vec<f64>, r_inv: Vec<f64>) -> PyResult<Self> {
         Ok(Self {
             cost_func: CostFuncContainer::Invest(
-                match InvestFixedCost::new(
-                    match Array::from_shape_vec(
+                Box::new(InvestFixedCost::new(
+                    Array::from_shape_vec(
                         (r.len(), 4),
                         vec![vec![r, r_inv].concat(); 2].concat()
-                    ) {
-                        Ok(r) => r,
-                        Err(e) => return Err(value_error(format!("{}", e)))
-                    }
-                ) {
-                    Ok(r) => Box::new(r),
-                    Err(e) => return Err(value_error(e))
-                }
+                    ).map_err(DynapaiError::from)?
+                )?)
             ),
             class: "FixedCost",
         })
@@ -65,18 +54,12 @@ impl PyCostFunc {
         };
         Ok(Self {
             cost_func: CostFuncContainer::Sharing(
-                match SharingFixedCost::new(
-                    match Array::from_shape_vec(
+                Box::new(SharingFixedCost::new(
+                    Array::from_shape_vec(
                         (r.len(), 6),
                         vec![vec![r, r_inv, r_share].concat(); 2].concat()
-                    ) {
-                        Ok(r) => r,
-                        Err(e) => return Err(value_error(format!("{}", e)))
-                    }
-                ) {
-                    Ok(r) => Box::new(r),
-                    Err(e) => return Err(value_error(e))
-                }
+                    ).map_err(DynapaiError::from)?
+                )?)
             ),
             class: "FixedCost",
         })
@@ -102,20 +85,38 @@ impl PyCostFunc {
         }
     }
 
-    pub fn c_i(&self, i: usize, actions: &PyActions) -> f64 {
-        unpack_py_enum! {
+    // c_i(i, actions) -> float, or an object with a c_i method (and optionally dc_i),
+    // for actions of the given type; the gradient dc_i(i, actions) wrt player i's actions
+    // is found by finite differences if not given
+    #[staticmethod]
+    #[args(atype = "\"basic\"", grad = "None")]
+    pub fn from_callable(f: &PyAny, n: usize, atype: &str, grad: Option<&PyAny>) -> PyResult<Self> {
+        let cost_func = CallableCost::new(f, n, grad)?;
+        let cost_func = match atype {
+            "basic" => CostFuncContainer::Basic(Box::new(cost_func)),
+            "invest" => CostFuncContainer::Invest(Box::new(cost_func)),
+            "sharing" => CostFuncContainer::Sharing(Box::new(cost_func)),
+            _ => return Err(DynapaiError::InvalidParameter(format!(
+                "Invalid atype: {}, expected one of \"basic\", \"invest\", \"sharing\"", atype
+            )).into()),
+        };
+        Ok(Self { cost_func, class: "Callable" })
+    }
+
+    pub fn c_i(&self, i: usize, actions: &PyActions) -> PyResult<f64> {
+        check_callable(unpack_py_enum! {
             [CostFuncContainer, ActionContainer](cost_func, actions) = self.get(), actions.get(); 
             cost_func.c_i(i, &actions)
-        }
+        })
     }
-    pub fn c<'py>(&self, py: Python<'py>, actions: &PyActions) -> &'py PyArray1<f64> {
-        unpack_py_enum! {
+    pub fn c<'py>(&self, py: Python<'py>, actions: &PyActions) -> PyResult<&'py PyArray1<f64>> {
+        Ok(check_callable(unpack_py_enum! {
             [CostFuncContainer, ActionContainer](cost_func, actions) = self.get(), actions.get(); 
-            cost_func.c(&actions).into_pyarray(py)
-        }
+            cost_func.c(&actions)
+        })?.into_pyarray(py))
     }
 
-    pub fn __call__<'py>(&self, py: Python<'py>, actions: &PyActions) -> &'py PyArray1<f64> {
+    pub fn __call__<'py>(&self, py: Python<'py>, actions: &PyActions) -> PyResult<&'py PyArray1<f64>> {
         self.c(py, actions)
     }
 
@@ -124,8 +125,43 @@ impl PyCostFunc {
         format!("{}", self.get().object_type())
     }
 
+    pub fn to_json(&self) -> PyResult<String> {
+        unpack_py_enum! {
+            [CostFuncContainer](cost_func) = self.get();
+            py_to_json(cost_func)
+        }
+    }
+
+    // the action type is inferred from the number of columns in the cost matrix
+    #[staticmethod]
+    pub fn from_json(s: &str) -> PyResult<Self> {
+        Ok(Self {
+            cost_func: from_json_py_enum!(s; CostFunc => CostFuncContainer; |x| x)?,
+            class: "FixedCost",
+        })
+    }
+
     pub fn __str__(&self) -> String {
         format!("CostFunc ({}): atype = {}", self.class, self.atype())
     }
 
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::{py::*, pycontainer};
+use crate::{py::*, impl_pickle, pycontainer};
 
 #[derive(Clone)]
 #[pyclass(name = "CSF")]
@@ -7,6 +7,7 @@ pub struct PyCSF {
     pub class: &'static str,
 }
 pycontainer!(PyCSF(csf: Box<dyn CSF>));
+impl_pickle!(json: PyCSF);
 
 #[pymethods]
 impl PyCSF {
@@ -21,19 +22,122 @@ impl PyCSF {
     pub fn maybe_no_win(scale: f64) -> PyResult<Self> {
         match MaybeNoWinCSF::new(scale) {
             Ok(csf) => Ok(Self{ csf: Box::new(csf), class: "MaybeNoWin" }),
-            Err(e) => Err(value_error(e)),
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub fn q_i(&self, i: usize, p: Vec<f64>) -> f64 {
-        self.csf.q_i(i, Array::from(p).view())
+    // p_i^r / sum_j p_j^r
+    #[staticmethod]
+    #[args(r = "1.0")]
+    pub fn tullock(r: f64) -> PyResult<Self> {
+        Ok(Self{ csf: Box::new(TullockCSF::new(r)?), class: "Tullock" })
+    }
+
+    // scale * p_i^r / (1 + scale * sum_j p_j^r), so nobody may win
+    #[staticmethod]
+    #[args(r = "1.0", scale = "1.0")]
+    pub fn maybe_no_win_tullock(r: f64, scale: f64) -> PyResult<Self> {
+        Ok(Self{ csf: Box::new(MaybeNoWinTullockCSF::new(r, scale)?), class: "MaybeNoWinTullock" })
+    }
+
+    // exp(k p_i) / sum_j exp(k p_j)
+    #[staticmethod]
+    #[args(k = "1.0")]
+    pub fn logit(k: f64) -> PyResult<Self> {
+        Ok(Self{ csf: Box::new(LogitCSF::new(k)?), class: "Logit" })
+    }
+
+    // scale * exp(k p_i) / (1 + scale * sum_j exp(k p_j)), so nobody may win
+    #[staticmethod]
+    #[args(k = "1.0", scale = "1.0")]
+    pub fn maybe_no_win_logit(k: f64, scale: f64) -> PyResult<Self> {
+        Ok(Self{ csf: Box::new(MaybeNoWinLogitCSF::new(k, scale)?), class: "MaybeNoWinLogit" })
+    }
+
+    // w_i p_i^r / sum_j w_j p_j^r, where each player has its own weight w_i
+    #[staticmethod]
+    #[args(r = "1.0")]
+    pub fn weighted(w: Vec<f64>, r: f64) -> PyResult<Self> {
+        Ok(Self{ csf: Box::new(WeightedCSF::new(Array::from(w), r)?), class: "Weighted" })
+    }
+
+    // scale_i p_i^r / (1 + sum_j scale_j p_j^r), so nobody may win
+    #[staticmethod]
+    #[args(r = "1.0")]
+    pub fn maybe_no_win_weighted(scale: Vec<f64>, r: f64) -> PyResult<Self> {
+        Ok(Self{ csf: Box::new(MaybeNoWinWeightedCSF::new(Array::from(scale), r)?), class: "MaybeNoWinWeighted" })
+    }
+
+    // q_i(i, p) -> float, or an object with a q_i method (and optionally dq_i);
+    // the gradient dq_i(i, p) is found by finite differences if not given
+    #[staticmethod]
+    #[args(grad = "None")]
+    pub fn from_callable(f: &PyAny, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(Self{ csf: Box::new(CallableCSF::new(f, grad)?), class: "Callable" })
     }
 
-    pub fn q<'py>(&self, py: Python<'py>, p: Vec<f64>) -> &'py PyArray1<f64> {
-        self.csf.q(Array::from(p).view()).into_pyarray(py)
+    // number of players, or None if the CSF works for any number
+    #[getter]
+    pub fn n(&self) -> Option<usize> {
+        self.csf.n()
+    }
+
+    pub fn q_i(&self, i: usize, p: Vec<f64>) -> PyResult<f64> {
+        check_callable(self.csf.q_i(i, Array::from(p).view()))
+    }
+
+    pub fn q<'py>(&self, py: Python<'py>, p: Vec<f64>) -> PyResult<&'py PyArray1<f64>> {
+        Ok(check_callable(self.csf.q(Array::from(p).view()))?.into_pyarray(py))
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        py_to_json(&self.csf)
+    }
+
+    #[staticmethod]
+    pub fn from_json(s: &str) -> PyResult<Self> {
+        let csf: Box<dyn CSF> = py_from_json(s)?;
+        let class = if csf.is::<MaybeNoWinCSF>() {
+            "MaybeNoWin"
+        } else if csf.is::<TullockCSF>() {
+            "Tullock"
+        } else if csf.is::<MaybeNoWinTullockCSF>() {
+            "MaybeNoWinTullock"
+        } else if csf.is::<LogitCSF>() {
+            "Logit"
+        } else if csf.is::<MaybeNoWinLogitCSF>() {
+            "MaybeNoWinLogit"
+        } else if csf.is::<WeightedCSF>() {
+            "Weighted"
+        } else if csf.is::<MaybeNoWinWeightedCSF>() {
+            "MaybeNoWinWeighted"
+        } else {
+            "Default"
+        };
+        Ok(Self { csf, class })
     }
 
     pub fn __str__(&self) -> String {
         format!("CSF ({})", self.class)
     }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
\ No newline at end of file
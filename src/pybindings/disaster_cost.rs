@@ -0,0 +1,104 @@
+use crate::{py::*, impl_pickle, pycontainer};
+
+#[derive(Clone)]
+#[pyclass(name = "DisasterCost")]
+pub struct PyDisasterCost {
+    pub disaster_cost: Box<dyn DisasterCost>,
+    pub class: &'static str,
+}
+pycontainer!(PyDisasterCost(disaster_cost: Box<dyn DisasterCost>));
+impl_pickle!(json: PyDisasterCost);
+
+impl PyDisasterCost {
+    // PayoffFunc takes either a DisasterCost or the vector of constant costs d
+    pub fn extract_from(d: &PyAny) -> PyResult<Box<dyn DisasterCost>> {
+        match d.extract::<PyDisasterCost>() {
+            Ok(d) => Ok(d.unpack()),
+            Err(_) => Ok(Box::new(ConstantDisasterCost { d: Array::from(d.extract::<Vec<f64>>()?) })),
+        }
+    }
+}
+
+#[pymethods]
+impl PyDisasterCost {
+    #[new]
+    pub fn constant(d: Vec<f64>) -> Self {
+        Self { disaster_cost: Box::new(ConstantDisasterCost { d: Array::from(d) }), class: "Constant" }
+    }
+
+    // d_ij, the cost to player i if player j causes a disaster, given as a list of rows;
+    // d_none, the cost to each player if nobody wins, defaults to the diagonal of d
+    #[staticmethod]
+    #[args(d_none = "None")]
+    pub fn matrix(d: Vec<Vec<f64>>, d_none: Option<Vec<f64>>) -> PyResult<Self> {
+        Ok(Self { disaster_cost: Box::new(MatrixDisasterCost::new(from_rows(d)?, d_none.map(Array::from))?), class: "Matrix" })
+    }
+
+    // d_ij = d_i p_j^r, so more capable systems cause bigger disasters;
+    // d_none, the cost to each player if nobody wins, defaults to d
+    #[staticmethod]
+    #[args(r = "1.0", d_none = "None")]
+    pub fn capability(d: Vec<f64>, r: f64, d_none: Option<Vec<f64>>) -> PyResult<Self> {
+        Ok(Self { disaster_cost: Box::new(CapabilityDisasterCost::new(Array::from(d), r, d_none.map(Array::from))?), class: "Capability" })
+    }
+
+    // d_ij(i, j, s, p) -> float, or an object with a d_ij method (and optionally dd_ij);
+    // the gradient dd_ij(i, j, s, p) -> (ds, dp) is found by finite differences if not given.
+    // d_none is the cost to each player if nobody wins
+    #[staticmethod]
+    #[args(grad = "None")]
+    pub fn from_callable(f: &PyAny, n: usize, d_none: Vec<f64>, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(Self { disaster_cost: Box::new(CallableDisasterCost::new(f, n, Array::from(d_none), grad)?), class: "Callable" })
+    }
+
+    // cost to player i if player j causes a disaster
+    pub fn d_ij(&self, i: usize, j: usize, s: Vec<f64>, p: Vec<f64>) -> PyResult<f64> {
+        check_callable(self.disaster_cost.d_ij(i, j, Array::from(s).view(), Array::from(p).view()))
+    }
+
+    // costs to player i for each player that might cause a disaster
+    pub fn d<'py>(&self, py: Python<'py>, i: usize, s: Vec<f64>, p: Vec<f64>) -> PyResult<&'py PyArray1<f64>> {
+        Ok(check_callable(self.disaster_cost.d(i, Array::from(s).view(), Array::from(p).view()))?.into_pyarray(py))
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        py_to_json(&self.disaster_cost)
+    }
+
+    #[staticmethod]
+    pub fn from_json(s: &str) -> PyResult<Self> {
+        let disaster_cost: Box<dyn DisasterCost> = py_from_json(s)?;
+        let class = if disaster_cost.is::<MatrixDisasterCost>() {
+            "Matrix"
+        } else if disaster_cost.is::<CapabilityDisasterCost>() {
+            "Capability"
+        } else {
+            "Constant"
+        };
+        Ok(Self { disaster_cost, class })
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("DisasterCost ({})", self.class)
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
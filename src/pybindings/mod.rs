@@ -1,15 +1,23 @@
 pub use pyo3::prelude::*;
-pub use pyo3::types::PyList;
-use pyo3::exceptions::PyTypeError;
-pub use numpy::{PyArray1, PyArray2, PyReadonlyArray2, IntoPyArray};
-pub use numpy::ndarray::{stack, Array, Array1, Axis};
+pub use pyo3::types::{PyDict, PyList};
+pub use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::once_cell::GILOnceCell;
+use pyo3::types::PyType;
+pub use numpy::{PyArray1, PyArray2, PyArray3, PyArray4, PyArrayDyn, PyReadonlyArray2, IntoPyArray, ToPyArray};
+pub use ndarray::{stack, s, Array, Array1, Axis, Ix1, Ix2, Ix3, Ix4, IxDyn};
 
 pub use crate::prelude::*;
+pub use serde::{Deserialize, Serialize};
 
 pub mod aggregator;
+pub mod callable;
 pub mod cost_func;
 pub mod csf;
+pub mod disaster_cost;
+pub mod monitor;
 pub mod payoff_func;
+pub mod pickle;
 pub mod prod_func;
 pub mod reward_func;
 pub mod risk_func;
@@ -17,9 +25,13 @@ pub mod state;
 pub mod strategies;
 
 pub use aggregator::*;
+pub use callable::*;
 pub use cost_func::*;
 pub use csf::*;
+pub use disaster_cost::*;
+pub use monitor::*;
 pub use payoff_func::*;
+pub use pickle::*;
 pub use prod_func::*;
 pub use reward_func::*;
 pub use risk_func::*;
@@ -108,7 +120,7 @@ macro_rules! unpack_inner {
                 [$($other_enumname),*]($($other_name),*) = $($other_in),*;
                 $exec $(=> $outenumname)?
             ),
-            _ => panic!("Invalid object type, expected {} type", ObjectType::$obj_type),
+            _ => return Err(PyErr::from(DynapaiError::WrongActionType(format!("expected {} type", ObjectType::$obj_type)))),
         }
     };
     (
@@ -118,7 +130,7 @@ macro_rules! unpack_inner {
     ) => {
         match $in {
             $enumname::$obj_type($name) => $exec,
-            _ => panic!("Invalid object type, expected {} type", ObjectType::$obj_type),
+            _ => return Err(PyErr::from(DynapaiError::WrongActionType(format!("expected {} type", ObjectType::$obj_type)))),
         }
     };
     (
@@ -128,7 +140,7 @@ macro_rules! unpack_inner {
     ) => {
         match $in {
             $enumname::$obj_type($name) => $outenumname::$obj_type($exec),
-            _ => panic!("Invalid object type, expected {} type", ObjectType::$obj_type),
+            _ => return Err(PyErr::from(DynapaiError::WrongActionType(format!("expected {} type", ObjectType::$obj_type)))),
         }
     };
 }
@@ -197,12 +209,46 @@ macro_rules! unpack_py_enum_expect {
     ( $in:expr => $enumname:ident::$variant:ident ) => {
         match $in {
             $enumname::$variant(x) => Ok(x),
-            _ => Err(PyErr::new::<PyTypeError, _>("Invalid input type")),
+            _ => Err(PyErr::from(DynapaiError::WrongActionType(format!("expected {} type", ObjectType::$variant)))),
         }
     };
 }
 
 
+// deserializes json into the variant of a py enum for whichever action type it is valid for,
+// converting each deserialized Box<dyn $trait<A>> (or $type<A>) with $convert
+#[macro_export]
+macro_rules! from_json_py_enum {
+    ( @types $s:expr; $basic:ty, $invest:ty, $sharing:ty => $enumname:ident; |$x:ident| $convert:expr ) => {
+        match from_json::<$basic>($s) {
+            Ok($x) => Ok($enumname::Basic($convert)),
+            Err(e_basic) => match from_json::<$invest>($s) {
+                Ok($x) => Ok($enumname::Invest($convert)),
+                Err(e_invest) => match from_json::<$sharing>($s) {
+                    Ok($x) => Ok($enumname::Sharing($convert)),
+                    Err(e_sharing) => Err(PyErr::from(DynapaiError::Serialization(format!(
+                        "Could not deserialize for any action type:\nbasic: {}\ninvest: {}\nsharing: {}",
+                        e_basic, e_invest, e_sharing
+                    )))),
+                },
+            },
+        }
+    };
+    ( $s:expr; $trait:ident => $enumname:ident; |$x:ident| $convert:expr ) => {
+        $crate::from_json_py_enum!(
+            @types $s; Box<dyn $trait<Actions>>, Box<dyn $trait<InvestActions>>, Box<dyn $trait<SharingActions>>
+            => $enumname; |$x| $convert
+        )
+    };
+    ( $s:expr; $type:ident<_> => $enumname:ident; |$x:ident| $convert:expr ) => {
+        $crate::from_json_py_enum!(
+            @types $s; $type<Actions>, $type<InvestActions>, $type<SharingActions>
+            => $enumname; |$x| $convert
+        )
+    };
+}
+
+
 trait PyContainer {
     type Item;
     fn unpack(self) -> Self::Item;
@@ -236,8 +282,68 @@ macro_rules! pycontainer {
     };
 }
 
+// python exception classes for DynapaiError, created when the module is initialized;
+// each variant gets its own subclass of dynapai.DynapaiError, which is a ValueError,
+// since that's what these errors were raised as before
+const EXCEPTION_NAMES: [&str; 7] = [
+    "DimensionMismatchError",
+    "InvalidParameterError",
+    "WrongActionTypeError",
+    "SolverFailureError",
+    "NonFinitePayoffError",
+    "SerializationError",
+    "CancelledError",
+];
+
+static EXCEPTION_TYPES: GILOnceCell<(Py<PyType>, Vec<Py<PyType>>)> = GILOnceCell::new();
+
+pub fn add_exceptions(py: Python, m: &PyModule) -> PyResult<()> {
+    if EXCEPTION_TYPES.get(py).is_none() {
+        let base = PyErr::new_type(py, "dynapai.DynapaiError", None, Some(py.get_type::<PyValueError>()), None)?;
+        let variants = EXCEPTION_NAMES.iter()
+            .map(|name| PyErr::new_type(py, &format!("dynapai.{}", name), None, Some(base.as_ref(py)), None))
+            .collect::<PyResult<Vec<_>>>()?;
+        // the cell was empty while holding the gil, so this can't fail
+        let _ = EXCEPTION_TYPES.set(py, (base, variants));
+    }
+    if let Some((base, variants)) = EXCEPTION_TYPES.get(py) {
+        m.add("DynapaiError", base.clone_ref(py))?;
+        for (name, ty) in EXCEPTION_NAMES.iter().zip(variants.iter()) {
+            m.add(name, ty.clone_ref(py))?;
+        }
+    }
+    Ok(())
+}
+
+impl From<DynapaiError> for PyErr {
+    fn from(e: DynapaiError) -> PyErr {
+        let (k, msg) = match e {
+            DynapaiError::DimensionMismatch(msg) => (0, msg),
+            DynapaiError::InvalidParameter(msg) => (1, msg),
+            DynapaiError::WrongActionType(msg) => (2, msg),
+            DynapaiError::SolverFailure(msg) => (3, msg),
+            DynapaiError::NonFinitePayoff(msg) => (4, msg),
+            DynapaiError::Serialization(msg) => (5, msg),
+            DynapaiError::Cancelled(msg) => (6, msg),
+        };
+        Python::with_gil(|py| match EXCEPTION_TYPES.get(py) {
+            Some((_, variants)) => PyErr::from_type(variants[k].as_ref(py), msg),
+            // only before the module is initialized
+            None => PyValueError::new_err(msg),
+        })
+    }
+}
+
 fn value_error<S>(msg: S) -> PyErr
 where S: Into<String> + std::marker::Send + std::marker::Sync + pyo3::IntoPy<pyo3::Py<pyo3::PyAny>> + 'static
 {
-    pyo3::exceptions::PyValueError::new_err(msg).into()
+    PyValueError::new_err(msg)
+}
+
+fn py_to_json<T: serde::Serialize + ?Sized>(x: &T) -> PyResult<String> {
+    Ok(to_json(x)?)
+}
+
+fn py_from_json<T: serde::de::DeserializeOwned>(s: &str) -> PyResult<T> {
+    Ok(from_json(s)?)
 }
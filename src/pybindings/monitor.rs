@@ -0,0 +1,186 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::py::*;
+
+// how often the calling thread wakes up to check for KeyboardInterrupt while a solve runs
+const SIGNAL_INTERVAL: Duration = Duration::from_millis(50);
+
+
+// lets other python threads stop a solve: pass it as SolverOptions.cancel and call cancel(),
+// then the solve raises CancelledError after its current iteration
+#[derive(Clone, Default)]
+#[pyclass(name = "CancelToken")]
+pub struct PyCancelToken(Arc<AtomicBool>);
+
+#[pymethods]
+impl PyCancelToken {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    // makes the token usable for another solve
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    #[getter]
+    pub fn cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("CancelToken(cancelled = {})", self.cancelled())
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
+}
+
+// a pickled token is a copy, which no longer cancels solves passed the original
+impl PyPickle for PyCancelToken {
+    fn to_state(&self) -> PyResult<String> {
+        py_to_json(&self.cancelled())
+    }
+
+    fn from_state(state: &str) -> PyResult<Self> {
+        Ok(Self(Arc::new(AtomicBool::new(py_from_json(state)?))))
+    }
+
+    // tokens are only equal if they share the same flag
+    fn state_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+
+// connects a solve running in the background to the python thread waiting on it
+#[derive(Clone)]
+pub struct SolveMonitor {
+    interrupted: Arc<AtomicBool>,
+    cancel: Option<PyCancelToken>,
+    progress: Option<PyObject>,
+    // first python exception raised while solving, from the progress callback or a signal handler;
+    // raised in place of the CancelledError the solver returns
+    error: Arc<Mutex<Option<PyErr>>>,
+    // first exception raised by a python component during this solve, see install_error_slot
+    callable_error: ErrorSlot,
+}
+
+impl SolveMonitor {
+    fn new(options: &PySolverOptions) -> Self {
+        SolveMonitor {
+            interrupted: Arc::new(AtomicBool::new(false)),
+            cancel: options.cancel.clone(),
+            progress: options.progress.clone(),
+            error: Arc::new(Mutex::new(None)),
+            callable_error: ErrorSlot::default(),
+        }
+    }
+
+    pub fn callback(&self) -> SolverCallback {
+        let monitor = self.clone();
+        Arc::new(move |iter, payoffs| monitor.check(iter, payoffs))
+    }
+
+    fn check(&self, iter: u64, payoffs: &Array1<f64>) -> Result<(), DynapaiError> {
+        if self.interrupted.load(Ordering::Relaxed) {
+            return Err(DynapaiError::Cancelled(format!("interrupted on iteration {}", iter)));
+        }
+        if self.cancel.as_ref().is_some_and(|c| c.cancelled()) {
+            return Err(DynapaiError::Cancelled(format!("cancelled on iteration {}", iter)));
+        }
+        if let Some(progress) = &self.progress {
+            Python::with_gil(|py| progress.call1(py, (iter, payoffs.to_pyarray(py))))
+                .map_err(|e| {
+                    let msg = format!("progress callback raised {} on iteration {}", e, iter);
+                    self.interrupt(e);
+                    DynapaiError::Cancelled(msg)
+                })?;
+        }
+        Ok(())
+    }
+
+    fn interrupt(&self, e: PyErr) {
+        self.error.lock().unwrap().get_or_insert(e);
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+}
+
+
+// runs f on another thread with the gil released, so python threads can run in the meantime,
+// while this thread checks for KeyboardInterrupt; f gets options with a SolveMonitor attached,
+// which expand_options turns into SolverOptions.callback, so the solver stops after its current iteration.
+// f runs in a thread pool of its own, with options.threads threads, whose threads record errors
+// from python components in the monitor, so concurrent solves don't see each other's errors
+pub fn run_solve<T, F>(options: &PySolverOptions, f: F) -> PyResult<T>
+where T: Send, F: FnOnce(&PySolverOptions) -> PyResult<T> + Send
+{
+    let monitor = SolveMonitor::new(options);
+    let slot = monitor.callable_error.clone();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads.unwrap_or(0))
+        .start_handler(move |_| install_error_slot(slot.clone()))
+        .build()
+        .map_err(|e| PyErr::from(DynapaiError::SolverFailure(format!("Error when creating thread pool: {}", e))))?;
+    let options = PySolverOptions {
+        monitor: Some(monitor.clone()),
+        ..options.clone()
+    };
+    let done = (Mutex::new(false), Condvar::new());
+    // called from python, so this just gets the gil we already hold
+    let res = Python::with_gil(|py| std::thread::scope(|scope| {
+        let handle = scope.spawn(|| {
+            let res = pool.install(|| f(&options));
+            *done.0.lock().unwrap() = true;
+            done.1.notify_all();
+            res
+        });
+        loop {
+            let finished = py.allow_threads(|| {
+                let guard = done.0.lock().unwrap();
+                let (guard, _) = done.1.wait_timeout_while(guard, SIGNAL_INTERVAL, |finished| !*finished).unwrap();
+                *guard
+            });
+            if finished || handle.is_finished() {
+                break;
+            }
+            if let Err(e) = py.check_signals() {
+                monitor.interrupt(e);
+            }
+        }
+        py.allow_threads(|| handle.join())
+    }));
+    let res = res.map_err(|_| PyErr::from(DynapaiError::SolverFailure("solver thread panicked".to_string())))?;
+    let error = monitor.error.lock().unwrap().take();
+    let callable_error = monitor.callable_error.lock().unwrap().take();
+    match (error, callable_error) {
+        (Some(e), _) => Err(e),
+        // an exception from a python component explains the nan payoffs it led to, so raise that first
+        (None, Some(e)) => Err(e),
+        (None, None) => res,
+    }
+}
@@ -1,7 +1,8 @@
 use crate::init_rep;
 use crate::py::*;
 use crate::pycontainer;
-use crate::{def_py_enum, unpack_py_enum};
+use crate::impl_pickle;
+use crate::{def_py_enum, from_json_py_enum, unpack_py_enum};
 
 def_py_enum!(PayoffFuncContainer(ModularPayoff));
 
@@ -9,21 +10,23 @@ def_py_enum!(PayoffFuncContainer(ModularPayoff));
 #[pyclass(name = "PayoffFunc")]
 pub struct PyPayoffFunc(pub PayoffFuncContainer);
 pycontainer!(PyPayoffFunc(PayoffFuncContainer));
+impl_pickle!(json: PyPayoffFunc);
 
 #[pymethods]
 impl PyPayoffFunc {
+    // d is a DisasterCost, or a vector of constant disaster costs
     #[new]
     #[args(csf = "None", reward_func = "None", r_inv = "None")]
     pub fn new(
         prod_func: PyProdFunc,
         risk_func: PyRiskFunc,
-        d: Vec<f64>,
+        d: &PyAny,
         cost_func: PyCostFunc,
         csf: Option<PyCSF>,
         reward_func: Option<PyRewardFunc>,
     ) -> PyResult<Self> {
-        let n = ProdFunc::<Actions>::n(&prod_func.0);
-        let prod_func = Box::new(prod_func.unpack());
+        let n = prod_func.get().n();
+        let prod_func = prod_func.unpack();
         let risk_func = risk_func.unpack();
         let csf = match csf {
             None => Box::new(DefaultCSF),
@@ -33,20 +36,17 @@ impl PyPayoffFunc {
             None => Box::new(LinearReward::default(n)),
             Some(reward_func) => reward_func.unpack(),
         };
-        let disaster_cost = Box::new(ConstantDisasterCost { d: Array::from(d) });
+        let disaster_cost = PyDisasterCost::extract_from(d)?;
         Ok(Self(unpack_py_enum! {
             [CostFuncContainer](cost_func) = cost_func.unpack();
-            match ModularPayoff::new(
-                prod_func,
+            ModularPayoff::new(
+                prod_func.boxed(),
                 risk_func,
                 csf,
                 reward_func,
                 disaster_cost,
                 cost_func,
-            ) {
-                Ok(pfunc) => pfunc,
-                Err(e) => return Err(value_error(e)),
-            } => PayoffFuncContainer
+            )? => PayoffFuncContainer
         }))
     }
 
@@ -54,7 +54,7 @@ impl PyPayoffFunc {
     pub fn expand_from(
         prod_func_list: Vec<PyProdFunc>,
         risk_func_list: Vec<PyRiskFunc>,
-        d_list: Vec<Vec<f64>>,
+        d_list: Vec<&PyAny>,
         cost_func_list: Vec<PyCostFunc>,
         csf_list: Option<Vec<PyCSF>>,
         reward_func_list: Option<Vec<PyRewardFunc>>,
@@ -78,18 +78,18 @@ impl PyPayoffFunc {
         )
     }
 
-    pub fn u_i(&self, i: usize, actions: &PyActions) -> f64 {
-        unpack_py_enum! {
+    pub fn u_i(&self, i: usize, actions: &PyActions) -> PyResult<f64> {
+        check_callable(unpack_py_enum! {
             [PayoffFuncContainer, ActionContainer](pfunc, actions) = self.get(), actions.get();
             pfunc.u_i(i, &actions)
-        }
+        })
     }
 
-    pub fn u<'py>(&self, py: Python<'py>, actions: &PyActions) -> &'py PyArray1<f64> {
-        unpack_py_enum! {
+    pub fn u<'py>(&self, py: Python<'py>, actions: &PyActions) -> PyResult<&'py PyArray1<f64>> {
+        Ok(check_callable(unpack_py_enum! {
             [PayoffFuncContainer, ActionContainer](pfunc, actions) = self.get(), actions.get();
-            pfunc.u(&actions).into_pyarray(py)
-        }
+            pfunc.u(&actions)
+        })?.into_pyarray(py))
     }
 
     #[getter]
@@ -97,7 +97,47 @@ impl PyPayoffFunc {
         format!("{}", self.get().object_type())
     }
 
-    pub fn __call__<'py>(&self, py: Python<'py>, actions: &PyActions) -> &'py PyArray1<f64> {
+    pub fn __call__<'py>(&self, py: Python<'py>, actions: &PyActions) -> PyResult<&'py PyArray1<f64>> {
         self.u(py, actions)
     }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        unpack_py_enum! {
+            [PayoffFuncContainer](pfunc) = self.get();
+            py_to_json(&(Box::new(pfunc.clone()) as Box<dyn PayoffFunc<_>>))
+        }
+    }
+
+    #[staticmethod]
+    pub fn from_json(s: &str) -> PyResult<Self> {
+        // deserialization only produces ModularPayoff
+        Ok(Self(from_json_py_enum!(
+            s; PayoffFunc => PayoffFuncContainer;
+            |x| x.downcast_ref::<ModularPayoff<_>>().unwrap().clone()
+        )?))
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("PayoffFunc: atype = {}", self.atype())
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
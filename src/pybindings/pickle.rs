@@ -0,0 +1,127 @@
+use pyo3::PyTypeInfo;
+use pyo3::basic::CompareOp;
+
+use crate::py::*;
+
+// longer reprs show the summary from __str__ instead of the state
+const REPR_WIDTH: usize = 200;
+
+
+// python classes are pickled and compared by their state, a json string holding the underlying rust objects;
+// __reduce__ gives dynapai._unpickle along with the class name and state, so nothing needs a default constructor
+pub trait PyPickle: PyTypeInfo + Sized {
+    fn to_state(&self) -> PyResult<String>;
+    fn from_state(state: &str) -> PyResult<Self>;
+
+    fn reduce(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        let unpickle = py.import("dynapai")?.getattr("_unpickle")?;
+        Ok((unpickle.into(), (Self::NAME, self.to_state()?)))
+    }
+
+    fn set_state(&mut self, state: &str) -> PyResult<()> {
+        *self = Self::from_state(state)?;
+        Ok(())
+    }
+
+    // objects that can't be serialized, like those holding python callables, are only equal to themselves
+    fn state_eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other) || matches!((self.to_state(), other.to_state()), (Ok(a), Ok(b)) if a == b)
+    }
+
+    fn richcmp(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        match op {
+            CompareOp::Eq => self.state_eq(other).into_py(py),
+            CompareOp::Ne => (!self.state_eq(other)).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    // the state on one line if it's short enough, otherwise the summary, one line per field
+    fn repr(&self, summary: &str) -> String {
+        match self.to_state().ok().map(|s| compact(&s)) {
+            Some(s) if s.len() <= REPR_WIDTH => format!("{}({})", Self::NAME, s),
+            _ => {
+                let mut lines = summary.lines().map(str::trim);
+                let first = lines.next().unwrap_or(Self::NAME);
+                let rest = lines.collect::<Vec<_>>();
+                if rest.is_empty() {
+                    format!("<{}>", first)
+                } else {
+                    format!("<{} {}>", first, rest.join("; "))
+                }
+            },
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! impl_pickle {
+    // classes with their own to_json and from_json methods
+    (json: $($name:ident),*) => {
+        $(
+            impl PyPickle for $name {
+                fn to_state(&self) -> PyResult<String> {
+                    self.to_json()
+                }
+                fn from_state(state: &str) -> PyResult<Self> {
+                    Self::from_json(state)
+                }
+            }
+        )*
+    };
+    // classes that derive Serialize and Deserialize
+    (serde: $($name:ident),*) => {
+        $(
+            impl PyPickle for $name {
+                fn to_state(&self) -> PyResult<String> {
+                    py_to_json(self)
+                }
+                fn from_state(state: &str) -> PyResult<Self> {
+                    py_from_json(state)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! unpickle_as {
+    ($py:ident, $name:ident, $state:ident; $($cls:ident),*) => {
+        $(
+            if $name == <$cls as PyTypeInfo>::NAME {
+                return Ok(Py::new($py, $cls::from_state($state)?)?.into_py($py));
+            }
+        )*
+    };
+}
+
+// inverse of __reduce__, for every class in the module
+#[pyfunction]
+#[pyo3(name = "_unpickle")]
+pub fn unpickle(py: Python, name: &str, state: &str) -> PyResult<PyObject> {
+    unpickle_as!(
+        py, name, state;
+        PyActions, PyAggregator, PyCancelToken, PyCostFunc, PyCSF, PyDisasterCost, PyEquilibrium,
+        PyMarkovPolicy, PyMixedSolverResult, PyMultistartResult, PyPayoffFunc, PyPlannerResult, PyProdFunc,
+        PyRewardFunc, PyRiskFunc, PySavedResult, PyScenario, PySolverOptions, PySolverResult,
+        PySolverTrace, PyStackelbergResult, PyState, PyStrategies, PyVerification
+    );
+    Err(PyErr::from(DynapaiError::Serialization(format!("Can't unpickle unknown class {}", name))))
+}
+
+// json without the whitespace outside of strings
+fn compact(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for c in json.chars() {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else if c == '"' {
+            in_string = true;
+        } else if c.is_whitespace() {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
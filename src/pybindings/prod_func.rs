@@ -1,11 +1,46 @@
 use crate::py::*;
-use crate::{pycontainer, unpack_py_enum, init_rep};
+use crate::{impl_pickle, pycontainer, unpack_py_enum, init_rep};
 
 
+// the production functions the python class can hold, each of which works with any action type
+#[derive(Clone)]
+pub enum ProdFuncKind {
+    Default(DefaultProd),
+    Callable(CallableProd),
+}
+
+impl ProdFuncKind {
+    pub fn n(&self) -> usize {
+        match self {
+            ProdFuncKind::Default(p) => ProdFunc::<Actions>::n(p),
+            ProdFuncKind::Callable(p) => p.n(),
+        }
+    }
+
+    pub fn as_dyn<A: ActionType + Clone + 'static>(&self) -> &dyn ProdFunc<A>
+    where DefaultProd: ProdFunc<A>, CallableProd: ProdFunc<A>
+    {
+        match self {
+            ProdFuncKind::Default(p) => p,
+            ProdFuncKind::Callable(p) => p,
+        }
+    }
+
+    pub fn boxed<A: ActionType + Clone + 'static>(self) -> Box<dyn ProdFunc<A>>
+    where DefaultProd: ProdFunc<A>, CallableProd: ProdFunc<A>
+    {
+        match self {
+            ProdFuncKind::Default(p) => Box::new(p),
+            ProdFuncKind::Callable(p) => Box::new(p),
+        }
+    }
+}
+
 #[derive(Clone)]
 #[pyclass(name = "ProdFunc")]
-pub struct PyProdFunc(pub DefaultProd);
-pycontainer!(PyProdFunc(DefaultProd));
+pub struct PyProdFunc(pub ProdFuncKind);
+pycontainer!(PyProdFunc(ProdFuncKind));
+impl_pickle!(json: PyProdFunc);
 
 #[pymethods]
 impl PyProdFunc {
@@ -21,11 +56,20 @@ impl PyProdFunc {
             Array::from(beta),
         );
         match prod_func {
-            Ok(p) => Ok(Self(p)),
-            Err(e) => Err(value_error(e)),
+            Ok(p) => Ok(Self(ProdFuncKind::Default(p))),
+            Err(e) => Err(e.into()),
         }
     }
 
+    // f_i(i, actions) -> (s_i, p_i), or an object with an f_i method (and optionally df_i);
+    // the gradients df_i(i, actions) -> (ds, dp) wrt player i's actions are found by finite differences if not given.
+    // these don't carry a state, so investment has no effect on them
+    #[staticmethod]
+    #[args(grad = "None")]
+    fn from_callable(f: &PyAny, n: usize, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(Self(ProdFuncKind::Callable(CallableProd::new(f, n, grad)?)))
+    }
+
     #[staticmethod]
     fn expand_from(
         a_list: Vec<Vec<f64>>, alpha_list: Vec<Vec<f64>>,
@@ -39,26 +83,62 @@ impl PyProdFunc {
         )
     }
 
-    fn f_i(&self, i: usize, actions: &PyActions) -> (f64, f64) {
-        unpack_py_enum! {
+    fn f_i(&self, i: usize, actions: &PyActions) -> PyResult<(f64, f64)> {
+        check_callable(unpack_py_enum! {
             [ActionContainer](actions) = actions.get();
-            self.0.f_i(i, actions)
-        }
+            self.0.as_dyn().f_i(i, actions)
+        })
     }
 
-    fn f<'py>(&self, py: Python<'py>, actions: &PyActions) -> (&'py PyArray1<f64>, &'py PyArray1<f64>) {
-        let (s, p) = unpack_py_enum! {
+    fn f<'py>(&self, py: Python<'py>, actions: &PyActions) -> PyResult<(&'py PyArray1<f64>, &'py PyArray1<f64>)> {
+        let (s, p) = check_callable(unpack_py_enum! {
             [ActionContainer](actions) = actions.get();
-            self.0.f(actions)
-        };
-        (s.into_pyarray(py), p.into_pyarray(py))
+            self.0.as_dyn().f(actions)
+        })?;
+        Ok((s.into_pyarray(py), p.into_pyarray(py)))
     }
 
-    fn __call__<'py>(&self, py: Python<'py>, actions: &PyActions) -> (&'py PyArray1<f64>, &'py PyArray1<f64>) {
+    fn __call__<'py>(&self, py: Python<'py>, actions: &PyActions) -> PyResult<(&'py PyArray1<f64>, &'py PyArray1<f64>)> {
         self.f(py, actions)
     }
 
+    fn to_json(&self) -> PyResult<String> {
+        py_to_json(&self.0.clone().boxed::<Actions>())
+    }
+
+    #[staticmethod]
+    fn from_json(s: &str) -> PyResult<Self> {
+        let prod_func: Box<dyn ProdFunc<Actions>> = py_from_json(s)?;
+        match prod_func.downcast_ref::<DefaultProd>() {
+            Some(p) => Ok(Self(ProdFuncKind::Default(p.clone()))),
+            None => Err(value_error("Expected DefaultProd")),
+        }
+    }
+
     fn __str__(&self) -> String {
-        format!("{}", &self.0)
+        match &self.0 {
+            ProdFuncKind::Default(p) => format!("{}", p),
+            ProdFuncKind::Callable(p) => format!("{}", p),
+        }
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
     }
 }
@@ -1,4 +1,4 @@
-use crate::{py::*, pycontainer};
+use crate::{py::*, impl_pickle, pycontainer};
 
 
 #[derive(Clone)]
@@ -9,6 +9,7 @@ pub struct PyRewardFunc {
 }
 
 pycontainer!(PyRewardFunc(reward_func: Box<dyn RewardFunc>));
+impl_pickle!(json: PyRewardFunc);
 
 #[pymethods]
 impl PyRewardFunc {
@@ -20,19 +21,151 @@ impl PyRewardFunc {
         }
     }
 
+    // win_a_i + win_b_i p_i if player i wins, lose_a_j + lose_b_j p_j to everyone else if player j wins
     #[staticmethod]
     pub fn linear_reward(
         win_a: Vec<f64>, win_b: Vec<f64>,
         lose_a: Vec<f64>, lose_b: Vec<f64>
-    ) -> Self {
-        Self {
+    ) -> PyResult<Self> {
+        Ok(Self {
             reward_func: Box::new(LinearReward::new(
                 Array::from(win_a),
                 Array::from(win_b),
                 Array::from(lose_a),
                 Array::from(lose_b),
-            ).unwrap()),
+            )?),
             class: "LinearReward",
-        }
+        })
+    }
+
+    // rho_ij = a_ij + b_ij p_i, the reward to player i if player j wins, given as lists of rows
+    #[staticmethod]
+    #[args(b = "None")]
+    pub fn matrix_reward(a: Vec<Vec<f64>>, b: Option<Vec<Vec<f64>>>) -> PyResult<Self> {
+        let a = from_rows(a)?;
+        let reward_func = match b {
+            Some(b) => MatrixReward::new(a, from_rows(b)?)?,
+            None => MatrixReward::constant(a)?,
+        };
+        Ok(Self { reward_func: Box::new(reward_func), class: "MatrixReward" })
+    }
+
+    // win_a_i + win_b_i p_i if player i wins, lose_a_i + lose_b_i p_i if anyone else wins
+    #[staticmethod]
+    pub fn per_player_reward(
+        win_a: Vec<f64>, win_b: Vec<f64>,
+        lose_a: Vec<f64>, lose_b: Vec<f64>
+    ) -> PyResult<Self> {
+        Ok(Self {
+            reward_func: Box::new(MatrixReward::per_player(
+                Array::from(win_a),
+                Array::from(win_b),
+                Array::from(lose_a),
+                Array::from(lose_b),
+            )?),
+            class: "MatrixReward",
+        })
+    }
+
+    // win_a_i p_i^win_b_i if player i wins, lose_a_j p_j^lose_b_j to everyone else if player j wins
+    #[staticmethod]
+    pub fn power_reward(
+        win_a: Vec<f64>, win_b: Vec<f64>,
+        lose_a: Vec<f64>, lose_b: Vec<f64>
+    ) -> PyResult<Self> {
+        Ok(Self {
+            reward_func: Box::new(PowerReward::new(
+                Array::from(win_a),
+                Array::from(win_b),
+                Array::from(lose_a),
+                Array::from(lose_b),
+            )?),
+            class: "PowerReward",
+        })
+    }
+
+    // win_a_i + win_b_i p_i if player i wins, lose_a_i - gap_i (p_j - p_i) if player j wins
+    #[staticmethod]
+    pub fn capability_gap_reward(
+        win_a: Vec<f64>, win_b: Vec<f64>,
+        lose_a: Vec<f64>, gap: Vec<f64>
+    ) -> PyResult<Self> {
+        Ok(Self {
+            reward_func: Box::new(CapabilityGapReward::new(
+                Array::from(win_a),
+                Array::from(win_b),
+                Array::from(lose_a),
+                Array::from(gap),
+            )?),
+            class: "CapabilityGapReward",
+        })
+    }
+
+    // win_i(i, p) -> float for the winner's reward, and lose_i(j, p) -> float for the others' when player j wins (0 if not given),
+    // or an object with win_i and lose_i methods (and optionally dwin_i and dlose_i);
+    // gradients wrt p are found by finite differences if not given
+    #[staticmethod]
+    #[args(lose = "None", dwin = "None", dlose = "None")]
+    pub fn from_callable(
+        win: &PyAny, n: usize, lose: Option<&PyAny>,
+        dwin: Option<&PyAny>, dlose: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        Ok(Self {
+            reward_func: Box::new(CallableReward::new(win, n, lose, dwin, dlose)?),
+            class: "Callable",
+        })
+    }
+
+    // reward to player i if player j wins
+    pub fn reward_ij(&self, i: usize, j: usize, p: Vec<f64>) -> PyResult<f64> {
+        check_callable(self.reward_func.reward_ij(i, j, Array::from(p).view()))
+    }
+
+    // rewards to player i for each possible winner
+    pub fn reward<'py>(&self, py: Python<'py>, i: usize, p: Vec<f64>) -> PyResult<&'py PyArray1<f64>> {
+        Ok(check_callable(self.reward_func.reward(i, Array::from(p).view()))?.into_pyarray(py))
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        py_to_json(&self.reward_func)
+    }
+
+    #[staticmethod]
+    pub fn from_json(s: &str) -> PyResult<Self> {
+        let reward_func: Box<dyn RewardFunc> = py_from_json(s)?;
+        let class = if reward_func.is::<MatrixReward>() {
+            "MatrixReward"
+        } else if reward_func.is::<PowerReward>() {
+            "PowerReward"
+        } else if reward_func.is::<CapabilityGapReward>() {
+            "CapabilityGapReward"
+        } else {
+            "LinearReward"
+        };
+        Ok(Self { reward_func, class })
+    }
+
+    pub fn __str__(&self) -> String {
+        format!("RewardFunc ({})", self.class)
+    }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
     }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::{py::*, pycontainer};
+use crate::{py::*, impl_pickle, pycontainer};
 
 #[derive(Clone)]
 #[pyclass(name = "RiskFunc")]
@@ -7,6 +7,7 @@ pub struct PyRiskFunc {
     pub class: &'static str,
 }
 pycontainer!(PyRiskFunc(risk_func: Box<dyn RiskFunc>));
+impl_pickle!(json: PyRiskFunc);
 
 #[pymethods]
 impl PyRiskFunc {
@@ -16,15 +17,72 @@ impl PyRiskFunc {
         Self{ risk_func: Box::new(WinnerOnlyRisk { theta: Array::from(theta) }), class: "WinnerOnly" }
     }
 
-    pub fn sigma_i(&self, i: usize, s: Vec<f64>, p: Vec<f64>) -> f64 {
-        self.risk_func.sigma_i(i, Array::from(s).view(), Array::from(p).view())
+    // disaster is avoided only if every player's system is safe
+    #[staticmethod]
+    pub fn all_players(theta: Vec<f64>) -> Self {
+        Self{ risk_func: Box::new(AllPlayersRisk { theta: Array::from(theta) }), class: "AllPlayers" }
+    }
+
+    // safety depends on sum(s) relative to sum(p)
+    #[staticmethod]
+    pub fn aggregate(n: usize, theta: f64) -> PyResult<Self> {
+        Ok(Self{ risk_func: Box::new(AggregateRisk::new(n, theta)?), class: "Aggregate" })
+    }
+
+    // sigma_i(i, s, p) -> float, or an object with a sigma_i method (and optionally dsigma_i);
+    // the gradient dsigma_i(i, s, p) -> (ds, dp) is found by finite differences if not given
+    #[staticmethod]
+    #[args(grad = "None")]
+    pub fn from_callable(f: &PyAny, n: usize, grad: Option<&PyAny>) -> PyResult<Self> {
+        Ok(Self { risk_func: Box::new(CallableRisk::new(f, n, grad)?), class: "Callable" })
+    }
+
+    pub fn sigma_i(&self, i: usize, s: Vec<f64>, p: Vec<f64>) -> PyResult<f64> {
+        check_callable(self.risk_func.sigma_i(i, Array::from(s).view(), Array::from(p).view()))
+    }
+
+    pub fn sigma<'py>(&self, py: Python<'py>, s: Vec<f64>, p: Vec<f64>) -> PyResult<&'py PyArray1<f64>> {
+        Ok(check_callable(self.risk_func.sigma(Array::from(s).view(), Array::from(p).view()))?.into_pyarray(py))
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        py_to_json(&self.risk_func)
     }
 
-    pub fn sigma<'py>(&self, py: Python<'py>, s: Vec<f64>, p: Vec<f64>) -> &'py PyArray1<f64> {
-        self.risk_func.sigma(Array::from(s).view(), Array::from(p).view()).into_pyarray(py)
+    #[staticmethod]
+    pub fn from_json(s: &str) -> PyResult<Self> {
+        let risk_func: Box<dyn RiskFunc> = py_from_json(s)?;
+        let class = if risk_func.is::<AllPlayersRisk>() {
+            "AllPlayers"
+        } else if risk_func.is::<AggregateRisk>() {
+            "Aggregate"
+        } else {
+            "WinnerOnly"
+        };
+        Ok(Self { risk_func, class })
     }
 
     pub fn __str__(&self) -> String {
         format!("RiskFunc ({})", self.class)
     }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
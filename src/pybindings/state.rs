@@ -1,5 +1,5 @@
 use crate::py::*;
-use crate::{def_py_enum, pycontainer, unpack_py_enum};
+use crate::{action_type_for, def_py_enum, from_json_py_enum, impl_pickle, pycontainer, unpack_py_enum, unpack_py_enum_expect};
 
 
 def_py_enum!(StateContainer(Box<dyn State>));
@@ -11,6 +11,19 @@ pub struct PyState {
     pub class: &'static str,
 }
 pycontainer!(PyState(state: StateContainer));
+impl_pickle!(json: PyState);
+
+macro_rules! het_beliefs_with_type {
+    ( $beliefs:expr ; $obj_type:ident ) => {
+        {
+            let beliefs = $beliefs.into_iter().map(|b|
+                unpack_py_enum_expect!(b.unpack() => PayoffFuncContainer::$obj_type)
+                    .map(|payoff_func| Box::new(payoff_func) as Box<dyn PayoffFunc<action_type_for!($obj_type)>>)
+            ).collect::<PyResult<Vec<_>>>()?;
+            StateContainer::$obj_type(Box::new(HetBeliefs::new(beliefs)?))
+        }
+    };
+}
 
 
 #[pymethods]
@@ -33,39 +46,20 @@ impl PyState {
     }
 
     #[staticmethod]
-    fn het_beliefs(beliefs: Vec<PyPayoffFunc>) -> Self {
-        // check that all beliefs are the same type, and unpack them
-        let state_container = if beliefs.iter().all(|b|
-            match b.get() {
-                PayoffFuncContainer::Basic(_) => true,
-                _ => false,
-            }
-        ) {
-            let beliefs = beliefs.into_iter().map(|b| match b.unpack() {
-                PayoffFuncContainer::Basic(payoff_func) => Box::new(payoff_func) as Box<dyn PayoffFunc<Actions>>,
-                _ => unreachable!(),
-            }).collect();
-            StateContainer::Basic(Box::new(HetBeliefs::new(beliefs).unwrap()))
-        }
-        else if beliefs.iter().all(|b|
-            match b.get() {
-                PayoffFuncContainer::Invest(_) => true,
-                _ => false,
-            }
-        ) {
-            let beliefs = beliefs.into_iter().map(|b| match b.unpack() {
-                PayoffFuncContainer::Invest(payoff_func) => Box::new(payoff_func) as Box<dyn PayoffFunc<InvestActions>>,
-                _ => unreachable!(),
-            }).collect();
-            StateContainer::Invest(Box::new(HetBeliefs::new(beliefs).unwrap()))
-        }
-        else {
-            panic!("All beliefs must be payoff functions of the same action type")
+    fn het_beliefs(beliefs: Vec<PyPayoffFunc>) -> PyResult<Self> {
+        // all beliefs must have the same action type as the first
+        let state_container = match beliefs.first().map(|b| b.get().object_type()) {
+            Some(ObjectType::Basic) => het_beliefs_with_type!(beliefs; Basic),
+            Some(ObjectType::Invest) => het_beliefs_with_type!(beliefs; Invest),
+            Some(ObjectType::Sharing) => het_beliefs_with_type!(beliefs; Sharing),
+            None => return Err(DynapaiError::DimensionMismatch(
+                "When creating new HetBeliefs: beliefs must have length > 0".to_string()
+            ).into()),
         };
-        Self {
+        Ok(Self {
             state: state_container,
             class: "HetBeliefs",
-        }
+        })
     }
 
     // pub fn belief(&self, i: usize) -> PyPayoffFunc {
@@ -78,27 +72,74 @@ impl PyState {
     //     }
     // }
 
+    // state that changes between periods, as a flat vector
+    #[getter]
+    pub fn state_vec<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        unpack_py_enum! {
+            [StateContainer](state) = self.get();
+            state.state_vec().into_pyarray(py)
+        }
+    }
+
     #[getter]
     pub fn atype(&self) -> String {
         format!("{}", self.get().object_type())
     }
 
+    fn to_json(&self) -> PyResult<String> {
+        unpack_py_enum! {
+            [StateContainer](state) = self.get();
+            py_to_json(state)
+        }
+    }
+
+    #[staticmethod]
+    fn from_json(s: &str) -> PyResult<Self> {
+        let state = from_json_py_enum!(s; State => StateContainer; |x| x)?;
+        let common = match &state {
+            StateContainer::Basic(x) => x.is::<CommonBeliefs<Actions>>(),
+            StateContainer::Invest(x) => x.is::<CommonBeliefs<InvestActions>>(),
+            StateContainer::Sharing(x) => x.is::<CommonBeliefs<SharingActions>>(),
+        };
+        Ok(Self { state, class: if common { "CommonBeliefs" } else { "HetBeliefs" } })
+    }
+
     fn __str__(&self) -> String {
         format!("State ({}): atype = {}", self.class, self.atype())
     }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
 
-pub fn as_state(x: &PyAny) -> PyState {
+pub fn as_state(x: &PyAny) -> PyResult<PyState> {
     if let Ok(y) = x.extract::<PyState>() {
-        y
+        Ok(y)
     }
     else if let Ok(y) = x.extract::<PyPayoffFunc>() {
-        PyState::common_beliefs(y)
+        Ok(PyState::common_beliefs(y))
     }
     else if let Ok(y) = x.extract::<Vec<PyPayoffFunc>>() {
         PyState::het_beliefs(y)
     }
     else {
-        panic!("Expected State, PayoffFunc, or list of PayoffFuncs, got something else");
+        Err(PyErr::new::<PyTypeError, _>("Expected State, PayoffFunc, or list of PayoffFuncs, got something else"))
     }
 }
@@ -1,6 +1,10 @@
-use numpy::Ix2;
+use ndarray::Ix2;
+
+use serde::{Deserializer, Serializer, de};
 
 use crate::def_py_enum;
+use crate::from_json_py_enum;
+use crate::impl_pickle;
 use crate::py::*;
 use crate::pycontainer;
 use crate::unpack_py_enum;
@@ -29,7 +33,7 @@ pub struct PyActions(pub ActionContainer);
 pycontainer!(PyActions(ActionContainer));
 
 impl PyActions {
-    pub fn from_data(data: Array<f64, Ix2>) -> Result<Self, String> {
+    pub fn from_data(data: Array<f64, Ix2>) -> Result<Self, DynapaiError> {
         if data.shape()[1] == 2 {
             Ok(Self(ActionContainer::Basic(Actions::from_array(data)?)))
         } else if data.shape()[1] == 4 {
@@ -37,10 +41,10 @@ impl PyActions {
         } else if data.shape()[1] == 6 {
             Ok(Self(ActionContainer::Sharing(SharingActions::from_array(data)?)))
         } else {
-            Err(format!(
+            Err(DynapaiError::WrongActionType(format!(
                 "Invalid shape for action type: {:?}",
                 data.shape()
-            ))
+            )))
         }
     }
 
@@ -52,6 +56,23 @@ impl PyActions {
     }
 }
 
+// written as a list of rows, like each period of Strategies
+impl Serialize for PyActions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        to_rows(self.data()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PyActions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        from_rows(Vec::deserialize(deserializer)?)
+            .and_then(PyActions::from_data)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl_pickle!(serde: PyActions);
+
 #[pymethods]
 impl PyActions {
     #[new]
@@ -60,36 +81,17 @@ impl PyActions {
         xs: Vec<f64>, xp: Vec<f64>,
         inv_s: Option<Vec<f64>>, inv_p: Option<Vec<f64>>,
         share_s: Option<Vec<f64>>, share_p: Option<Vec<f64>>,
-    ) -> Self {
-        PyActions(
-            if let (Some(inv_s), Some(inv_p)) = (inv_s, inv_p) {
-                if let (Some(share_s), Some(share_p)) = (share_s, share_p) {
-                    let data = stack![
-                        Axis(1),
-                        Array::from(xs), Array::from(xp),
-                        Array::from(inv_s), Array::from(inv_p),
-                        Array::from(share_s), Array::from(share_p)
-                    ];
-                    ActionContainer::Sharing(
-                        SharingActions::from_array(data).unwrap()
-                    )
-                } else {
-                    let data = stack![
-                        Axis(1),
-                        Array::from(xs), Array::from(xp),
-                        Array::from(inv_s), Array::from(inv_p),
-                    ];
-                    ActionContainer::Invest(
-                        InvestActions::from_array(data).unwrap()
-                    )
-                }
-            } else {
-                let data = stack![Axis(1), Array::from(xs), Array::from(xp)];
-                ActionContainer::Basic(
-                    Actions::from_array(data).unwrap()
-                )
+    ) -> PyResult<Self> {
+        let mut columns = vec![xs, xp];
+        if let (Some(inv_s), Some(inv_p)) = (inv_s, inv_p) {
+            columns.extend([inv_s, inv_p]);
+            if let (Some(share_s), Some(share_p)) = (share_s, share_p) {
+                columns.extend([share_s, share_p]);
             }
-        )
+        }
+        let columns = columns.into_iter().map(Array::from).collect::<Vec<_>>();
+        let views = columns.iter().map(|c| c.view()).collect::<Vec<_>>();
+        Ok(Self::from_data(stack(Axis(1), &views).map_err(DynapaiError::from)?)?)
     }
 
     #[pyo3(name = "data")]
@@ -133,6 +135,26 @@ impl PyActions {
             format!("{}", actions)
         }
     }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
 
 
@@ -147,6 +169,19 @@ def_py_enum!(StrategyContainer(Strategies));
 pub struct PyStrategies(pub StrategyContainer);
 pycontainer!(PyStrategies(StrategyContainer));
 
+impl PyPickle for PyStrategies {
+    fn to_state(&self) -> PyResult<String> {
+        unpack_py_enum! {
+            [StrategyContainer](strategies) = self.get();
+            py_to_json(strategies)
+        }
+    }
+
+    fn from_state(state: &str) -> PyResult<Self> {
+        Ok(Self(from_json_py_enum!(state; Strategies<_> => StrategyContainer; |x| x)?))
+    }
+}
+
 macro_rules! build_strat_with_type {
     ( $pyactions_list:expr ; $obj_type:ident ) => {
         {
@@ -154,7 +189,7 @@ macro_rules! build_strat_with_type {
             for pyactions in $pyactions_list {
                 let container = pyactions.unpack();
                 if container.object_type() != ObjectType::$obj_type {
-                    return Err(value_error("All actions must be of the same type"));
+                    return Err(DynapaiError::WrongActionType("All actions must be of the same type".to_string()).into());
                 }
                 let actions = unpack_py_enum_expect!(container => ActionContainer::$obj_type)?;
                 actions_list.push(actions);
@@ -170,7 +205,7 @@ impl PyStrategies {
     #[staticmethod]
     pub fn from_actions_list(pyactions_list: Vec<PyActions>) -> PyResult<Self> {
         if pyactions_list.len() == 0 {
-            return Err(value_error("Actions list must not be empty"));
+            return Err(DynapaiError::DimensionMismatch("Actions list must not be empty".to_string()).into());
         }
         let action_type = pyactions_list[0].get().object_type();
         Ok(if action_type == ObjectType::Basic {
@@ -183,7 +218,7 @@ impl PyStrategies {
             build_strat_with_type!(pyactions_list; Sharing)
         }
         else {
-            return Err(value_error("Invalid action type"))
+            return Err(DynapaiError::WrongActionType("Invalid action type".to_string()).into())
         })
     }
 
@@ -213,4 +248,24 @@ impl PyStrategies {
         ).collect::<Vec<_>>().join("\n");
         format!("Strategies:\n{}", s_string)
     }
+
+    pub fn __getstate__(&self) -> PyResult<String> {
+        self.to_state()
+    }
+
+    pub fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        self.set_state(state)
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (&'static str, String))> {
+        self.reduce(py)
+    }
+
+    pub fn __richcmp__(&self, py: Python, other: &Self, op: CompareOp) -> PyObject {
+        self.richcmp(py, other, op)
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.repr(&self.__str__())
+    }
 }
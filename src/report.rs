@@ -0,0 +1,113 @@
+use ndarray::{Array, Ix2};
+
+use crate::prelude::*;
+
+
+// per-period breakdown of players' payoffs along the path of play;
+// every field has shape (t, n), with entry (t, i) computed under player i's beliefs
+#[derive(Clone, Debug)]
+pub struct PeriodReport {
+    // outputs of ProdFunc::f
+    pub s: Array<f64, Ix2>,
+    pub p: Array<f64, Ix2>,
+    pub sigma: Array<f64, Ix2>,
+    pub q: Array<f64, Ix2>,
+    // expected reward, sum_j sigma_j * q_j * reward_ij
+    pub reward: Array<f64, Ix2>,
+    // expected disaster cost, sum_j (1 - sigma_j) * q_j * d_ij + (1 - sum_j q_j) * d_none_i,
+    // since nobody winning is a disaster too
+    pub disaster_cost: Array<f64, Ix2>,
+    pub cost: Array<f64, Ix2>,
+    // reward - disaster_cost - cost
+    pub payoff: Array<f64, Ix2>,
+    // factor applied to each period's payoff by the aggregator (see Aggregator::continuation)
+    pub discount: Array<f64, Ix2>,
+    pub discounted_payoff: Array<f64, Ix2>,
+    // probability that a disaster occurs in the period, 1 - sum_j sigma_j * q_j
+    pub disaster_proba: Array<f64, Ix2>,
+}
+
+// walks the states visited under strategies, the same way Aggregator::u does;
+// players' beliefs must all be ModularPayoff
+pub fn report<A>(agg: &dyn Aggregator<A>, strategies: &Strategies<A>) -> Result<PeriodReport, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let (t, n) = (strategies.t(), agg.n());
+    let mut report = PeriodReport {
+        s: Array::zeros((t, n)),
+        p: Array::zeros((t, n)),
+        sigma: Array::zeros((t, n)),
+        q: Array::zeros((t, n)),
+        reward: Array::zeros((t, n)),
+        disaster_cost: Array::zeros((t, n)),
+        cost: Array::zeros((t, n)),
+        payoff: Array::zeros((t, n)),
+        discount: Array::zeros((t, n)),
+        discounted_payoff: Array::zeros((t, n)),
+        disaster_proba: Array::zeros((t, n)),
+    };
+    let mut discount = vec![1.; n];
+    let mut state = agg.state0().clone();
+    for (period, actions) in strategies.actions().iter().enumerate() {
+        for i in 0..n {
+            let payoff_func = match state.belief(i).downcast_ref::<ModularPayoff<A>>() {
+                Some(f) => f,
+                None => return Err(DynapaiError::InvalidParameter("Can only report on aggregators whose beliefs are ModularPayoff".to_string())),
+            };
+            let (s, p) = payoff_func.prod_func.f(actions);
+            let sigmas = payoff_func.risk_func.sigma(s.view(), p.view());
+            let qs = payoff_func.csf.q(p.view());
+            let rewards = payoff_func.reward_func.reward(i, p.view());
+            let disaster_costs = payoff_func.disaster_cost.d(i, s.view(), p.view());
+            let no_win = 1. - qs.sum();
+            // disasters are caused by the contest's winner, or happen when there's none
+            let proba_d = 1. - sigmas.iter().zip(qs.iter()).map(|(sigma, q)| sigma * q).sum::<f64>();
+            let idx = (period, i);
+            report.s[idx] = s[i];
+            report.p[idx] = p[i];
+            report.sigma[idx] = sigmas[i];
+            report.q[idx] = qs[i];
+            report.reward[idx] = sigmas.iter().zip(qs.iter()).zip(rewards.iter()).map(
+                |((sigma, q), reward)| sigma * q * reward
+            ).sum();
+            report.disaster_cost[idx] = sigmas.iter().zip(qs.iter()).zip(disaster_costs.iter()).map(
+                |((sigma, q), d)| (1. - sigma) * q * d
+            ).sum::<f64>() + no_win * payoff_func.disaster_cost.d_none(i, s.view(), p.view());
+            report.cost[idx] = payoff_func.cost_func.c_i(i, actions);
+            report.payoff[idx] = payoff_func.u_i(i, actions);
+            report.discount[idx] = discount[i];
+            report.discounted_payoff[idx] = discount[i] * report.payoff[idx];
+            report.disaster_proba[idx] = proba_d;
+            discount[i] *= agg.continuation(i, state.as_ref(), actions);
+        }
+        agg.advance_state(&mut state, actions);
+    }
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Axis, array};
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn check_report<A: ActionType + Clone + 'static>(agg: &dyn Aggregator<A>) {
+        let strategies = strategies::<A>(3, 2);
+        let report = report(agg, &strategies).unwrap();
+        assert_close(&report.payoff, &(&report.reward - &report.disaster_cost - &report.cost), 1e-12);
+        assert_close(&report.discounted_payoff, &(&report.discount * &report.payoff), 1e-12);
+        assert_close(&report.discounted_payoff.sum_axis(Axis(0)), &agg.u(&strategies), 1e-12);
+        assert!(report.disaster_proba.iter().all(|p| (0. ..=1.).contains(p)));
+        // the fixtures' disaster costs are the constant d = [1, 0.8], whoever wins or if nobody does
+        assert_close(&report.disaster_cost, &(&report.disaster_proba * &array![[1., 0.8]]), 1e-12);
+    }
+
+    #[test]
+    fn discounted_payoffs_add_up_to_u() {
+        check_report(&invest_agg(Box::new(DefaultCSF)));
+        check_report(&basic_agg(Box::new(MaybeNoWinCSF::new(1.).unwrap())));
+        check_report(&EndsOnContestWin::new(invest_agg(Box::new(MaybeNoWinCSF::new(1.).unwrap()))).unwrap());
+    }
+}
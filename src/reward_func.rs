@@ -1,19 +1,28 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
-use numpy::ndarray::{Array, ArrayView, Ix1};
+use ndarray::{Array, ArrayView, Ix1, Ix2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 use std::fmt;
 
+use crate::error::DynapaiError;
+use crate::serialize::{from_rows, to_rows};
+
 pub trait RewardFunc: DynClone + Downcast + Send + Sync {
-    fn win_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64;
-    fn lose_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64;
+    // rho_ij, the reward to player i if player j wins
+    fn reward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> f64;
+    // gradient of reward_ij wrt p
+    fn dreward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1>;
+    // rewards to player i for each possible winner j
     fn reward(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
-        Array::from_iter((0..p.len()).map(|j| {
-            if j == i {
-                self.win_i(j, p)
-            } else {
-                self.lose_i(j, p)
-            }
-        }))
+        Array::from_iter((0..p.len()).map(|j| self.reward_ij(i, j, p)))
+    }
+    // jacobian of reward(i, p) wrt p
+    fn dreward(&self, i: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix2> {
+        let mut out = Array::zeros((p.len(), p.len()));
+        for j in 0..p.len() {
+            out.row_mut(j).assign(&self.dreward_ij(i, j, p));
+        }
+        out
     }
 
     fn n(&self) -> usize;
@@ -22,6 +31,23 @@ pub trait RewardFunc: DynClone + Downcast + Send + Sync {
 clone_trait_object!(RewardFunc);
 impl_downcast!(RewardFunc);
 
+// gradient that is zero except for entry i
+fn unit_grad(i: usize, n: usize, x: f64) -> Array<f64, Ix1> {
+    let mut out = Array::zeros(n);
+    out[i] = x;
+    out
+}
+
+fn check_lengths(name: &str, arrays: &[&Array<f64, Ix1>]) -> Result<usize, DynapaiError> {
+    let n = arrays[0].len();
+    if arrays.iter().any(|x| x.len() != n) {
+        return Err(DynapaiError::DimensionMismatch(
+            format!("When creating {}: All input arrays must have the same length", name)
+        ));
+    }
+    Ok(n)
+}
+
 
 #[derive(Clone)]
 pub struct LinearReward {
@@ -38,11 +64,8 @@ impl LinearReward {
         win_b: Array<f64, Ix1>,
         lose_a: Array<f64, Ix1>,
         lose_b: Array<f64, Ix1>
-    ) -> Result<Self, &'static str> {
-        let n = win_a.len();
-        if win_b.len() != n || lose_a.len() != n || lose_b.len() != n {
-            return Err("When creating LinearReward: All input arrays must have the same length");
-        }
+    ) -> Result<Self, DynapaiError> {
+        let n = check_lengths("LinearReward", &[&win_a, &win_b, &lose_a, &lose_b])?;
         Ok(LinearReward { n, win_a, win_b, lose_a, lose_b, })
     }
 
@@ -56,12 +79,23 @@ impl LinearReward {
     }
 }
 
+// player i gets win_a_i + win_b_i p_i if it wins, and if player j wins instead, every other player gets
+// lose_a_j + lose_b_j p_j, so the lose parameters are the winner's; MatrixReward::per_player uses the loser's own
 impl RewardFunc for LinearReward {
-    fn win_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
-        self.win_a[i] + self.win_b[i] * p[i]
+    fn reward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        if i == j {
+            self.win_a[i] + self.win_b[i] * p[i]
+        } else {
+            self.lose_a[j] + self.lose_b[j] * p[j]
+        }
     }
-    fn lose_i(&self, i: usize, p: ArrayView<f64, Ix1>) -> f64 {
-        self.lose_a[i] + self.lose_b[i] * p[i]
+
+    fn dreward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        if i == j {
+            unit_grad(i, p.len(), self.win_b[i])
+        } else {
+            unit_grad(j, p.len(), self.lose_b[j])
+        }
     }
 
     fn n(&self) -> usize {
@@ -77,3 +111,330 @@ impl fmt::Display for LinearReward {
         )
     }
 }
+
+
+// a full reward matrix, rho_ij = a_ij + b_ij p_i
+#[derive(Clone)]
+pub struct MatrixReward {
+    n: usize,
+    pub a: Array<f64, Ix2>,
+    pub b: Array<f64, Ix2>,
+}
+
+impl MatrixReward {
+    pub fn new(a: Array<f64, Ix2>, b: Array<f64, Ix2>) -> Result<Self, DynapaiError> {
+        let n = a.nrows();
+        if a.shape() != [n, n] || b.shape() != [n, n] {
+            return Err(DynapaiError::DimensionMismatch("When creating MatrixReward: a and b must be square matrices of the same size".to_string()));
+        }
+        Ok(MatrixReward { n, a, b })
+    }
+
+    // rewards that don't depend on p
+    pub fn constant(a: Array<f64, Ix2>) -> Result<Self, DynapaiError> {
+        let b = Array::zeros(a.raw_dim());
+        Self::new(a, b)
+    }
+
+    // like LinearReward, but a loser gets lose_a_i + lose_b_i p_i from its own parameters, whoever wins
+    pub fn per_player(
+        win_a: Array<f64, Ix1>,
+        win_b: Array<f64, Ix1>,
+        lose_a: Array<f64, Ix1>,
+        lose_b: Array<f64, Ix1>
+    ) -> Result<Self, DynapaiError> {
+        let n = check_lengths("MatrixReward", &[&win_a, &win_b, &lose_a, &lose_b])?;
+        let a = Array::from_shape_fn((n, n), |(i, j)| if i == j { win_a[i] } else { lose_a[i] });
+        let b = Array::from_shape_fn((n, n), |(i, j)| if i == j { win_b[i] } else { lose_b[i] });
+        Self::new(a, b)
+    }
+}
+
+impl RewardFunc for MatrixReward {
+    fn reward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.a[[i, j]] + self.b[[i, j]] * p[i]
+    }
+
+    fn dreward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        unit_grad(i, p.len(), self.b[[i, j]])
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+impl fmt::Display for MatrixReward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MatrixReward {{ a: {}, b: {} }}", self.a, self.b)
+    }
+}
+
+
+// a p^b and its derivative wrt p; b = 0 is constant, even at p = 0
+fn power(a: f64, b: f64, p: f64) -> (f64, f64) {
+    if b == 0. {
+        (a, 0.)
+    } else {
+        (a * p.powf(b), a * b * p.powf(b - 1.))
+    }
+}
+
+// like LinearReward, but player i gets win_a_i p_i^win_b_i if it wins, and if player j wins instead,
+// every other player gets lose_a_j p_j^lose_b_j, again with the winner's parameters and capability;
+// exponents between 0 and 1 give concave rewards
+#[derive(Clone)]
+pub struct PowerReward {
+    n: usize,
+    pub win_a: Array<f64, Ix1>,
+    pub win_b: Array<f64, Ix1>,
+    pub lose_a: Array<f64, Ix1>,
+    pub lose_b: Array<f64, Ix1>,
+}
+
+impl PowerReward {
+    pub fn new(
+        win_a: Array<f64, Ix1>,
+        win_b: Array<f64, Ix1>,
+        lose_a: Array<f64, Ix1>,
+        lose_b: Array<f64, Ix1>
+    ) -> Result<Self, DynapaiError> {
+        let n = check_lengths("PowerReward", &[&win_a, &win_b, &lose_a, &lose_b])?;
+        if win_b.iter().chain(lose_b.iter()).any(|b| *b < 0.) {
+            return Err(DynapaiError::InvalidParameter("When creating PowerReward: Exponents must be nonnegative".to_string()));
+        }
+        Ok(PowerReward { n, win_a, win_b, lose_a, lose_b })
+    }
+
+    fn power_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> (f64, f64) {
+        if i == j {
+            power(self.win_a[i], self.win_b[i], p[i])
+        } else {
+            power(self.lose_a[j], self.lose_b[j], p[j])
+        }
+    }
+}
+
+impl RewardFunc for PowerReward {
+    fn reward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        self.power_ij(i, j, p).0
+    }
+
+    // only the winner's capability enters, whether or not i is the winner
+    fn dreward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        unit_grad(j, p.len(), self.power_ij(i, j, p).1)
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+impl fmt::Display for PowerReward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "PowerReward {{ win_a: {}, win_b: {}, lose_a: {}, lose_b: {} }}",
+            self.win_a, self.win_b, self.lose_a, self.lose_b
+        )
+    }
+}
+
+
+// winners get win_a_i + win_b_i p_i as in LinearReward, while a loser's reward depends on how far the winner is ahead,
+// rho_ij = lose_a_i - gap_i (p_j - p_i), so gap_i > 0 makes losing to a more capable winner worse;
+// unlike LinearReward, lose_a and gap are the loser's own parameters
+#[derive(Clone)]
+pub struct CapabilityGapReward {
+    n: usize,
+    pub win_a: Array<f64, Ix1>,
+    pub win_b: Array<f64, Ix1>,
+    pub lose_a: Array<f64, Ix1>,
+    pub gap: Array<f64, Ix1>,
+}
+
+impl CapabilityGapReward {
+    pub fn new(
+        win_a: Array<f64, Ix1>,
+        win_b: Array<f64, Ix1>,
+        lose_a: Array<f64, Ix1>,
+        gap: Array<f64, Ix1>
+    ) -> Result<Self, DynapaiError> {
+        let n = check_lengths("CapabilityGapReward", &[&win_a, &win_b, &lose_a, &gap])?;
+        Ok(CapabilityGapReward { n, win_a, win_b, lose_a, gap })
+    }
+}
+
+impl RewardFunc for CapabilityGapReward {
+    fn reward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> f64 {
+        if i == j {
+            self.win_a[i] + self.win_b[i] * p[i]
+        } else {
+            self.lose_a[i] - self.gap[i] * (p[j] - p[i])
+        }
+    }
+
+    fn dreward_ij(&self, i: usize, j: usize, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        if i == j {
+            return unit_grad(i, p.len(), self.win_b[i]);
+        }
+        let mut out = unit_grad(i, p.len(), self.gap[i]);
+        out[j] = -self.gap[i];
+        out
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+impl fmt::Display for CapabilityGapReward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "CapabilityGapReward {{ win_a: {}, win_b: {}, lose_a: {}, gap: {} }}",
+            self.win_a, self.win_b, self.lose_a, self.gap
+        )
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum RewardFuncSpec {
+    #[serde(rename = "LinearReward")]
+    Linear { win_a: Vec<f64>, win_b: Vec<f64>, lose_a: Vec<f64>, lose_b: Vec<f64> },
+    #[serde(rename = "MatrixReward")]
+    Matrix { a: Vec<Vec<f64>>, b: Vec<Vec<f64>> },
+    #[serde(rename = "PowerReward")]
+    Power { win_a: Vec<f64>, win_b: Vec<f64>, lose_a: Vec<f64>, lose_b: Vec<f64> },
+    #[serde(rename = "CapabilityGapReward")]
+    CapabilityGap { win_a: Vec<f64>, win_b: Vec<f64>, lose_a: Vec<f64>, gap: Vec<f64> },
+}
+
+impl Serialize for Box<dyn RewardFunc> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if let Some(x) = self.downcast_ref::<LinearReward>() {
+            RewardFuncSpec::Linear {
+                win_a: x.win_a.to_vec(),
+                win_b: x.win_b.to_vec(),
+                lose_a: x.lose_a.to_vec(),
+                lose_b: x.lose_b.to_vec(),
+            }
+        }
+        else if let Some(x) = self.downcast_ref::<MatrixReward>() {
+            RewardFuncSpec::Matrix { a: to_rows(&x.a), b: to_rows(&x.b) }
+        }
+        else if let Some(x) = self.downcast_ref::<PowerReward>() {
+            RewardFuncSpec::Power {
+                win_a: x.win_a.to_vec(),
+                win_b: x.win_b.to_vec(),
+                lose_a: x.lose_a.to_vec(),
+                lose_b: x.lose_b.to_vec(),
+            }
+        }
+        else if let Some(x) = self.downcast_ref::<CapabilityGapReward>() {
+            RewardFuncSpec::CapabilityGap {
+                win_a: x.win_a.to_vec(),
+                win_b: x.win_b.to_vec(),
+                lose_a: x.lose_a.to_vec(),
+                gap: x.gap.to_vec(),
+            }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this RewardFunc"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn RewardFunc> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match RewardFuncSpec::deserialize(deserializer)? {
+            RewardFuncSpec::Linear { win_a, win_b, lose_a, lose_b } => Box::new(LinearReward::new(
+                Array::from(win_a), Array::from(win_b), Array::from(lose_a), Array::from(lose_b),
+            ).map_err(de::Error::custom)?),
+            RewardFuncSpec::Matrix { a, b } => Box::new(MatrixReward::new(
+                from_rows(a).map_err(de::Error::custom)?, from_rows(b).map_err(de::Error::custom)?,
+            ).map_err(de::Error::custom)?),
+            RewardFuncSpec::Power { win_a, win_b, lose_a, lose_b } => Box::new(PowerReward::new(
+                Array::from(win_a), Array::from(win_b), Array::from(lose_a), Array::from(lose_b),
+            ).map_err(de::Error::custom)?),
+            RewardFuncSpec::CapabilityGap { win_a, win_b, lose_a, gap } => Box::new(CapabilityGapReward::new(
+                Array::from(win_a), Array::from(win_b), Array::from(lose_a), Array::from(gap),
+            ).map_err(de::Error::custom)?),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn check_grads(reward_func: &dyn RewardFunc, p: &Array<f64, Ix1>) {
+        for i in 0..p.len() {
+            for j in 0..p.len() {
+                assert_close(&reward_func.dreward_ij(i, j, p.view()), &fd_grad(p.view(), |p| reward_func.reward_ij(i, j, p)), 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn linear_losers_get_the_winners_lose_value() {
+        let reward_func = LinearReward::new(array![1., 2.], array![0.1, 0.2], array![0.3, 0.4], array![0.05, 0.06]).unwrap();
+        let p = array![2., 3.];
+        // rows are who gets the reward, columns who wins
+        let expected = array![
+            [1. + 0.1 * 2., 0.4 + 0.06 * 3.],
+            [0.3 + 0.05 * 2., 2. + 0.2 * 3.],
+        ];
+        for i in 0..2 {
+            assert_close(&reward_func.reward(i, p.view()), expected.row(i), 1e-12);
+        }
+        check_grads(&reward_func, &p);
+    }
+
+    #[test]
+    fn per_player_losers_get_their_own_lose_value() {
+        let reward_func = MatrixReward::per_player(array![1., 2.], array![0.1, 0.2], array![0.3, 0.4], array![0.05, 0.06]).unwrap();
+        let p = array![2., 3.];
+        let expected = array![
+            [1. + 0.1 * 2., 0.3 + 0.05 * 2.],
+            [0.4 + 0.06 * 3., 2. + 0.2 * 3.],
+        ];
+        for i in 0..2 {
+            assert_close(&reward_func.reward(i, p.view()), expected.row(i), 1e-12);
+        }
+        check_grads(&reward_func, &p);
+    }
+
+    #[test]
+    fn power_losers_get_the_winners_lose_value() {
+        let reward_func = PowerReward::new(array![1., 2.], array![0.5, 0.], array![0.3, 0.4], array![2., 0.5]).unwrap();
+        let p = array![2., 3.];
+        let expected = array![
+            [2f64.sqrt(), 0.4 * 3f64.sqrt()],
+            [0.3 * 4., 2.],
+        ];
+        for i in 0..2 {
+            assert_close(&reward_func.reward(i, p.view()), expected.row(i), 1e-12);
+        }
+        check_grads(&reward_func, &p);
+    }
+
+    #[test]
+    fn capability_gap_losers_use_their_own_gap() {
+        let reward_func = CapabilityGapReward::new(array![1., 2.], array![0.1, 0.2], array![0.3, 0.4], array![0.5, 0.25]).unwrap();
+        let p = array![2., 3.];
+        let expected = array![
+            [1. + 0.1 * 2., 0.3 - 0.5 * (3. - 2.)],
+            [0.4 - 0.25 * (2. - 3.), 2. + 0.2 * 3.],
+        ];
+        for i in 0..2 {
+            assert_close(&reward_func.reward(i, p.view()), expected.row(i), 1e-12);
+        }
+        check_grads(&reward_func, &p);
+    }
+}
@@ -2,7 +2,10 @@ use std::fmt;
 
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
-use numpy::ndarray::{ArrayView, Ix1, Array};
+use ndarray::{ArrayView, Ix1, Array};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, ser};
+
+use crate::error::DynapaiError;
 
 pub trait RiskFunc: DynClone + Downcast + Send + Sync {
     // sigma_i is proba(safe | i wins)
@@ -10,6 +13,8 @@ pub trait RiskFunc: DynClone + Downcast + Send + Sync {
     fn sigma(&self, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
         Array::from_iter((0..s.len()).map(|i| self.sigma_i(i, s, p)))
     }
+    // gradients of sigma_i wrt s and p
+    fn dsigma_i(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>);
 
     fn n(&self) -> usize;
 }
@@ -25,13 +30,23 @@ pub trait RiskFuncWithTheta: RiskFunc {
 clone_trait_object!(RiskFuncWithTheta);
 
 
+// probability that a system with safety s and capability p is safe, s p^-theta / (1 + s p^-theta),
+// along with its derivatives wrt s and p
+fn safety(s: f64, p: f64, theta: f64) -> (f64, f64, f64) {
+    let p_pow = p.powf(-theta);
+    let s_ = s * p_pow;
+    let dsafety_ds_ = 1.0 / (1.0 + s_).powi(2);
+    (s_ / (1.0 + s_), dsafety_ds_ * p_pow, -dsafety_ds_ * theta * s_ / p)
+}
+
+
 #[derive(Clone)]
 pub struct WinnerOnlyRisk {
     pub theta: Array<f64, Ix1>,
 }
 
 impl WinnerOnlyRisk {
-    pub fn new(n: usize, theta: f64) -> Result<Self, &'static str> {
+    pub fn new(n: usize, theta: f64) -> Result<Self, DynapaiError> {
         Ok(WinnerOnlyRisk {
             theta: Array::from_elem(n, theta),
         })
@@ -40,8 +55,16 @@ impl WinnerOnlyRisk {
 
 impl RiskFunc for WinnerOnlyRisk {
     fn sigma_i(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> f64 {
-        let s_ = s[i] * p[i].powf(-self.theta[i]);
-        s_ / (1.0 + s_)
+        safety(s[i], p[i], self.theta[i]).0
+    }
+
+    fn dsigma_i(&self, i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let mut ds = Array::zeros(s.len());
+        let mut dp = Array::zeros(p.len());
+        let (_, dsafety_ds, dsafety_dp) = safety(s[i], p[i], self.theta[i]);
+        ds[i] = dsafety_ds;
+        dp[i] = dsafety_dp;
+        (ds, dp)
     }
 
     fn n(&self) -> usize {
@@ -60,3 +83,190 @@ impl fmt::Display for WinnerOnlyRisk {
         write!(f, "WinnerOnlyRisk: theta = {}", self.theta)
     }
 }
+
+
+// every player's system has to be safe, whoever wins,
+// so sigma_i is the product of each player's safety, as in WinnerOnlyRisk
+#[derive(Clone)]
+pub struct AllPlayersRisk {
+    pub theta: Array<f64, Ix1>,
+}
+
+impl AllPlayersRisk {
+    pub fn new(n: usize, theta: f64) -> Result<Self, DynapaiError> {
+        Ok(AllPlayersRisk {
+            theta: Array::from_elem(n, theta),
+        })
+    }
+}
+
+impl RiskFunc for AllPlayersRisk {
+    fn sigma_i(&self, _i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> f64 {
+        (0..s.len()).map(|j| safety(s[j], p[j], self.theta[j]).0).product()
+    }
+
+    // same for every i, since it doesn't depend on who wins
+    fn sigma(&self, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> Array<f64, Ix1> {
+        Array::from_elem(s.len(), self.sigma_i(0, s, p))
+    }
+
+    fn dsigma_i(&self, _i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let safeties = (0..s.len()).map(|j| safety(s[j], p[j], self.theta[j])).collect::<Vec<_>>();
+        let mut ds = Array::zeros(s.len());
+        let mut dp = Array::zeros(p.len());
+        for (k, (_, dsafety_ds, dsafety_dp)) in safeties.iter().enumerate() {
+            // product of the others' safety, found directly in case player k's is 0
+            let others = safeties.iter().enumerate()
+                .filter(|(j, _)| *j != k)
+                .map(|(_, (x, _, _))| x)
+                .product::<f64>();
+            ds[k] = others * dsafety_ds;
+            dp[k] = others * dsafety_dp;
+        }
+        (ds, dp)
+    }
+
+    fn n(&self) -> usize {
+        self.theta.len()
+    }
+}
+
+impl RiskFuncWithTheta for AllPlayersRisk {
+    fn theta(&self) -> &Array<f64, Ix1> {
+        &self.theta
+    }
+}
+
+impl fmt::Display for AllPlayersRisk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AllPlayersRisk: theta = {}", self.theta)
+    }
+}
+
+
+// safety of the whole field, with aggregate safety sum(s) against aggregate capability sum(p);
+// this isn't a RiskFuncWithTheta, since theta() is one exponent per player, applied to that player's p,
+// while the single theta here applies to sum(p), so no vector of per-player thetas gives the same sigma
+#[derive(Clone)]
+pub struct AggregateRisk {
+    pub n: usize,
+    pub theta: f64,
+}
+
+impl AggregateRisk {
+    pub fn new(n: usize, theta: f64) -> Result<Self, DynapaiError> {
+        Ok(AggregateRisk { n, theta })
+    }
+}
+
+impl RiskFunc for AggregateRisk {
+    fn sigma_i(&self, _i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> f64 {
+        safety(s.sum(), p.sum(), self.theta).0
+    }
+
+    fn dsigma_i(&self, _i: usize, s: ArrayView<f64, Ix1>, p: ArrayView<f64, Ix1>) -> (Array<f64, Ix1>, Array<f64, Ix1>) {
+        let (_, dsafety_ds, dsafety_dp) = safety(s.sum(), p.sum(), self.theta);
+        (Array::from_elem(s.len(), dsafety_ds), Array::from_elem(p.len(), dsafety_dp))
+    }
+
+    fn n(&self) -> usize {
+        self.n
+    }
+}
+
+impl fmt::Display for AggregateRisk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AggregateRisk: n = {}, theta = {}", self.n, self.theta)
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum RiskFuncSpec {
+    #[serde(rename = "WinnerOnlyRisk")]
+    WinnerOnly { theta: Vec<f64> },
+    #[serde(rename = "AllPlayersRisk")]
+    AllPlayers { theta: Vec<f64> },
+    #[serde(rename = "AggregateRisk")]
+    Aggregate { n: usize, theta: f64 },
+}
+
+impl Serialize for Box<dyn RiskFunc> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if let Some(x) = self.downcast_ref::<WinnerOnlyRisk>() {
+            RiskFuncSpec::WinnerOnly { theta: x.theta.to_vec() }
+        }
+        else if let Some(x) = self.downcast_ref::<AllPlayersRisk>() {
+            RiskFuncSpec::AllPlayers { theta: x.theta.to_vec() }
+        }
+        else if let Some(x) = self.downcast_ref::<AggregateRisk>() {
+            RiskFuncSpec::Aggregate { n: x.n, theta: x.theta }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this RiskFunc"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn RiskFunc> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match RiskFuncSpec::deserialize(deserializer)? {
+            RiskFuncSpec::WinnerOnly { theta } => Box::new(WinnerOnlyRisk { theta: Array::from(theta) }),
+            RiskFuncSpec::AllPlayers { theta } => Box::new(AllPlayersRisk { theta: Array::from(theta) }),
+            RiskFuncSpec::Aggregate { n, theta } => Box::new(AggregateRisk { n, theta }),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    fn check_grads(risk_func: &dyn RiskFunc, s: &Array<f64, Ix1>, p: &Array<f64, Ix1>) {
+        for i in 0..risk_func.n() {
+            let (ds, dp) = risk_func.dsigma_i(i, s.view(), p.view());
+            assert_close(&ds, &fd_grad(s.view(), |s| risk_func.sigma_i(i, s, p.view())), 1e-6);
+            assert_close(&dp, &fd_grad(p.view(), |p| risk_func.sigma_i(i, s.view(), p)), 1e-6);
+        }
+    }
+
+    #[test]
+    fn winner_only_uses_the_winners_safety() {
+        let risk_func = WinnerOnlyRisk { theta: array![0.5, 0.7] };
+        let (s, p) = (array![1., 2.], array![3., 0.5]);
+        let expected = [1. * 3f64.powf(-0.5), 2. * 0.5f64.powf(-0.7)].map(|x| x / (1. + x));
+        assert_close(&risk_func.sigma(s.view(), p.view()), &expected, 1e-12);
+        assert_eq!(risk_func.theta(), &array![0.5, 0.7]);
+        check_grads(&risk_func, &s, &p);
+    }
+
+    #[test]
+    fn all_players_is_the_product_of_safeties() {
+        let risk_func = AllPlayersRisk { theta: array![0.5, 0.7, 0.3] };
+        let (s, p) = (array![1., 2., 0.5], array![3., 0.5, 1.5]);
+        let product = (0..3).map(|j| safety(s[j], p[j], risk_func.theta[j]).0).product::<f64>();
+        assert_close(&risk_func.sigma(s.view(), p.view()), &[product; 3], 1e-12);
+        assert_eq!(risk_func.theta(), &array![0.5, 0.7, 0.3]);
+        check_grads(&risk_func, &s, &p);
+        // with one player's safety at 0, only that player's gradient is nonzero
+        let s = array![0., 2., 0.5];
+        let (ds, _) = risk_func.dsigma_i(0, s.view(), p.view());
+        assert!(ds[0] > 0.);
+        assert_eq!((ds[1], ds[2]), (0., 0.));
+    }
+
+    #[test]
+    fn aggregate_uses_total_safety_and_capability() {
+        let risk_func = AggregateRisk::new(3, 0.6).unwrap();
+        let (s, p) = (array![1., 2., 0.5], array![3., 0.5, 1.5]);
+        let x = 3.5 * 5f64.powf(-0.6);
+        assert_close(&risk_func.sigma(s.view(), p.view()), &[x / (1. + x); 3], 1e-12);
+        check_grads(&risk_func, &s, &p);
+    }
+}
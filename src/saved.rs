@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::Path;
+
+use ndarray::{Array, Ix1, Ix3};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::prelude::*;
+
+const METADATA_FILE: &str = "result.json";
+const STRATEGIES_FILE: &str = "strategies.npy";
+
+
+// a solver run together with the model and options that produced it;
+// saved as a directory holding result.json, with everything but the strategies
+// and tagged with the action type, and strategies.npy, with the array of shape (t, n, nparams) given by Strategies::data
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Box<dyn Aggregator<A>>: Serialize",
+    deserialize = "Box<dyn Aggregator<A>>: Deserialize<'de>",
+))]
+pub struct SavedResult<A: ActionType + Clone + 'static> {
+    pub status: String,
+    pub strategies: Strategies<A>,
+    pub payoffs: Array<f64, Ix1>,
+    pub aggregator: Box<dyn Aggregator<A>>,
+    // also holds the seed of the run, see seed
+    pub options: SolverOptions<A>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Box<dyn Aggregator<A>>: Serialize",
+    deserialize = "Box<dyn Aggregator<A>>: Deserialize<'de>",
+))]
+struct Metadata<A: ActionType + Clone + 'static> {
+    atype: String,
+    status: String,
+    payoffs: Vec<f64>,
+    aggregator: Box<dyn Aggregator<A>>,
+    options: SolverOptions<A>,
+}
+
+impl<A: ActionType + Clone + 'static> SavedResult<A> {
+    // payoffs are evaluated under aggregator
+    pub fn new(
+        status: String,
+        strategies: Strategies<A>,
+        aggregator: Box<dyn Aggregator<A>>,
+        options: SolverOptions<A>,
+    ) -> Result<Self, DynapaiError> {
+        if strategies.n() != aggregator.n() {
+            return Err(DynapaiError::DimensionMismatch("When creating new SavedResult: strategies must have n == aggregator.n()".to_string()));
+        }
+        Ok(SavedResult {
+            status,
+            payoffs: aggregator.u(&strategies),
+            strategies,
+            aggregator,
+            options,
+        })
+    }
+
+    // seed used to draw the initial guess and the solver's randomness, if any
+    pub fn seed(&self) -> Option<u64> {
+        self.options.seed
+    }
+
+    // warm start for solving again from this result
+    pub fn init_guess(&self) -> InitGuess<A> {
+        InitGuess::Fixed(self.strategies.clone())
+    }
+
+    pub fn verify(&self, verify_options: &VerifyOptions) -> Result<Verification<A>, DynapaiError> {
+        verify(self.aggregator.as_ref(), &self.strategies, &self.options, verify_options)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DynapaiError>
+    where Box<dyn Aggregator<A>>: Serialize
+    {
+        fs::create_dir_all(path).map_err(|e| DynapaiError::Serialization(format!("Error when creating {}: {}", path.display(), e)))?;
+        let metadata = Metadata {
+            atype: A::atype().to_string(),
+            status: self.status.clone(),
+            payoffs: self.payoffs.to_vec(),
+            aggregator: self.aggregator.clone(),
+            options: self.options.clone(),
+        };
+        let metadata_path = path.join(METADATA_FILE);
+        fs::write(&metadata_path, to_json(&metadata)?)
+            .map_err(|e| DynapaiError::Serialization(format!("Error when writing {}: {}", metadata_path.display(), e)))?;
+        write_npy(&path.join(STRATEGIES_FILE), &self.strategies.data())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, DynapaiError>
+    where Box<dyn Aggregator<A>>: DeserializeOwned
+    {
+        let atype = saved_atype(path)?;
+        if atype != A::atype() {
+            return Err(DynapaiError::WrongActionType(format!("Saved result has atype {}, expected {}", atype, A::atype())));
+        }
+        let metadata: Metadata<A> = from_json(&read_metadata(path)?)?;
+        let strategies = Strategies::from_data(read_npy::<Ix3>(&path.join(STRATEGIES_FILE))?)?;
+        if strategies.n() != metadata.aggregator.n() {
+            return Err(DynapaiError::DimensionMismatch(format!(
+                "Saved strategies have {} players, but saved aggregator has {}", strategies.n(), metadata.aggregator.n()
+            )));
+        }
+        Ok(SavedResult {
+            status: metadata.status,
+            strategies,
+            payoffs: Array::from(metadata.payoffs),
+            aggregator: metadata.aggregator,
+            options: metadata.options,
+        })
+    }
+}
+
+// action type of the result saved at path, to pick the type to load it as
+pub fn saved_atype(path: &Path) -> Result<String, DynapaiError> {
+    #[derive(Deserialize)]
+    struct Header {
+        atype: String,
+    }
+    from_json::<Header>(&read_metadata(path)?).map(|h| h.atype)
+}
+
+fn read_metadata(path: &Path) -> Result<String, DynapaiError> {
+    let metadata_path = path.join(METADATA_FILE);
+    fs::read_to_string(&metadata_path).map_err(|e| DynapaiError::Serialization(format!("Error when reading {}: {}", metadata_path.display(), e)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn save_and_load() {
+        let dir = temp_dir("saved");
+        let agg: Box<dyn Aggregator<InvestActions>> = Box::new(invest_agg(Box::new(DefaultCSF)));
+        let options = SolverOptions { seed: Some(7), init_guess: InitGuess::random(2, Some(7)), ..solver_options(2) };
+        let strategies = strategies::<InvestActions>(2, 2);
+        let saved = SavedResult::new("done".to_string(), strategies.clone(), agg.clone(), options).unwrap();
+        saved.save(&dir).unwrap();
+
+        let loaded = SavedResult::<InvestActions>::load(&dir).unwrap();
+        assert_eq!(loaded.status, "done");
+        assert_eq!(loaded.strategies.data(), strategies.data());
+        assert_eq!(loaded.payoffs, agg.u(&strategies));
+        assert_eq!(loaded.aggregator.u(&strategies), agg.u(&strategies));
+        assert_eq!(loaded.seed(), Some(7));
+        assert!(matches!(loaded.options.init_guess, InitGuess::Random { t: 2, seed: Some(7), .. }));
+        assert_eq!(saved_atype(&dir).unwrap(), "invest");
+        // the result is tagged with its action type
+        assert!(matches!(SavedResult::<Actions>::load(&dir), Err(DynapaiError::WrongActionType(_))));
+        assert!(matches!(SavedResult::<SharingActions>::load(&dir), Err(DynapaiError::WrongActionType(_))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
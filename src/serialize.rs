@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::Path;
+
+use ndarray::{Array, Dimension, Ix2, IxDyn};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::error::DynapaiError;
+
+// model components are serialized through an enum per trait, tagged with the full type name,
+// e.g. {"type": "DefaultProd", "a": [...], ...},
+// with variants holding the arguments to each type's constructor
+
+pub fn to_json<T: Serialize + ?Sized>(x: &T) -> Result<String, DynapaiError> {
+    serde_json::to_string_pretty(x).map_err(|e| DynapaiError::Serialization(format!("Error when serializing to json: {}", e)))
+}
+
+pub fn from_json<T: DeserializeOwned>(s: &str) -> Result<T, DynapaiError> {
+    serde_json::from_str(s).map_err(|e| DynapaiError::Serialization(format!("Error when deserializing from json: {}", e)))
+}
+
+pub fn to_toml<T: Serialize + ?Sized>(x: &T) -> Result<String, DynapaiError> {
+    toml::to_string_pretty(x).map_err(|e| DynapaiError::Serialization(format!("Error when serializing to toml: {}", e)))
+}
+
+pub fn from_toml<T: DeserializeOwned>(s: &str) -> Result<T, DynapaiError> {
+    toml::from_str(s).map_err(|e| DynapaiError::Serialization(format!("Error when deserializing from toml: {}", e)))
+}
+
+// 2d arrays are written as lists of rows
+pub(crate) fn to_rows(x: &Array<f64, Ix2>) -> Vec<Vec<f64>> {
+    x.rows().into_iter().map(|row| row.to_vec()).collect()
+}
+
+pub(crate) fn from_rows(rows: Vec<Vec<f64>>) -> Result<Array<f64, Ix2>, DynapaiError> {
+    let ncols = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != ncols) {
+        return Err(DynapaiError::DimensionMismatch("All rows must have the same length".to_string()));
+    }
+    Ok(Array::from_shape_vec((rows.len(), ncols), rows.concat())?)
+}
+
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+// writes x in numpy's .npy format (version 1.0, little endian f64, C order)
+pub fn write_npy<D: Dimension>(path: &Path, x: &Array<f64, D>) -> Result<(), DynapaiError> {
+    let shape = match x.shape() {
+        [k] => format!("({},)", k),
+        shape => format!("({})", shape.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}", shape);
+    // magic, version, and header length take 10 bytes, and the data must start on a multiple of 64
+    let pad = (64 - (10 + header.len() + 1) % 64) % 64;
+    header.push_str(&" ".repeat(pad));
+    header.push('\n');
+    let mut bytes = Vec::with_capacity(10 + header.len() + 8 * x.len());
+    bytes.extend_from_slice(NPY_MAGIC);
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    x.iter().for_each(|v| bytes.extend_from_slice(&v.to_le_bytes()));
+    fs::write(path, bytes).map_err(|e| DynapaiError::Serialization(format!("Error when writing {}: {}", path.display(), e)))
+}
+
+// reads a .npy file of little endian f64 in C order, such as written by write_npy or numpy.save
+pub fn read_npy<D: Dimension>(path: &Path) -> Result<Array<f64, D>, DynapaiError> {
+    let bytes = fs::read(path).map_err(|e| DynapaiError::Serialization(format!("Error when reading {}: {}", path.display(), e)))?;
+    let err = |msg: &str| DynapaiError::Serialization(format!("Invalid npy file {}: {}", path.display(), msg));
+    if bytes.len() < 10 || &bytes[..6] != NPY_MAGIC {
+        return Err(err("missing magic string"));
+    }
+    let (header_len, start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize, 12),
+        _ => return Err(err("unsupported version")),
+    };
+    let header = bytes.get(start..start + header_len)
+        .and_then(|h| std::str::from_utf8(h).ok())
+        .ok_or_else(|| err("invalid header"))?;
+    if !header.contains("'descr': '<f8'") {
+        return Err(err("expected dtype '<f8'"));
+    }
+    if !header.contains("'fortran_order': False") {
+        return Err(err("expected C order"));
+    }
+    let shape = header.split("'shape': (").nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .ok_or_else(|| err("missing shape"))?
+        .split(',')
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(|k| k.parse::<usize>().map_err(|_| err("invalid shape")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data = bytes[start + header_len..].chunks_exact(8)
+        .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    Array::from_shape_vec(IxDyn(&shape), data)
+        .map_err(|e| err(&e.to_string()))?
+        .into_dimensionality::<D>()
+        .map_err(|e| err(&e.to_string()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Ix1, Ix3, array};
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::test_utils::*;
+
+    // serializing and deserializing must give an aggregator with identical payoffs,
+    // which serializes to the same text
+    fn check_round_trip<A>(agg: Box<dyn Aggregator<A>>)
+    where A: ActionType + Clone + 'static, Box<dyn Aggregator<A>>: Serialize + DeserializeOwned
+    {
+        let strategies = strategies::<A>(2, 2);
+        let u = agg.u(&strategies);
+
+        let json = to_json(&agg).unwrap();
+        let agg_json: Box<dyn Aggregator<A>> = from_json(&json).unwrap();
+        assert_eq!(agg_json.u(&strategies), u, "{}", json);
+        assert_eq!(to_json(&agg_json).unwrap(), json);
+
+        let toml = to_toml(&agg).unwrap();
+        let agg_toml: Box<dyn Aggregator<A>> = from_toml(&toml).unwrap();
+        assert_eq!(agg_toml.u(&strategies), u, "{}", toml);
+        assert_eq!(to_toml(&agg_toml).unwrap(), toml);
+    }
+
+    fn dyn_agg<A: ActionType + Clone + 'static>(payoff: ModularPayoff<A>) -> Box<dyn Aggregator<A>> {
+        Box::new(DynStateDiscounter::new(Box::new(CommonBeliefs(Box::new(payoff))), array![0.9, 0.8]).unwrap())
+    }
+
+    #[test]
+    fn csfs_round_trip() {
+        let csfs: Vec<Box<dyn CSF>> = vec![
+            Box::new(DefaultCSF),
+            Box::new(MaybeNoWinCSF::new(1.5).unwrap()),
+            Box::new(TullockCSF::new(2.).unwrap()),
+            Box::new(MaybeNoWinTullockCSF::new(0.5, 1.5).unwrap()),
+            Box::new(LogitCSF::new(1.5).unwrap()),
+            Box::new(MaybeNoWinLogitCSF::new(1.5, 0.5).unwrap()),
+            Box::new(WeightedCSF::new(array![1., 2.], 1.5).unwrap()),
+            Box::new(MaybeNoWinWeightedCSF::new(array![1., 0.5], 1.5).unwrap()),
+        ];
+        for csf in csfs {
+            check_round_trip(dyn_agg(invest_payoff(csf)));
+        }
+    }
+
+    #[test]
+    fn risk_funcs_round_trip() {
+        let risk_funcs: Vec<Box<dyn RiskFunc>> = vec![
+            Box::new(WinnerOnlyRisk { theta: array![0.5, 0.7] }),
+            Box::new(AllPlayersRisk { theta: array![0.5, 0.7] }),
+            Box::new(AggregateRisk::new(2, 0.6).unwrap()),
+        ];
+        for risk_func in risk_funcs {
+            check_round_trip(dyn_agg(ModularPayoff { risk_func, ..invest_payoff(Box::new(DefaultCSF)) }));
+        }
+    }
+
+    #[test]
+    fn reward_funcs_round_trip() {
+        let reward_funcs: Vec<Box<dyn RewardFunc>> = vec![
+            Box::new(LinearReward::new(array![1., 1.2], array![0.1, 0.], array![0.2, 0.1], array![0., 0.05]).unwrap()),
+            Box::new(MatrixReward::new(array![[1., 0.2], [0.1, 1.2]], array![[0.1, 0.], [0.05, 0.]]).unwrap()),
+            Box::new(PowerReward::new(array![1., 1.2], array![0.5, 1.], array![0.2, 0.1], array![0., 0.5]).unwrap()),
+            Box::new(CapabilityGapReward::new(array![1., 1.2], array![0.1, 0.], array![0.2, 0.1], array![0.3, 0.1]).unwrap()),
+        ];
+        for reward_func in reward_funcs {
+            check_round_trip(dyn_agg(ModularPayoff { reward_func, ..invest_payoff(Box::new(DefaultCSF)) }));
+        }
+    }
+
+    #[test]
+    fn disaster_costs_round_trip() {
+        let disaster_costs: Vec<Box<dyn DisasterCost>> = vec![
+            Box::new(ConstantDisasterCost { d: array![1., 0.8] }),
+            Box::new(MatrixDisasterCost::new(array![[1., 2.], [0.5, 0.8]], None).unwrap()),
+            Box::new(CapabilityDisasterCost::new(array![1., 0.8], 1.5, Some(array![2., 0.5])).unwrap()),
+        ];
+        for disaster_cost in disaster_costs {
+            check_round_trip(dyn_agg(ModularPayoff { disaster_cost, ..invest_payoff(Box::new(MaybeNoWinCSF::new(1.).unwrap())) }));
+        }
+    }
+
+    #[test]
+    fn states_and_aggregators_round_trip() {
+        check_round_trip(dyn_agg(basic_payoff(Box::new(DefaultCSF))));
+        check_round_trip(dyn_agg(sharing_payoff(Box::new(DefaultCSF))));
+
+        let mut other = invest_payoff(Box::new(DefaultCSF));
+        other.set_state(array![0.8, 1.2, 1., 1.].view());
+        let het = || -> Box<dyn State<InvestActions>> {
+            Box::new(HetBeliefs::new(vec![Box::new(invest_payoff(Box::new(DefaultCSF))), Box::new(other.clone())]).unwrap())
+        };
+        let gammas = array![0.9, 0.8];
+        check_round_trip::<InvestActions>(Box::new(FixedStateDiscounter::new(het(), gammas.clone()).unwrap()));
+        check_round_trip::<InvestActions>(Box::new(DynStateDiscounter::new(het(), gammas.clone()).unwrap()));
+        check_round_trip::<InvestActions>(Box::new(EndsOnContestWin::new(
+            FixedStateDiscounter::new(het(), gammas.clone()).unwrap()
+        ).unwrap()));
+        check_round_trip::<InvestActions>(Box::new(EndsOnContestWin::new(
+            DynStateDiscounter::new(het(), gammas).unwrap()
+        ).unwrap()));
+    }
+
+    #[test]
+    fn npy_round_trip() {
+        let dir = temp_dir("npy");
+        let path = dir.join("x.npy");
+
+        let x1 = array![1., -2.5, 3.];
+        write_npy(&path, &x1).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        // data starts on a multiple of 64, after a newline
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(bytes[10 + header_len - 1], b'\n');
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }"), "{}", header);
+        assert_eq!(bytes.len(), 10 + header_len + 8 * 3);
+        assert_eq!(read_npy::<Ix1>(&path).unwrap(), x1);
+
+        let x3 = Array::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 12 + j * 4 + k) as f64 / 7.);
+        write_npy(&path, &x3).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 3, 4)"), "{}", header);
+        assert_eq!(read_npy::<Ix3>(&path).unwrap(), x3);
+        // wrong number of dimensions
+        assert!(read_npy::<Ix2>(&path).is_err());
+
+        // only little endian f64 is supported
+        let mut bytes = fs::read(&path).unwrap();
+        let descr = bytes.windows(3).position(|w| w == b"<f8").unwrap();
+        bytes[descr + 2] = b'4';
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(read_npy::<Ix3>(&path), Err(DynapaiError::Serialization(_))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
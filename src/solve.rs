@@ -1,44 +1,133 @@
-use numpy::ndarray::{Array, ArrayView, Ix2, s};
-use argmin::core::{CostFunction, Executor};
+use ndarray::{Array, ArrayView, Dimension, Ix1, Ix2, Zip, s};
+use argmin::core::{CostFunction, Executor, Gradient};
+use argmin::solver::linesearch::MoreThuenteLineSearch;
 use argmin::solver::neldermead::NelderMead;
-use rayon::prelude::*;
+use argmin::solver::quasinewton::LBFGS;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 use crate::prelude::*;
 
-const INIT_MU: f64 = -1.;
-const INIT_SIGMA: f64 = 0.1;
+pub const INIT_MU: f64 = -1.;
+pub const INIT_SIGMA: f64 = 0.1;
+pub const MIXED_TOL: f64 = 1e-3;
+// floor on log actions, keeps actions that are driven to zero from underflowing
+pub(crate) const MIN_LOG_ACTION: f64 = -100.;
 
+pub(crate) fn from_log(x: &f64) -> f64 {
+    x.max(MIN_LOG_ACTION).exp()
+}
 
-#[derive(Clone)]
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub enum InitGuess<A: ActionType> {
-    Random(usize),
+    // t periods drawn from lognormal(mu, sigma), see Strategies::random_seeded;
+    // a different draw each time if seed is None
+    Random {
+        t: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+        #[serde(default = "init_mu")]
+        mu: f64,
+        #[serde(default = "init_sigma")]
+        sigma: f64,
+    },
     Fixed(Strategies<A>),
 }
 
+fn init_mu() -> f64 { INIT_MU }
+fn init_sigma() -> f64 { INIT_SIGMA }
+fn mixed_tol() -> f64 { MIXED_TOL }
+
 impl<A: ActionType + Clone> InitGuess<A> {
-    fn to_fixed(&self, n: usize) -> Strategies<A> {
+    // random guess with the default distribution
+    pub fn random(t: usize, seed: Option<u64>) -> Self {
+        InitGuess::Random { t, seed, mu: INIT_MU, sigma: INIT_SIGMA }
+    }
+
+    // lognormal parameters of a random guess, or the defaults for a fixed one
+    pub fn distribution(&self) -> (f64, f64) {
         match self {
-            InitGuess::Random(t) => Strategies::<A>::random(*t, n, INIT_MU, INIT_SIGMA).unwrap(),
-            InitGuess::Fixed(x) => x.clone(),
+            InitGuess::Random { mu, sigma, .. } => (*mu, *sigma),
+            InitGuess::Fixed(_) => (INIT_MU, INIT_SIGMA),
+        }
+    }
+
+    // number of periods
+    pub fn t(&self) -> usize {
+        match self {
+            InitGuess::Random { t, .. } => *t,
+            InitGuess::Fixed(x) => x.t(),
+        }
+    }
+
+    pub(crate) fn to_fixed(&self, n: usize) -> Result<Strategies<A>, DynapaiError> {
+        match self {
+            InitGuess::Random { t, seed, mu, sigma } => Strategies::<A>::random_seeded(*t, n, *mu, *sigma, *seed),
+            InitGuess::Fixed(x) => Ok(x.clone()),
         }
     }
 }
 
 
-// TODO: Add option to save trace of solver progress
-#[derive(Clone)]
+// called after each outer iteration of a solver with the iteration number and current payoffs,
+// possibly from several threads at once (e.g. in solve_multistart);
+// returning an error, typically DynapaiError::Cancelled, stops the solver with that error
+pub type SolverCallback = Arc<dyn Fn(u64, &Array<f64, Ix1>) -> Result<(), DynapaiError> + Send + Sync>;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct SolverOptions<A: ActionType + Clone> {
     pub init_guess: InitGuess<A>,
     pub iters: u64,
     pub tol: f64,
+    pub method: SolverMethod,
+    pub update: UpdateMode,
+    // weight on the new best response when blending it with the previous strategy in log space,
+    // 1 means no damping
+    pub damping: f64,
     pub nm_options: NMOptions,
-    // last two options needed only for mixed solver
+    pub lbfgs_options: LBFGSOptions,
+    // whether to record a SolverTrace
+    pub trace: bool,
+    // if provided, check whether the solution is an equilibrium
+    pub verify: Option<VerifyOptions>,
+    // last three options needed only for mixed solver:
+    // max number of strategies in each player's support
     pub hist_size: usize,
+    // number of opponent profiles sampled in each iteration
     pub mixed_samples: usize,
+    // fictitious play stops once exploitability is at most mixed_tol times the largest payoff
+    // in absolute value, since tol is a relative change in payoffs rather than a gap
+    #[serde(default = "mixed_tol")]
+    pub mixed_tol: f64,
+    // whether to solve for players' best responses in parallel
     pub parallel: bool,
+    // if provided, run in a dedicated thread pool of this size
+    pub threads: Option<usize>,
+    // seeds the randomness in solve_mixed, verify and solve_multistart; drawn from entropy if None
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // for progress reporting and cancellation, not saved with the options
+    #[serde(skip)]
+    pub callback: Option<SolverCallback>,
 }
 
-#[derive(Clone, Debug)]
+impl<A: ActionType + Clone> SolverOptions<A> {
+    // runs the callback, if any, only computing payoffs when there is one
+    pub(crate) fn progress<F>(&self, iter: u64, payoffs: F) -> Result<(), DynapaiError>
+    where F: FnOnce() -> Array<f64, Ix1>
+    {
+        match &self.callback {
+            Some(callback) => callback(iter, &payoffs()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NMOptions {
     pub init_simplex_size: f64,
     pub iters: u64,
@@ -56,7 +145,82 @@ impl Default for NMOptions {
 }
 
 
-fn create_simplex(init_guess: ArrayView<f64, Ix2>, init_simplex_size: f64) -> Vec<Vec<f64>> {
+// method used to find each player's best response;
+// named nelder_mead and lbfgs in model files and in Python
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SolverMethod {
+    NelderMead,
+    // uses gradients from Aggregator::du_i
+    #[serde(rename = "lbfgs")]
+    LBFGS,
+}
+
+// how players' strategies are updated in each iteration
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateMode {
+    // all players respond to the strategies from the previous iteration
+    Jacobi,
+    // players respond in order, each to the already updated strategies of earlier players
+    GaussSeidel,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LBFGSOptions {
+    pub memory: usize,
+    pub iters: u64,
+    pub tol_grad: f64,
+    pub tol_cost: f64,
+}
+
+impl Default for LBFGSOptions {
+    fn default() -> Self {
+        LBFGSOptions {
+            memory: 10,
+            iters: 200,
+            tol_grad: 1e-8,
+            tol_cost: 1e-12,
+        }
+    }
+}
+
+
+// summary of how the solver for a single player's best response exited
+#[derive(Clone, Debug)]
+pub struct BestResponseInfo {
+    pub iters: u64,
+    pub termination: String,
+}
+
+// history of the outer (best response) iterations in solve
+#[derive(Clone)]
+pub struct SolverTrace<A: ActionType> {
+    pub strategies: Vec<Strategies<A>>,
+    pub payoffs: Vec<Array<f64, Ix1>>,
+    // max absolute change in payoffs from the previous iteration
+    pub max_change: Vec<f64>,
+    pub best_responses: Vec<Vec<BestResponseInfo>>,
+}
+
+impl<A: ActionType> SolverTrace<A> {
+    fn new() -> Self {
+        SolverTrace {
+            strategies: Vec::new(),
+            payoffs: Vec::new(),
+            max_change: Vec::new(),
+            best_responses: Vec::new(),
+        }
+    }
+
+    pub fn iters(&self) -> usize {
+        self.strategies.len()
+    }
+}
+
+
+pub(crate) fn create_simplex(init_guess: ArrayView<f64, Ix2>, init_simplex_size: f64) -> Vec<Vec<f64>> {
     let mut simplex = Vec::new();
     let base: Vec<f64> = init_guess.iter().map(|x| x.ln()).collect();
     for i in 0..base.len() {
@@ -74,7 +238,18 @@ fn create_simplex(init_guess: ArrayView<f64, Ix2>, init_simplex_size: f64) -> Ve
 struct PlayerObjective<'a, A: ActionType + Clone>{
     pub payoff_aggregator: &'a dyn Aggregator<A>,
     pub i: usize,
-    pub base_strategies: &'a Strategies<A>,
+    // player i maximizes their expected payoff over these profiles, with the given probabilities
+    pub base_strategies: &'a [Strategies<A>],
+    pub weights: &'a [f64],
+}
+
+impl<A: ActionType + Clone + 'static> PlayerObjective<'_, A> {
+    fn x_from_params(&self, params: &[f64]) -> Result<Array<f64, Ix2>, DynapaiError> {
+        Ok(Array::from_shape_vec(
+            (self.base_strategies[0].t(), A::nparams()),
+            params.iter().map(from_log).collect(),
+        )?)
+    }
 }
 
 impl<A: ActionType + Clone + 'static> CostFunction for PlayerObjective<'_, A> {
@@ -82,152 +257,297 @@ impl<A: ActionType + Clone + 'static> CostFunction for PlayerObjective<'_, A> {
     type Output = f64;
 
     fn cost(&self, params: &Self::Param) -> Result<Self::Output, argmin::core::Error> {
-        // unfortunately have to clone strategies every time
-        // since cost doesn't allow mutable self
-        let mut strategies = self.base_strategies.clone();
-        strategies.set_i(
-            self.i,
-            Array::from_shape_vec(
-                (self.base_strategies.t(), A::nparams()),
-                params.iter().map(|x| x.exp()).collect(),
-            )?
-        );
-        Ok(-self.payoff_aggregator.u_i(self.i, &strategies))
+        let x = self.x_from_params(params)?;
+        let mut u = 0.;
+        for (base, w) in self.base_strategies.iter().zip(self.weights.iter()) {
+            // unfortunately have to clone strategies every time
+            // since cost doesn't allow mutable self
+            let mut strategies = base.clone();
+            strategies.set_i(self.i, x.clone());
+            u += w * self.payoff_aggregator.u_i(self.i, &strategies);
+        }
+        Ok(-u)
+    }
+}
+
+impl<A: ActionType + Clone + 'static> Gradient for PlayerObjective<'_, A> {
+    type Param = Vec<f64>;
+    type Gradient = Vec<f64>;
+
+    fn gradient(&self, params: &Self::Param) -> Result<Self::Gradient, argmin::core::Error> {
+        let x = self.x_from_params(params)?;
+        let mut grad = Array::zeros(x.dim());
+        for (base, w) in self.base_strategies.iter().zip(self.weights.iter()) {
+            let mut strategies = base.clone();
+            strategies.set_i(self.i, x.clone());
+            grad.scaled_add(*w, &self.payoff_aggregator.du_i(self.i, &strategies));
+        }
+        // chain rule through x = exp(params)
+        grad *= &x;
+        // the line search never terminates on nan, so fail early instead
+        if grad.iter().any(|g| !g.is_finite()) {
+            return Err(DynapaiError::NonFinitePayoff("gradient is not finite".to_string()).into());
+        }
+        Ok(grad.iter().map(|g| -g).collect())
     }
 }
 
-fn solve_for_i<A>(i: usize, strat: &Strategies<A>, agg: &dyn Aggregator<A>, options: &NMOptions) -> Result<Array<f64, Ix2>, argmin::core::Error>
+pub(crate) fn solve_for_i<A>(i: usize, strat: &Strategies<A>, agg: &dyn Aggregator<A>, options: &SolverOptions<A>) -> Result<(Array<f64, Ix2>, BestResponseInfo), DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    best_response(i, std::slice::from_ref(strat), &[1.], strat.data().slice(s![.., i, ..]), agg, options)
+}
+
+// player i's best response to a distribution over profiles, starting from init
+pub(crate) fn best_response<A>(
+    i: usize,
+    base_strategies: &[Strategies<A>],
+    weights: &[f64],
+    init: ArrayView<f64, Ix2>,
+    agg: &dyn Aggregator<A>,
+    options: &SolverOptions<A>,
+) -> Result<(Array<f64, Ix2>, BestResponseInfo), DynapaiError>
 where A: ActionType + Clone + 'static
 {
-    let init_simplex = create_simplex(
-        strat.data().slice(s![.., i, ..]),
-        options.init_simplex_size
-    );
     let obj = PlayerObjective {
         payoff_aggregator: agg,
         i,
-        base_strategies: strat,
+        base_strategies,
+        weights,
     };
+    match options.method {
+        SolverMethod::NelderMead => solve_for_i_nm(obj, init, &options.nm_options),
+        SolverMethod::LBFGS => solve_for_i_lbfgs(obj, init, &options.lbfgs_options),
+    }
+}
+
+fn solve_for_i_lbfgs<A>(obj: PlayerObjective<A>, init: ArrayView<f64, Ix2>, options: &LBFGSOptions) -> Result<(Array<f64, Ix2>, BestResponseInfo), DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let init_param: Vec<f64> = init.iter().map(|x| x.ln().max(MIN_LOG_ACTION)).collect();
+    let solver = LBFGS::new(MoreThuenteLineSearch::new(), options.memory)
+        .with_tolerance_grad(options.tol_grad)?
+        .with_tolerance_cost(options.tol_cost)?;
+    let res = Executor::new(obj, solver)
+        .configure(|state| state.param(init_param).max_iters(options.iters))
+        .run()?;
+    let info = BestResponseInfo {
+        iters: res.state.iter,
+        termination: res.state.termination_reason.text().to_string(),
+    };
+    Ok((Array::from_shape_vec(
+        init.dim(),
+        res.state.best_param.unwrap().iter().map(from_log).collect(),
+    )?, info))
+}
+
+fn solve_for_i_nm<A>(obj: PlayerObjective<A>, init: ArrayView<f64, Ix2>, options: &NMOptions) -> Result<(Array<f64, Ix2>, BestResponseInfo), DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let init_simplex = create_simplex(init, options.init_simplex_size);
     let solver = NelderMead::new(init_simplex).with_sd_tolerance(options.tol)?;
     let res = Executor::new(obj, solver)
         .configure(|state| state.max_iters(options.iters))
         .run()?;
-    Ok(Array::from_shape_vec(
-        (strat.t(), <A as ActionType>::nparams()),
-        res.state.best_param.unwrap().iter().map(|x| x.exp()).collect(),
-    )?)
+    let info = BestResponseInfo {
+        iters: res.state.iter,
+        termination: res.state.termination_reason.text().to_string(),
+    };
+    Ok((Array::from_shape_vec(
+        init.dim(),
+        res.state.best_param.unwrap().iter().map(from_log).collect(),
+    )?, info))
+}
+
+// blends new with old as new^damping * old^(1 - damping)
+pub(crate) fn damp<D: Dimension>(old: ArrayView<f64, D>, mut new: Array<f64, D>, damping: f64) -> Array<f64, D> {
+    if damping != 1. {
+        Zip::from(&mut new).and(&old).for_each(|x, y| *x = x.powf(damping) * y.powf(1. - damping));
+    }
+    new
 }
 
-fn update_strat<A>(strat: &mut Strategies<A>, agg: &dyn Aggregator<A>, nm_options: &NMOptions) -> Result<(), argmin::core::Error>
+// updates the strategies of the given players with their best responses
+pub(crate) fn update_strat<A>(
+    strat: &mut Strategies<A>,
+    players: &[usize],
+    agg: &dyn Aggregator<A>,
+    options: &SolverOptions<A>,
+) -> Result<Vec<BestResponseInfo>, DynapaiError>
 where A: ActionType + Clone + 'static
 {
-    let new_data = (0..strat.n()).into_par_iter().map(|i| {
-        solve_for_i(i, strat, agg, nm_options)
-    }).collect::<Result<Vec<_>,_>>()?;
-    for (i, x) in new_data.into_iter().enumerate() {
-        strat.set_i(i, x);
+    let mut infos = Vec::with_capacity(players.len());
+    match options.update {
+        UpdateMode::Jacobi => {
+            let new_data = map_range(players.len(), options.parallel, |k| {
+                solve_for_i(players[k], strat, agg, options)
+            }).into_iter().collect::<Result<Vec<_>,_>>()?;
+            for (&i, (x, info)) in players.iter().zip(new_data) {
+                let x = damp(strat.data().slice(s![.., i, ..]), x, options.damping);
+                strat.set_i(i, x);
+                infos.push(info);
+            }
+        },
+        UpdateMode::GaussSeidel => {
+            for &i in players {
+                let (x, info) = solve_for_i(i, strat, agg, options)?;
+                let x = damp(strat.data().slice(s![.., i, ..]), x, options.damping);
+                strat.set_i(i, x);
+                infos.push(info);
+            }
+        },
     }
-    Ok(())
+    Ok(infos)
 }
 
-pub fn solve<A>(agg: &dyn Aggregator<A>, options: &SolverOptions<A>) -> Result<Strategies<A>, argmin::core::Error>
+pub fn solve<A>(agg: &dyn Aggregator<A>, options: &SolverOptions<A>) -> Result<SolverResult<A>, DynapaiError>
 where A: ActionType + Clone + 'static
 {
-    let mut strat = options.init_guess.to_fixed(agg.n());
+    with_threads(options.threads, || solve_pure(agg, options))
+}
+
+fn solve_pure<A>(agg: &dyn Aggregator<A>, options: &SolverOptions<A>) -> Result<SolverResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let mut strat = options.init_guess.to_fixed(agg.n())?;
     let mut last_payoffs = agg.u(&strat);
+    let mut trace = if options.trace { Some(SolverTrace::new()) } else { None };
+    let players = (0..agg.n()).collect::<Vec<_>>();
     for i in 0..options.iters {
-        update_strat(&mut strat, agg, &options.nm_options)?;
+        let infos = update_strat(&mut strat, &players, agg, options)?;
         let new_payoffs = agg.u(&strat);
+        if let Some(trace) = trace.as_mut() {
+            trace.strategies.push(strat.clone());
+            trace.max_change.push(
+                new_payoffs.iter().zip(last_payoffs.iter()).fold(0., |acc, (a, b)| f64::max(acc, (a - b).abs()))
+            );
+            trace.payoffs.push(new_payoffs.clone());
+            trace.best_responses.push(infos);
+        }
+        options.progress(i, || new_payoffs.clone())?;
         if isapprox_iters(
             new_payoffs.clone().into_iter(),
             last_payoffs.into_iter(),
             options.tol, f64::EPSILON.sqrt()
         ) {
-            println!("Exited on iteration {}", i);
-            return Ok(strat);
+            return finish_solve(agg, options, strat, format!("Exited on iteration {}", i), trace);
         }
         last_payoffs = new_payoffs;
     }
-    println!("Reached max iterations ({})", options.iters);
-    Ok(strat)
+    finish_solve(agg, options, strat, format!("Reached max iterations ({})", options.iters), trace)
+}
+
+fn finish_solve<A>(
+    agg: &dyn Aggregator<A>,
+    options: &SolverOptions<A>,
+    strat: Strategies<A>,
+    status: String,
+    trace: Option<SolverTrace<A>>,
+) -> Result<SolverResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    check_finite(agg.u(&strat).iter(), "At solution")?;
+    let verification = match &options.verify {
+        Some(verify_options) => Some(verify(agg, &strat, options, verify_options)?),
+        None => None,
+    };
+    Ok(SolverResult {
+        status,
+        strategies: Some(strat),
+        trace,
+        verification,
+    })
 }
 
 
-// now for mixed solver:
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use std::sync::Mutex;
 
-struct MixedPlayerObjective<'a, A: ActionType + Clone>{
-    pub payoff_aggregator: &'a dyn Aggregator<A>,
-    pub i: usize,
-    pub shape: (usize, usize),
-    pub base_strategies: &'a Vec<Strategies<A>>,
-}
+    use super::*;
+    use crate::test_utils::*;
 
-impl<A: ActionType + Clone + 'static> CostFunction for MixedPlayerObjective<'_, A> {
-    type Param = Vec<f64>;
-    type Output = f64;
+    #[test]
+    fn trace_records_every_iteration() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let options = SolverOptions { trace: true, ..solver_options(1) };
+        let res = solve(&agg, &options).unwrap();
+        assert!(res.status.starts_with("Exited"), "{}", res.status);
+        let trace = res.trace.unwrap();
+        let iters = trace.iters();
+        assert!(iters > 1);
+        assert_eq!(trace.payoffs.len(), iters);
+        assert_eq!(trace.max_change.len(), iters);
+        assert_eq!(trace.best_responses.len(), iters);
+        assert!(trace.best_responses.iter().all(|infos| infos.len() == 2));
+        for k in 1..iters {
+            let change = (&trace.payoffs[k] - &trace.payoffs[k - 1]).iter().fold(0., |acc, x| f64::max(acc, x.abs()));
+            assert_close([&trace.max_change[k]], [&change], 1e-12);
+        }
+        assert_close(trace.payoffs.last().unwrap(), &agg.u(res.strategies.as_ref().unwrap()), 1e-12);
+        assert!(*trace.max_change.last().unwrap() < 1e-6);
+    }
 
-    fn cost(&self, params: &Self::Param) -> Result<Self::Output, argmin::core::Error> {
-        let params = Array::from_shape_vec(
-            self.shape,
-            params.iter().map(|x| x.exp()).collect(),
-        )?;
-        let mut out = 0.;
-        for mut strategies in self.base_strategies.clone().into_iter() {
-            strategies.set_i(self.i, params.clone());
-            out -= self.payoff_aggregator.u_i(self.i, &strategies);
+    #[test]
+    fn parallel_matches_serial() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let serial = solve(&agg, &solver_options(1)).unwrap().strategies.unwrap();
+        let options = SolverOptions { parallel: true, threads: Some(2), ..solver_options(1) };
+        let parallel = solve(&agg, &options).unwrap().strategies.unwrap();
+        assert_eq!(serial.data(), parallel.data());
+    }
+
+    #[test]
+    fn gauss_seidel_matches_jacobi() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let jacobi = solve(&agg, &solver_options(1)).unwrap().strategies.unwrap();
+        for damping in [1., 0.5] {
+            let options = SolverOptions { update: UpdateMode::GaussSeidel, damping, ..solver_options(1) };
+            let gauss_seidel = solve(&agg, &options).unwrap().strategies.unwrap();
+            assert_close(&gauss_seidel.data(), &jacobi.data(), 1e-4);
         }
-        Ok(out)
     }
-}
 
-fn solve_for_i_mixed<A>(i: usize, hist: &Vec<Strategies<A>>, agg: &dyn Aggregator<A>, new_init: 
-    ArrayView<f64, Ix2>, options: &NMOptions) -> Result<Array<f64, Ix2>, argmin::core::Error>
-where A: ActionType + Clone + 'static
-{
-    let (t, _, n_goods) = hist[0].data().dim();
-    let init_simplex = create_simplex(
-        new_init,
-        options.init_simplex_size
-    );
-    let mut hist = hist.clone();
-    let obj = MixedPlayerObjective {
-        payoff_aggregator: agg,
-        i,
-        shape: (t, n_goods),
-        base_strategies: &mut hist,
-    };
-    let solver = NelderMead::new(init_simplex).with_sd_tolerance(options.tol)?;
-    let res = Executor::new(obj, solver)
-        .configure(|state| state.max_iters(options.iters))
-        .run()?;
-    Ok(Array::from_shape_vec(
-        (t, n_goods),
-        res.state.best_param.unwrap().iter().map(|x| x.exp()).collect(),
-    )?)
-}
+    #[test]
+    fn seeded_solves_are_reproducible() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let options = SolverOptions { iters: 3, init_guess: InitGuess::random(1, Some(5)), ..solver_options(1) };
+        let a = solve(&agg, &options).unwrap().strategies.unwrap();
+        let b = solve(&agg, &options).unwrap().strategies.unwrap();
+        assert_eq!(a.data(), b.data());
+        let options = SolverOptions { init_guess: InitGuess::random(1, Some(6)), ..options };
+        let c = solve(&agg, &options).unwrap().strategies.unwrap();
+        assert_ne!(a.data(), c.data());
+    }
 
-fn update_strat_mixed<A>(hist: &mut Vec<Strategies<A>>, hist_idx: usize, agg: &dyn Aggregator<A>, nm_options: &NMOptions) -> Result<(), argmin::core::Error>
-where A: ActionType + Clone + 'static
-{
-    let (t, n, _) = hist[0].data().dim();
-    let new_inits = Strategies::<A>::random(t, n, INIT_MU, INIT_SIGMA).unwrap().data();
-    let new_data = (0..n).into_par_iter().map(|i| {
-        solve_for_i_mixed(i, hist, agg, new_inits.slice(s![i, .., ..]), nm_options)
-    }).collect::<Result<Vec<_>,_>>()?;
-    for (i, x) in new_data.into_iter().enumerate() {
-        hist[hist_idx].set_i(i, x);
+    #[test]
+    fn callback_errors_stop_the_solve() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let callback: SolverCallback = Arc::new(move |iter, payoffs| {
+            assert_eq!(payoffs.len(), 2);
+            seen.lock().unwrap().push(iter);
+            match iter {
+                2 => Err(DynapaiError::Cancelled(format!("cancelled on iteration {}", iter))),
+                _ => Ok(()),
+            }
+        });
+        let options = SolverOptions { callback: Some(callback), ..solver_options(1) };
+        assert_eq!(
+            solve(&agg, &options).err(),
+            Some(DynapaiError::Cancelled("cancelled on iteration 2".to_string()))
+        );
+        assert_eq!(*calls.lock().unwrap(), vec![0, 1, 2]);
     }
-    Ok(())
-}
 
-pub fn solve_mixed<A>(agg: &dyn Aggregator<A>, options: &SolverOptions<A>) -> Result<Vec<Strategies<A>>, argmin::core::Error>
-where A: ActionType + Clone + 'static
-{
-    let mut strats = (0..options.hist_size).map(|_| options.init_guess.to_fixed(agg.n())).collect::<Vec<_>>();
-    for _i in 0..options.iters {
-        for j in 0..options.hist_size {
-            update_strat_mixed(&mut strats, j, agg, &options.nm_options)?;
-        }
+    #[test]
+    fn damping_blends_in_log_space() {
+        let old = array![1., 4.];
+        let new = array![4., 1.];
+        assert_close(&damp(old.view(), new.clone(), 1.), &new, 1e-12);
+        assert_close(&damp(old.view(), new.clone(), 0.5), &array![2., 2.], 1e-12);
+        assert_close(&damp(old.view(), new, 0.), &old, 1e-12);
     }
-    Ok(strats)
 }
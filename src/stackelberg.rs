@@ -0,0 +1,215 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ndarray::{Array, Ix1, Ix2, s};
+use argmin::core::{CostFunction, Executor};
+use argmin::solver::neldermead::NelderMead;
+
+use crate::prelude::*;
+
+
+#[derive(Clone)]
+pub struct StackelbergResult<A: ActionType> {
+    pub status: String,
+    // leaders' commitments together with the followers' responses to them
+    pub strategies: Strategies<A>,
+    pub leaders: Vec<usize>,
+    pub payoffs: Array<f64, Ix1>,
+    // number of times the followers' response did not converge while evaluating leaders' strategies
+    pub unconverged: usize,
+}
+
+// iterates the followers' best responses to each other, holding the leaders' strategies fixed,
+// returns whether the followers' payoffs converged
+fn follower_response<A>(
+    strat: &mut Strategies<A>,
+    followers: &[usize],
+    agg: &dyn Aggregator<A>,
+    options: &SolverOptions<A>,
+) -> Result<bool, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    if followers.is_empty() {
+        return Ok(true);
+    }
+    let follower_u = |strat: &Strategies<A>| followers.iter().map(|&i| agg.u_i(i, strat)).collect::<Vec<_>>();
+    let mut last_payoffs = follower_u(strat);
+    for _ in 0..options.iters {
+        update_strat(strat, followers, agg, options)?;
+        let new_payoffs = follower_u(strat);
+        if isapprox_iters(
+            new_payoffs.iter().cloned(), last_payoffs.into_iter(),
+            options.tol, f64::EPSILON.sqrt()
+        ) {
+            return Ok(true);
+        }
+        last_payoffs = new_payoffs;
+    }
+    Ok(false)
+}
+
+struct LeaderObjective<'a, A: ActionType + Clone> {
+    agg: &'a dyn Aggregator<A>,
+    i: usize,
+    followers: &'a [usize],
+    base_strategies: &'a Strategies<A>,
+    options: &'a SolverOptions<A>,
+    unconverged: &'a AtomicUsize,
+}
+
+impl<A: ActionType + Clone + 'static> LeaderObjective<'_, A> {
+    // strategies after leader i commits to params and followers respond
+    fn respond(&self, params: &[f64]) -> Result<Strategies<A>, DynapaiError> {
+        let x = Array::from_shape_vec(
+            (self.base_strategies.t(), A::nparams()),
+            params.iter().map(from_log).collect(),
+        )?;
+        let mut strategies = self.base_strategies.clone();
+        strategies.set_i(self.i, x);
+        if !follower_response(&mut strategies, self.followers, self.agg, self.options)? {
+            self.unconverged.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(strategies)
+    }
+}
+
+impl<A: ActionType + Clone + 'static> CostFunction for LeaderObjective<'_, A> {
+    type Param = Vec<f64>;
+    type Output = f64;
+
+    fn cost(&self, params: &Self::Param) -> Result<Self::Output, argmin::core::Error> {
+        let strategies = self.respond(params)?;
+        Ok(-self.agg.u_i(self.i, &strategies))
+    }
+}
+
+// leader i's best commitment given the other leaders' strategies, anticipating the followers' response;
+// always uses Nelder-Mead since the followers' response is not differentiable in general
+fn leader_response<A>(
+    i: usize,
+    strat: &Strategies<A>,
+    followers: &[usize],
+    agg: &dyn Aggregator<A>,
+    options: &SolverOptions<A>,
+    unconverged: &AtomicUsize,
+) -> Result<Array<f64, Ix2>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let obj = LeaderObjective { agg, i, followers, base_strategies: strat, options, unconverged };
+    let init_simplex = create_simplex(strat.data().slice(s![.., i, ..]), options.nm_options.init_simplex_size);
+    let solver = NelderMead::new(init_simplex).with_sd_tolerance(options.nm_options.tol)?;
+    let res = Executor::new(obj, solver)
+        .configure(|state| state.max_iters(options.nm_options.iters))
+        .run()?;
+    Ok(Array::from_shape_vec(
+        (strat.t(), A::nparams()),
+        res.state.best_param.unwrap().iter().map(from_log).collect(),
+    )?)
+}
+
+// solves for a stackelberg equilibrium where the leaders commit to their strategies first
+// and the remaining players play a nash equilibrium in response;
+// with several leaders, leaders play a nash equilibrium among themselves, each anticipating the followers' response
+pub fn solve_stackelberg<A>(
+    agg: &dyn Aggregator<A>,
+    leaders: &[usize],
+    options: &SolverOptions<A>,
+) -> Result<StackelbergResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    with_threads(options.threads, || solve_stackelberg_pure(agg, leaders, options))
+}
+
+fn solve_stackelberg_pure<A>(
+    agg: &dyn Aggregator<A>,
+    leaders: &[usize],
+    options: &SolverOptions<A>,
+) -> Result<StackelbergResult<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    if leaders.is_empty() {
+        return Err(DynapaiError::InvalidParameter("must have at least one leader".to_string()));
+    }
+    if let Some(i) = leaders.iter().find(|&&i| i >= agg.n()) {
+        return Err(DynapaiError::InvalidParameter(format!("leader {} out of range for {} players", i, agg.n())));
+    }
+    if (1..leaders.len()).any(|k| leaders[..k].contains(&leaders[k])) {
+        return Err(DynapaiError::InvalidParameter("leaders must be distinct".to_string()));
+    }
+    let followers = (0..agg.n()).filter(|i| !leaders.contains(i)).collect::<Vec<_>>();
+    let unconverged = AtomicUsize::new(0);
+
+    let mut strat = options.init_guess.to_fixed(agg.n())?;
+    if !follower_response(&mut strat, &followers, agg, options)? {
+        unconverged.fetch_add(1, Ordering::Relaxed);
+    }
+    let leader_u = |strat: &Strategies<A>| leaders.iter().map(|&i| agg.u_i(i, strat)).collect::<Vec<_>>();
+    let mut last_payoffs = leader_u(&strat);
+    let mut status = format!("Reached max iterations ({})", options.iters);
+    for iter in 0..options.iters {
+        match options.update {
+            UpdateMode::Jacobi => {
+                let new_data = map_range(leaders.len(), options.parallel, |k| {
+                    leader_response(leaders[k], &strat, &followers, agg, options, &unconverged)
+                }).into_iter().collect::<Result<Vec<_>, _>>()?;
+                for (&i, x) in leaders.iter().zip(new_data) {
+                    let x = damp(strat.data().slice(s![.., i, ..]), x, options.damping);
+                    strat.set_i(i, x);
+                }
+            },
+            UpdateMode::GaussSeidel => {
+                for &i in leaders {
+                    let x = leader_response(i, &strat, &followers, agg, options, &unconverged)?;
+                    let x = damp(strat.data().slice(s![.., i, ..]), x, options.damping);
+                    strat.set_i(i, x);
+                }
+            },
+        }
+        if !follower_response(&mut strat, &followers, agg, options)? {
+            unconverged.fetch_add(1, Ordering::Relaxed);
+        }
+        let new_payoffs = leader_u(&strat);
+        options.progress(iter, || agg.u(&strat))?;
+        if isapprox_iters(
+            new_payoffs.iter().cloned(), last_payoffs.into_iter(),
+            options.tol, f64::EPSILON.sqrt()
+        ) {
+            status = format!("Exited on iteration {}", iter);
+            break;
+        }
+        last_payoffs = new_payoffs;
+    }
+    let payoffs = agg.u(&strat);
+    check_finite(payoffs.iter(), "At Stackelberg solution")?;
+    Ok(StackelbergResult {
+        status,
+        payoffs,
+        strategies: strat,
+        leaders: leaders.to_vec(),
+        unconverged: unconverged.into_inner(),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn leader_does_at_least_as_well_as_in_nash() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let options = SolverOptions { tol: 1e-8, ..solver_options(1) };
+        let nash = solve(&agg, &options).unwrap().strategies.unwrap();
+        let res = solve_stackelberg(&agg, &[0], &options).unwrap();
+        assert_eq!(res.unconverged, 0);
+        // committing to the nash strategy is always an option for the leader
+        assert!(res.payoffs[0] >= agg.u_i(0, &nash) - 1e-6);
+        // the follower best responds to the leader's commitment
+        let verification = verify(&agg, &res.strategies, &options, &VerifyOptions::default()).unwrap();
+        assert!(verification.gains[1] < 1e-6, "{}", verification.gains);
+
+        assert!(solve_stackelberg(&agg, &[], &options).is_err());
+        assert!(solve_stackelberg(&agg, &[2], &options).is_err());
+        assert!(solve_stackelberg(&agg, &[0, 0], &options).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use downcast_rs::{Downcast, impl_downcast};
 use dyn_clone::{DynClone, clone_trait_object};
+use ndarray::{Array, ArrayView, Ix1, Ix2, s};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
 
 use crate::prelude::*;
 
@@ -7,6 +9,12 @@ use crate::prelude::*;
 pub trait State<A: ActionType>: DynClone + Downcast + MutatesOn<A> + Send + Sync {
     fn n(&self) -> usize;
     fn belief(&self, i: usize) -> &Box<dyn PayoffFunc<A>>;
+    // jacobians of player i's belief state after mutate_on,
+    // wrt the current state and wrt player i's actions
+    fn dmutate_on(&self, i: usize, actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>);
+    // the beliefs' states, concatenated into a flat vector, and a way to overwrite them
+    fn state_vec(&self) -> Array<f64, Ix1>;
+    fn set_state_vec(&mut self, x: ArrayView<f64, Ix1>);
 }
 
 clone_trait_object!(<A> State<A> where A: ActionType);
@@ -28,6 +36,15 @@ impl<A: ActionType + Clone + 'static> State<A> for CommonBeliefs<A> {
     fn belief(&self, _i: usize) -> &Box<dyn PayoffFunc<A>> {
         &self.0
     }
+    fn dmutate_on(&self, i: usize, actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        self.0.dmutate_on(i, actions)
+    }
+    fn state_vec(&self) -> Array<f64, Ix1> {
+        self.0.state()
+    }
+    fn set_state_vec(&mut self, x: ArrayView<f64, Ix1>) {
+        self.0.set_state(x)
+    }
 } 
 
 
@@ -38,12 +55,12 @@ pub struct HetBeliefs<A: ActionType> {
 }
 
 impl<A: ActionType + 'static> HetBeliefs<A> {
-    pub fn new(beliefs: Vec<Box<dyn PayoffFunc<A>>>) -> Result<HetBeliefs<A>, &'static str> {
+    pub fn new(beliefs: Vec<Box<dyn PayoffFunc<A>>>) -> Result<HetBeliefs<A>, DynapaiError> {
         if beliefs.len() == 0 {
-            return Err("When creating new HetBeliefs: beliefs must have length > 0");
+            return Err(DynapaiError::DimensionMismatch("When creating new HetBeliefs: beliefs must have length > 0".to_string()));
         }
         if beliefs.iter().any(|b| b.n() != beliefs.len()) {
-            return Err("When creating new HetBeliefs: All beliefs must have the same n, matching length of beliefs");
+            return Err(DynapaiError::DimensionMismatch("When creating new HetBeliefs: All beliefs must have the same n, matching length of beliefs".to_string()));
         }
         Ok(HetBeliefs { n: beliefs.len(), beliefs })
     }
@@ -51,8 +68,8 @@ impl<A: ActionType + 'static> HetBeliefs<A> {
 
 impl<A: ActionType> MutatesOn<A> for HetBeliefs<A> {
     fn mutate_on(&mut self, actions: &A) {
-        for i in 1..self.n {
-            self.beliefs[i].mutate_on(actions);
+        for belief in self.beliefs.iter_mut() {
+            belief.mutate_on(actions);
         }
     }
 }
@@ -64,12 +81,29 @@ impl<A: ActionType + Clone + 'static> State<A> for HetBeliefs<A> {
     fn belief(&self, i: usize) -> &Box<dyn PayoffFunc<A>> {
         &self.beliefs[i]
     }
+    fn dmutate_on(&self, i: usize, actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>) {
+        self.beliefs[i].dmutate_on(i, actions)
+    }
+    fn state_vec(&self) -> Array<f64, Ix1> {
+        Array::from_iter(self.beliefs.iter().flat_map(|b| b.state().into_iter()))
+    }
+    fn set_state_vec(&mut self, x: ArrayView<f64, Ix1>) {
+        let mut start = 0;
+        for belief in self.beliefs.iter_mut() {
+            let end = start + belief.state_dim();
+            belief.set_state(x.slice(s![start..end]));
+            start = end;
+        }
+    }
 }
 
 pub trait StateIterator<A: ActionType>: DynClone + Send + Sync
 {
     fn state0(&self) -> &Box<dyn State<A>>;
     fn advance_state(&self, _state: &mut Box<dyn State<A>>, _actions: &A) {}
+    // jacobians of player i's belief state after advance_state,
+    // wrt the current state and wrt player i's actions
+    fn dadvance_state(&self, state: &dyn State<A>, i: usize, actions: &A) -> (Array<f64, Ix2>, Array<f64, Ix2>);
 }
 
 impl<A: ActionType> Clone for Box<dyn StateIterator<A>>
@@ -78,3 +112,72 @@ impl<A: ActionType> Clone for Box<dyn StateIterator<A>>
         dyn_clone::clone_box(&**self)
     }
 }
+
+
+#[derive(Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    bound(
+        serialize = "Box<dyn PayoffFunc<A>>: Serialize",
+        deserialize = "Box<dyn PayoffFunc<A>>: Deserialize<'de>",
+    ),
+)]
+enum StateSpec<A: ActionType + 'static> {
+    CommonBeliefs { belief: Box<dyn PayoffFunc<A>> },
+    HetBeliefs { beliefs: Vec<Box<dyn PayoffFunc<A>>> },
+}
+
+impl<A: ActionType + Clone + 'static> Serialize for Box<dyn State<A>>
+where Box<dyn PayoffFunc<A>>: Serialize
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let spec = if let Some(x) = self.downcast_ref::<CommonBeliefs<A>>() {
+            StateSpec::CommonBeliefs { belief: x.0.clone() }
+        }
+        else if let Some(x) = self.downcast_ref::<HetBeliefs<A>>() {
+            StateSpec::HetBeliefs { beliefs: x.beliefs.clone() }
+        }
+        else {
+            return Err(ser::Error::custom("Serialization not implemented for this State"));
+        };
+        spec.serialize(serializer)
+    }
+}
+
+impl<'de, A: ActionType + Clone + 'static> Deserialize<'de> for Box<dyn State<A>>
+where Box<dyn PayoffFunc<A>>: Deserialize<'de>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match StateSpec::deserialize(deserializer)? {
+            StateSpec::CommonBeliefs { belief } => Box::new(CommonBeliefs(belief)),
+            StateSpec::HetBeliefs { beliefs } => Box::new(HetBeliefs::new(beliefs).map_err(de::Error::custom)?),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn het_beliefs_mutate_every_belief() {
+        let mut other = invest_payoff(Box::new(DefaultCSF));
+        other.set_state(array![0.8, 1.2, 1., 1.].view());
+        let beliefs: Vec<Box<dyn PayoffFunc<InvestActions>>> = vec![
+            Box::new(invest_payoff(Box::new(DefaultCSF))), Box::new(other),
+        ];
+        let mut state = HetBeliefs::new(beliefs.clone()).unwrap();
+        let actions = strategies::<InvestActions>(1, 2).into_actions().remove(0);
+        state.mutate_on(&actions);
+        for (i, mut belief) in beliefs.into_iter().enumerate() {
+            let before = belief.state();
+            belief.mutate_on(&actions);
+            assert_close(&state.belief(i).state(), &belief.state(), 1e-12);
+            assert!(state.belief(i).state() != before);
+        }
+    }
+}
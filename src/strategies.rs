@@ -1,7 +1,12 @@
 use std::fmt;
-use numpy::ndarray::{Array, ArrayView, Ix1, Ix2, Ix3, s};
+use ndarray::{Array, ArrayView, Ix1, Ix2, Ix3, s};
 use dyn_clone::{DynClone, clone_trait_object};
-use ndarray_rand::{RandomExt, rand_distr::LogNormal};
+use ndarray_rand::{RandomExt, rand::Rng, rand_distr::LogNormal};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::error::DynapaiError;
+use crate::serialize::{from_rows, to_rows};
+use crate::utils::{INIT_STREAM, seeded_rng};
 
 
 pub trait ActionType: DynClone + Send + Sync {
@@ -9,8 +14,10 @@ pub trait ActionType: DynClone + Send + Sync {
     fn data_mut(&mut self) -> &mut Array<f64, Ix2>;
     fn n(&self) -> usize { self.data().shape()[0] }
 
-    fn from_array(data: Array<f64, Ix2>) -> Result<Self, String> where Self: Sized;
+    fn from_array(data: Array<f64, Ix2>) -> Result<Self, DynapaiError> where Self: Sized;
     fn nparams() -> usize where Self: Sized;
+    // name used for the action type in model files and saved results
+    fn atype() -> &'static str where Self: Sized;
 
     fn xs(&self) -> ArrayView<f64, Ix1> { self.data().slice(s![.., 0]) }
     fn xp(&self) -> ArrayView<f64, Ix1> { self.data().slice(s![.., 1]) }
@@ -19,7 +26,7 @@ pub trait ActionType: DynClone + Send + Sync {
 clone_trait_object!(ActionType);
 
 macro_rules! def_action_type {
-    ($name:ident, $n:expr) => {
+    ($name:ident, $atype:expr, $n:expr) => {
         #[derive(Clone)]
         pub struct $name(Array<f64, Ix2>);
 
@@ -27,14 +34,15 @@ macro_rules! def_action_type {
             fn data(&self) -> &Array<f64, Ix2> { &self.0 }
             fn data_mut(&mut self) -> &mut Array<f64, Ix2> { &mut self.0 }
 
-            fn from_array(data: Array<f64, Ix2>) -> Result<Self, String> {
+            fn from_array(data: Array<f64, Ix2>) -> Result<Self, DynapaiError> {
                 if data.shape()[1] != $n {
-                    return Err(concat!("When creating new ", stringify!($name), ": Input array must have ", stringify!($n), " columns").to_string());
+                    return Err(DynapaiError::DimensionMismatch(concat!("When creating new ", stringify!($name), ": Input array must have ", stringify!($n), " columns").to_string()));
                 }
                 Ok(Self(data))
             }
 
             fn nparams() -> usize { $n }
+            fn atype() -> &'static str { $atype }
         }
     }
 }
@@ -59,7 +67,7 @@ clone_trait_object!(InvestSharingActionType);
 impl<A: InvestActionType + SharingActionType> InvestSharingActionType for A {}
 
 
-def_action_type!(Actions, 2);
+def_action_type!(Actions, "basic", 2);
 
 impl fmt::Display for Actions {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -68,7 +76,7 @@ impl fmt::Display for Actions {
 }
 
 
-def_action_type!(InvestActions, 4);
+def_action_type!(InvestActions, "invest", 4);
 
 impl InvestActionType for InvestActions {
     fn inv_s(&self) -> ArrayView<f64, Ix1> { self.data().slice(s![.., 2]) }
@@ -82,7 +90,7 @@ impl fmt::Display for InvestActions {
 }
 
 
-def_action_type!(SharingActions, 6);
+def_action_type!(SharingActions, "sharing", 6);
 
 impl InvestActionType for SharingActions {
     fn inv_s(&self) -> ArrayView<f64, Ix1> { self.data().slice(s![.., 2]) }
@@ -136,19 +144,105 @@ impl<A: ActionType> Strategies<A> {
         assert!(actions.iter().all(|a| a.n() == actions[0].n()));
         Self(actions)
     }
-    pub fn random(t: usize, n: usize, mu: f64, sigma: f64) -> Result<Self, String> {
+    // inverse of data, from an array of shape (t, n, nparams)
+    pub fn from_data(data: Array<f64, Ix3>) -> Result<Self, DynapaiError> {
+        if data.shape()[0] == 0 {
+            return Err(DynapaiError::DimensionMismatch("Strategies must have at least one period".to_string()));
+        }
+        data.outer_iter().map(|x| A::from_array(x.to_owned())).collect::<Result<Vec<_>, _>>().map(Self)
+    }
+    pub fn random(t: usize, n: usize, mu: f64, sigma: f64) -> Result<Self, DynapaiError> {
+        Self::random_using(t, n, mu, sigma, &mut ndarray_rand::rand::thread_rng())
+    }
+    pub fn random_using<R: Rng>(t: usize, n: usize, mu: f64, sigma: f64, rng: &mut R) -> Result<Self, DynapaiError> {
         let dist = match LogNormal::new(mu, sigma) {
             Ok(d) => d,
-            Err(e) => return Err(format!("Error when creating LogNormal distribution: {}", e))
+            Err(e) => return Err(DynapaiError::InvalidParameter(format!("Error when creating LogNormal distribution: {}", e)))
         };
         let mut data = Vec::<A>::with_capacity(t);
         for _t in 0..t {
-            data.push(A::from_array(Array::random((n, A::nparams()), dist))?);
+            data.push(A::from_array(Array::random_using((n, A::nparams()), dist, rng))?);
         }
         Ok(Self(data))
     }
+    // each player's strategy is drawn from their own stream of seed,
+    // so it doesn't depend on the number of players
+    pub fn random_seeded(t: usize, n: usize, mu: f64, sigma: f64, seed: Option<u64>) -> Result<Self, DynapaiError> {
+        Self::random_in_stream(t, n, mu, sigma, seed, &[INIT_STREAM])
+    }
+    // like random_seeded, with player i drawing from stream + [i]
+    pub(crate) fn random_in_stream(
+        t: usize, n: usize, mu: f64, sigma: f64, seed: Option<u64>, stream: &[u64],
+    ) -> Result<Self, DynapaiError> {
+        let dist = match LogNormal::new(mu, sigma) {
+            Ok(d) => d,
+            Err(e) => return Err(DynapaiError::InvalidParameter(format!("Error when creating LogNormal distribution: {}", e)))
+        };
+        let mut data = Array::zeros((t, n, A::nparams()));
+        for i in 0..n {
+            let stream = stream.iter().cloned().chain([i as u64]).collect::<Vec<_>>();
+            let mut rng = seeded_rng(seed, &stream);
+            data.slice_mut(s![.., i, ..]).assign(&Array::random_using((t, A::nparams()), dist, &mut rng));
+        }
+        Self::from_data(data)
+    }
 }
 
 pub trait MutatesOn<A> {
     fn mutate_on(&mut self, _actions: &A) {}
 }
+
+
+// written as nested lists of shape (t, n, nparams)
+impl<A: ActionType> Serialize for Strategies<A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.iter().map(|a| to_rows(a.data())).collect::<Vec<_>>().serialize(serializer)
+    }
+}
+
+impl<'de, A: ActionType> Deserialize<'de> for Strategies<A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<Vec<f64>>>::deserialize(deserializer)?;
+        if rows.is_empty() {
+            return Err(de::Error::custom("Strategies must have at least one period"));
+        }
+        let actions = rows.into_iter()
+            .map(|x| from_rows(x).and_then(A::from_array))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(de::Error::custom)?;
+        if actions.iter().any(|a| a.n() != actions[0].n()) {
+            return Err(de::Error::custom("All periods must have the same number of players"));
+        }
+        Ok(Self(actions))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_strategies_are_reproducible() {
+        let a = Strategies::<Actions>::random_seeded(2, 3, -1., 0.5, Some(7)).unwrap();
+        let b = Strategies::<Actions>::random_seeded(2, 3, -1., 0.5, Some(7)).unwrap();
+        let c = Strategies::<Actions>::random_seeded(2, 3, -1., 0.5, Some(8)).unwrap();
+        assert_eq!(a.data(), b.data());
+        assert_ne!(a.data(), c.data());
+    }
+
+    #[test]
+    fn player_streams_do_not_depend_on_n() {
+        let small = Strategies::<Actions>::random_seeded(2, 2, -1., 0.5, Some(7)).unwrap();
+        let large = Strategies::<Actions>::random_seeded(2, 4, -1., 0.5, Some(7)).unwrap();
+        assert_eq!(small.data(), large.data().slice(s![.., ..2, ..]));
+        assert_ne!(large.data().slice(s![.., 0, ..]), large.data().slice(s![.., 1, ..]));
+    }
+
+    #[test]
+    fn init_distribution_is_configurable() {
+        let tight = Strategies::<Actions>::random_seeded(1, 50, 2., 1e-6, Some(0)).unwrap();
+        assert!(tight.data().iter().all(|x| (x.ln() - 2.).abs() < 1e-4));
+        assert!(Strategies::<Actions>::random_seeded(1, 2, 0., f64::NAN, Some(0)).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+// fixtures and finite-difference checks shared by the unit tests
+use std::fs;
+use std::path::PathBuf;
+
+use ndarray::array;
+
+use crate::prelude::*;
+// the same central differences as used for components without analytic gradients
+pub use crate::utils::fd_grad;
+
+
+// a 2-player model whose players differ, so that mixing up i and j shows up in the tests
+pub fn modular_payoff<A>(csf: Box<dyn CSF>, cost_func: Box<dyn CostFunc<A>>) -> ModularPayoff<A>
+where A: ActionType + Clone + 'static, DefaultProd: ProdFunc<A>
+{
+    ModularPayoff::new(
+        Box::new(DefaultProd::new(array![1., 1.5], array![0.5, 0.6], array![1.2, 0.8], array![0.4, 0.5]).unwrap()),
+        Box::new(WinnerOnlyRisk { theta: array![0.5, 0.7] }),
+        csf,
+        Box::new(LinearReward::new(array![1., 1.2], array![0.1, 0.], array![0.2, 0.1], array![0., 0.05]).unwrap()),
+        Box::new(ConstantDisasterCost { d: array![1., 0.8] }),
+        cost_func,
+    ).unwrap()
+}
+
+pub fn basic_payoff(csf: Box<dyn CSF>) -> ModularPayoff<Actions> {
+    modular_payoff(csf, Box::new(BasicFixedCost::new(array![[0.1, 0.1], [0.12, 0.08]]).unwrap()))
+}
+
+pub fn invest_payoff(csf: Box<dyn CSF>) -> ModularPayoff<InvestActions> {
+    modular_payoff(csf, Box::new(InvestFixedCost::new(array![[0.1, 0.1, 0.2, 0.2], [0.12, 0.08, 0.2, 0.3]]).unwrap()))
+}
+
+pub fn sharing_payoff(csf: Box<dyn CSF>) -> ModularPayoff<SharingActions> {
+    modular_payoff(csf, Box::new(SharingFixedCost::new(array![[0.1, 0.1, 0.2, 0.2, 0.1, 0.1], [0.12, 0.08, 0.2, 0.3, 0.05, 0.1]]).unwrap()))
+}
+
+// deterministic strategies with positive entries
+pub fn strategies<A: ActionType>(t: usize, n: usize) -> Strategies<A> {
+    Strategies::random_seeded(t, n, -1., 0.5, Some(0)).unwrap()
+}
+
+// central difference approximation of the gradient of f at x
+pub fn assert_close<'a, I, J>(a: I, b: J, tol: f64)
+where I: IntoIterator<Item = &'a f64>, J: IntoIterator<Item = &'a f64>
+{
+    let a = a.into_iter().cloned().collect::<Vec<_>>();
+    let b = b.into_iter().cloned().collect::<Vec<_>>();
+    assert_eq!(a.len(), b.len());
+    assert!(isapprox_iters(a.iter().cloned(), b.iter().cloned(), tol, tol), "{:?} != {:?}", a, b);
+}
+
+pub fn basic_agg(csf: Box<dyn CSF>) -> FixedStateDiscounter<Actions> {
+    FixedStateDiscounter::new(Box::new(CommonBeliefs(Box::new(basic_payoff(csf)))), array![0.9, 0.9]).unwrap()
+}
+
+pub fn invest_agg(csf: Box<dyn CSF>) -> DynStateDiscounter<InvestActions> {
+    DynStateDiscounter::new(Box::new(CommonBeliefs(Box::new(invest_payoff(csf)))), array![0.9, 0.8]).unwrap()
+}
+
+pub fn solver_options<A: ActionType + Clone>(t: usize) -> SolverOptions<A> {
+    SolverOptions {
+        init_guess: InitGuess::random(t, Some(0)),
+        iters: 200,
+        tol: 1e-10,
+        method: SolverMethod::LBFGS,
+        update: UpdateMode::Jacobi,
+        damping: 1.,
+        nm_options: NMOptions::default(),
+        lbfgs_options: LBFGSOptions::default(),
+        trace: false,
+        verify: None,
+        hist_size: 10,
+        mixed_samples: 10,
+        mixed_tol: MIXED_TOL,
+        parallel: false,
+        threads: None,
+        seed: Some(0),
+        callback: None,
+    }
+}
+
+// empty directory under the system temp dir, unique to this process and name
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dynapai-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -1,3 +1,9 @@
+use ndarray::{Array, Array1, ArrayView1};
+use ndarray_rand::rand::{SeedableRng, rngs::StdRng};
+use rayon::prelude::*;
+
+use crate::error::DynapaiError;
+
 pub fn isapprox(a: f64, b: f64, rtol: f64, atol: f64) -> bool
 {
     let maxval = f64::max(a.abs(), b.abs());
@@ -10,6 +16,73 @@ where I: Iterator<Item = f64>, J: Iterator<Item = f64>
     a.zip(b).all(|(a, b)| isapprox(a, b, rtol, atol))
 }
 
+// maps f over 0..n, in parallel if parallel is true
+pub fn map_range<T, F>(n: usize, parallel: bool, f: F) -> Vec<T>
+where T: Send, F: Fn(usize) -> T + Send + Sync
+{
+    if parallel {
+        (0..n).into_par_iter().map(f).collect()
+    }
+    else {
+        (0..n).map(f).collect()
+    }
+}
+
+// runs f in a dedicated thread pool with the given number of threads;
+// if already running inside a thread pool, f just runs in that pool
+pub fn with_threads<T, F>(threads: Option<usize>, f: F) -> Result<T, DynapaiError>
+where T: Send, F: FnOnce() -> Result<T, DynapaiError> + Send
+{
+    match threads {
+        Some(threads) if rayon::current_thread_index().is_none() => {
+            rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+                .map_err(|e| DynapaiError::SolverFailure(format!("Error when creating thread pool: {}", e)))?
+                .install(f)
+        },
+        _ => f(),
+    }
+}
+
+// independent rng streams derived from a single seed, so that draws don't depend on
+// the order in which threads run; each stream is named by a path starting with one of these
+pub(crate) const INIT_STREAM: u64 = 0;
+pub(crate) const VERIFY_STREAM: u64 = 1;
+pub(crate) const MIXED_STREAM: u64 = 2;
+pub(crate) const MULTISTART_STREAM: u64 = 3;
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// rng for the given stream, e.g. [VERIFY_STREAM, i, k];
+// seeded from entropy if seed is None, in which case the stream doesn't matter
+pub fn seeded_rng(seed: Option<u64>, stream: &[u64]) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(stream.iter().fold(seed, |acc, k| splitmix64(acc ^ splitmix64(*k)))),
+        None => StdRng::from_entropy(),
+    }
+}
+
+// step for central differences, relative to x once |x| > 1
+pub const FD_STEP: f64 = 1e-6;
+
+// central difference gradient of f at x, for functions without analytic gradients and for checking those that have them
+pub fn fd_grad<F: FnMut(ArrayView1<f64>) -> f64>(x: ArrayView1<f64>, mut f: F) -> Array1<f64> {
+    let mut x_ = x.to_owned();
+    Array::from_iter((0..x.len()).map(|k| {
+        let h = FD_STEP * f64::max(1., x[k].abs());
+        x_[k] = x[k] + h;
+        let up = f(x_.view());
+        x_[k] = x[k] - h;
+        let down = f(x_.view());
+        x_[k] = x[k];
+        (up - down) / (2. * h)
+    }))
+}
+
 pub fn positive_bound(x: f64) -> f64
 {
     1. - f64::exp(-x)
@@ -40,3 +113,29 @@ macro_rules! init_rep {
         }
     };
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_pool_has_requested_size() {
+        let serial = map_range(8, false, |k| k * k);
+        let parallel = with_threads(Some(2), || {
+            assert_eq!(rayon::current_num_threads(), 2);
+            Ok(map_range(8, true, |k| k * k))
+        }).unwrap();
+        assert_eq!(serial, parallel);
+        assert!(with_threads(Some(0), || Ok(rayon::current_num_threads())).unwrap() > 0);
+    }
+
+    #[test]
+    fn fd_step_does_not_vanish_at_zero() {
+        let grad = fd_grad(ndarray::array![0.].view(), |x| x[0].exp());
+        assert!(isapprox(grad[0], 1., 1e-8, 0.));
+        // and is relative to x away from zero
+        let grad = fd_grad(ndarray::array![1e3].view(), |x| x[0].powi(3));
+        assert!(isapprox(grad[0], 3e6, 1e-8, 0.));
+    }
+}
@@ -0,0 +1,130 @@
+use ndarray::{Array, Ix1, Ix2, s};
+use ndarray_rand::{RandomExt, rand_distr::LogNormal};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerifyOptions {
+    // number of starting points for each player's deviation search,
+    // the first of which is always the player's own strategy
+    pub starts: usize,
+    // other starts perturb the player's strategy by a lognormal factor with this sigma
+    pub spread: f64,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions {
+            starts: 4,
+            spread: 1.0,
+        }
+    }
+}
+
+// result of checking whether a set of strategies is an (epsilon-)Nash equilibrium
+#[derive(Clone)]
+pub struct Verification<A: ActionType> {
+    pub payoffs: Array<f64, Ix1>,
+    // largest gain each player can get by deviating unilaterally
+    pub gains: Array<f64, Ix1>,
+    // best deviation found for each player, shape (t, nparams)
+    pub deviations: Vec<Array<f64, Ix2>>,
+    _phantom: std::marker::PhantomData<A>,
+}
+
+impl<A: ActionType + Clone> Verification<A> {
+    pub fn epsilon(&self) -> f64 {
+        self.gains.iter().cloned().fold(0., f64::max)
+    }
+
+    // strategies where player i plays their best deviation
+    pub fn deviation(&self, i: usize, strategies: &Strategies<A>) -> Strategies<A> {
+        let mut out = strategies.clone();
+        out.set_i(i, self.deviations[i].clone());
+        out
+    }
+}
+
+// each (i, k) draws from its own stream of seed
+fn deviation_start<A: ActionType + Clone>(
+    i: usize, k: usize, strategies: &Strategies<A>, spread: f64, seed: Option<u64>,
+) -> Result<Strategies<A>, DynapaiError> {
+    let mut start = strategies.clone();
+    if k == 0 {
+        return Ok(start);
+    }
+    let dist = match LogNormal::new(0., spread) {
+        Ok(d) => d,
+        Err(e) => return Err(DynapaiError::InvalidParameter(format!("Error when creating LogNormal distribution: {}", e))),
+    };
+    let own = strategies.data().slice(s![.., i, ..]).to_owned();
+    let mut rng = seeded_rng(seed, &[VERIFY_STREAM, i as u64, k as u64]);
+    start.set_i(i, &own * &Array::random_using(own.dim(), dist, &mut rng));
+    Ok(start)
+}
+
+pub fn verify<A>(
+    agg: &dyn Aggregator<A>,
+    strategies: &Strategies<A>,
+    options: &SolverOptions<A>,
+    verify_options: &VerifyOptions,
+) -> Result<Verification<A>, DynapaiError>
+where A: ActionType + Clone + 'static
+{
+    let payoffs = agg.u(strategies);
+    let searches = (0..strategies.n()).flat_map(|i|
+        (0..verify_options.starts.max(1)).map(move |k| (i, k))
+    ).collect::<Vec<_>>();
+    let results = with_threads(options.threads, || map_range(searches.len(), options.parallel, |j| {
+        let (i, k) = searches[j];
+        let start = deviation_start(i, k, strategies, verify_options.spread, options.seed)?;
+        let (x, _) = solve_for_i(i, &start, agg, options)?;
+        let mut deviated = strategies.clone();
+        deviated.set_i(i, x.clone());
+        Ok((i, agg.u_i(i, &deviated), x))
+    }).into_iter().collect::<Result<Vec<_>, DynapaiError>>())?;
+
+    // the player's own strategy is the baseline deviation
+    let mut best = (0..strategies.n()).map(|i|
+        (payoffs[i], strategies.data().slice(s![.., i, ..]).to_owned())
+    ).collect::<Vec<_>>();
+    for (i, u, x) in results {
+        if u > best[i].0 {
+            best[i] = (u, x);
+        }
+    }
+    Ok(Verification {
+        gains: Array::from_iter(best.iter().zip(payoffs.iter()).map(|((u, _), u0)| u - u0)),
+        payoffs,
+        deviations: best.into_iter().map(|(_, x)| x).collect(),
+        _phantom: std::marker::PhantomData,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn equilibrium_has_small_epsilon() {
+        let agg = basic_agg(Box::new(DefaultCSF));
+        let options = solver_options(1);
+        let strategies = solve(&agg, &options).unwrap().strategies.unwrap();
+        let verification = verify(&agg, &strategies, &options, &VerifyOptions::default()).unwrap();
+        assert!(verification.epsilon() < 1e-6, "{}", verification.epsilon());
+        assert_close(&verification.payoffs, &agg.u(&strategies), 1e-12);
+
+        // doubling player 0's actions moves it away from its best response
+        let mut off = strategies.clone();
+        off.set_i(0, 2. * &strategies.data().slice(s![.., 0, ..]));
+        let verification = verify(&agg, &off, &options, &VerifyOptions::default()).unwrap();
+        assert!(verification.gains[0] > 1e-4, "{}", verification.gains);
+        let deviated = verification.deviation(0, &off);
+        assert_close([&agg.u_i(0, &deviated)], [&(verification.payoffs[0] + verification.gains[0])], 1e-12);
+    }
+}
@@ -0,0 +1,252 @@
+# tests of the python bindings, run with `python -m unittest discover tests` after `maturin develop`;
+# tests that pass or return numpy arrays are skipped when numpy isn't installed
+import importlib.util
+import json
+import pickle
+import threading
+import unittest
+
+import dynapai as dp
+
+HAS_NUMPY = importlib.util.find_spec("numpy") is not None
+
+
+def model():
+    payoff = {
+        "type": "ModularPayoff",
+        "prod_func": {"type": "DefaultProd", "a": [1.0, 1.5], "alpha": [0.5, 0.6], "b": [1.2, 0.8], "beta": [0.4, 0.5]},
+        "risk_func": {"type": "WinnerOnlyRisk", "theta": [0.5, 0.5]},
+        "csf": {"type": "DefaultCSF"},
+        "reward_func": {"type": "LinearReward", "win_a": [1.0, 1.0], "win_b": [0.0, 0.0], "lose_a": [0.0, 0.0], "lose_b": [0.0, 0.0]},
+        "disaster_cost": {"type": "ConstantDisasterCost", "d": [1.0, 1.0]},
+        "cost_func": {"type": "FixedCost", "r": [[0.1, 0.1], [0.12, 0.08]]},
+    }
+    return dp.Aggregator.from_json(json.dumps({
+        "type": "FixedStateDiscounter",
+        "state": {"type": "CommonBeliefs", "belief": payoff},
+        "gammas": [0.9, 0.9],
+    }))
+
+
+def payoff(csf=None, reward_func=None):
+    return dp.PayoffFunc(
+        dp.ProdFunc([1.0, 1.5], [0.5, 0.6], [1.2, 0.8], [0.4, 0.5]),
+        dp.RiskFunc.winner_only([0.5, 0.5]),
+        [1.0, 1.0],
+        dp.CostFunc.fixed_basic([0.1, 0.12]),
+        csf=csf,
+        reward_func=reward_func,
+    )
+
+
+ACTIONS = [dp.Actions([1.0, 2.0], [0.5, 1.5])]
+
+
+class TestSolverOptions(unittest.TestCase):
+    def test_options_are_keyword_only(self):
+        options = dp.SolverOptions(iters=5, method="lbfgs", seed=3, threads=2)
+        self.assertIn("iters = 5", str(options))
+        self.assertIn("method = lbfgs", str(options))
+        self.assertEqual(dp.SolverOptions(), dp.SolverOptions(iters=200))
+        with self.assertRaises(TypeError):
+            dp.SolverOptions(5)
+        with self.assertRaises(TypeError):
+            dp.SolverOptions(itres=5)
+
+    def test_invalid_options_raise(self):
+        with self.assertRaises(dp.InvalidParameterError):
+            dp.SolverOptions(damping=2.0)
+        with self.assertRaises(dp.InvalidParameterError):
+            dp.SolverOptions(method="newton")
+        # names match the ones used in model files
+        with self.assertRaises(dp.InvalidParameterError):
+            dp.SolverOptions(method="LBFGS")
+        self.assertIn("update = gauss_seidel", str(dp.SolverOptions(update="gauss_seidel")))
+
+
+class TestCancellation(unittest.TestCase):
+    def test_cancelled_token_stops_the_solve(self):
+        token = dp.CancelToken()
+        token.cancel()
+        with self.assertRaises(dp.CancelledError):
+            model().solve(t=1, options=dp.SolverOptions(seed=0, cancel=token))
+        token.reset()
+        self.assertFalse(token.cancelled)
+
+    @unittest.skipUnless(HAS_NUMPY, "needs numpy")
+    def test_progress_is_called_every_iteration(self):
+        iters = []
+        model().solve(t=1, options=dp.SolverOptions(iters=3, tol=0.0, seed=0, progress=lambda k, u: iters.append((k, len(u)))))
+        self.assertEqual(iters, [(0, 2), (1, 2), (2, 2)])
+
+    @unittest.skipUnless(HAS_NUMPY, "needs numpy")
+    def test_progress_errors_are_raised(self):
+        def progress(k, u):
+            raise RuntimeError("stop")
+        with self.assertRaises(RuntimeError):
+            model().solve(t=1, options=dp.SolverOptions(seed=0, progress=progress))
+
+
+class TestCallables(unittest.TestCase):
+    def test_non_callables_are_rejected(self):
+        with self.assertRaises(TypeError):
+            dp.CSF.from_callable(1.0)
+        with self.assertRaises(TypeError):
+            dp.RiskFunc.from_callable(object(), 2)
+
+    def test_callables_cannot_be_pickled(self):
+        with self.assertRaises(dp.SerializationError):
+            pickle.dumps(dp.CSF.from_callable(lambda i, p: p[i] / p.sum()))
+
+    @unittest.skipUnless(HAS_NUMPY, "needs numpy")
+    def test_callables_match_builtins(self):
+        builtin = dp.Aggregator(payoff(dp.CSF.tullock(1.0), dp.RewardFunc.default(2)), [0.9, 0.9])
+        callable_ = dp.Aggregator(payoff(
+            dp.CSF.from_callable(lambda i, p: p[i] / p.sum()),
+            dp.RewardFunc.from_callable(lambda i, p: 1.0, 2),
+        ), [0.9, 0.9])
+        for i in range(2):
+            self.assertAlmostEqual(callable_.u_i(i, ACTIONS), builtin.u_i(i, ACTIONS), places=12)
+            # gradients of the callables come from finite differences
+            for x, y in zip(callable_.du_i(i, ACTIONS).flat, builtin.du_i(i, ACTIONS).flat):
+                self.assertAlmostEqual(x, y, places=5)
+
+    @unittest.skipUnless(HAS_NUMPY, "needs numpy")
+    def test_objects_provide_their_own_gradients(self):
+        class Tullock:
+            def __init__(self):
+                self.grads = 0
+
+            def q_i(self, i, p):
+                return p[i] / p.sum()
+
+            def dq_i(self, i, p):
+                self.grads += 1
+                return [((k == i) * p.sum() - p[i]) / p.sum() ** 2 for k in range(len(p))]
+
+        tullock = Tullock()
+        agg = dp.Aggregator(payoff(dp.CSF.from_callable(tullock)), [0.9, 0.9])
+        builtin = dp.Aggregator(payoff(dp.CSF.tullock(1.0)), [0.9, 0.9])
+        for x, y in zip(agg.du_i(0, ACTIONS).flat, builtin.du_i(0, ACTIONS).flat):
+            self.assertAlmostEqual(x, y, places=12)
+        self.assertGreater(tullock.grads, 0)
+
+    @unittest.skipUnless(HAS_NUMPY, "needs numpy")
+    def test_exceptions_are_raised(self):
+        def q_i(i, p):
+            raise ZeroDivisionError("q_i")
+        with self.assertRaises(ZeroDivisionError):
+            payoff(dp.CSF.from_callable(q_i)).u_i(0, ACTIONS[0])
+
+    @unittest.skipUnless(HAS_NUMPY, "needs numpy")
+    def test_exceptions_stay_with_their_solve(self):
+        raised = threading.Event()
+
+        def failing(i, p):
+            raised.set()
+            raise ZeroDivisionError("q_i")
+
+        def working(i, p):
+            # keeps this solve running until the other one has failed
+            raised.wait(5)
+            return p[i] / p.sum()
+
+        results = {}
+
+        def solve(name, q_i):
+            agg = dp.Aggregator(payoff(dp.CSF.from_callable(q_i)), [0.9, 0.9])
+            try:
+                results[name] = agg.solve(t=1, options=dp.SolverOptions(seed=0, method="lbfgs"))
+            except Exception as e:
+                results[name] = e
+
+        threads = [threading.Thread(target=solve, args=args) for args in [("failing", failing), ("working", working)]]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertIsInstance(results["failing"], ZeroDivisionError)
+        self.assertNotIsInstance(results["working"], Exception)
+
+
+class TestRewards(unittest.TestCase):
+    def test_lose_values(self):
+        args = ([1.0, 2.0], [0.1, 0.2], [0.3, 0.4], [0.05, 0.06])
+        p = [2.0, 3.0]
+        # linear rewards give losers the winner's lose value, per player rewards their own
+        self.assertAlmostEqual(dp.RewardFunc.linear_reward(*args).reward_ij(0, 1, p), 0.4 + 0.06 * 3.0)
+        self.assertAlmostEqual(dp.RewardFunc.per_player_reward(*args).reward_ij(0, 1, p), 0.3 + 0.05 * 2.0)
+        for reward_func in [dp.RewardFunc.linear_reward(*args), dp.RewardFunc.per_player_reward(*args)]:
+            self.assertAlmostEqual(reward_func.reward_ij(1, 1, p), 2.0 + 0.2 * 3.0)
+
+
+class TestMultistart(unittest.TestCase):
+    def test_starts_follow_the_solver_options(self):
+        options = dp.SolverOptions(seed=1, init_mu=-1.0, init_sigma=0.5, method="lbfgs")
+        res = model().solve_multistart(1, starts=3, options=options)
+        self.assertEqual(res.failed, [])
+        self.assertEqual(sum(eq.count for eq in res.equilibria), 3)
+        self.assertEqual(model().solve_multistart(1, starts=3, options=options), res)
+        with self.assertRaises(TypeError):
+            model().solve_multistart(1, seed=1)
+
+
+class TestPlanner(unittest.TestCase):
+    def test_price_of_anarchy_needs_positive_welfare(self):
+        options = dp.SolverOptions(seed=0, method="lbfgs")
+        planner = model().solve_planner(t=1, options=options)
+        # disaster costs make welfare negative in this model's equilibrium
+        equilibrium = model().solve(t=1, options=options).optimum
+        with self.assertRaises(dp.InvalidParameterError):
+            model().price_of_anarchy(planner, equilibrium)
+
+
+class TestPickle(unittest.TestCase):
+    def objects(self):
+        return [
+            dp.CSF.maybe_no_win_logit(2.0, 0.5),
+            dp.RiskFunc.all_players([0.5, 0.6]),
+            dp.RewardFunc.linear_reward([1.0, 1.0], [0.0, 0.1], [0.0, 0.0], [0.0, 0.0]),
+            dp.DisasterCost.matrix([[1.0, 0.5], [0.5, 1.0]]),
+            dp.CostFunc.fixed_basic([0.1, 0.2]),
+            dp.ProdFunc([1.0, 1.0], [0.5, 0.5], [1.0, 1.0], [0.5, 0.5]),
+            payoff(),
+            dp.State(payoff()),
+            dp.Aggregator(payoff(), [0.9, 0.9]),
+            ACTIONS[0],
+            dp.SolverOptions(seed=2, method="lbfgs"),
+            model().solve(t=1, options=dp.SolverOptions(seed=0, method="lbfgs")),
+            model().solve_multistart(1, starts=2, options=dp.SolverOptions(seed=0, method="lbfgs")),
+        ]
+
+    def test_objects_round_trip(self):
+        for x in self.objects():
+            with self.subTest(type(x).__name__):
+                y = pickle.loads(pickle.dumps(x))
+                self.assertIs(type(y), type(x))
+                self.assertEqual(y, x)
+                self.assertEqual(repr(y), repr(x))
+
+    def test_equality_is_by_value(self):
+        self.assertEqual(dp.CSF.logit(2.0), dp.CSF.logit(2.0))
+        self.assertNotEqual(dp.CSF.logit(2.0), dp.CSF.logit(3.0))
+        self.assertEqual(payoff(), payoff())
+        self.assertNotEqual(payoff(), payoff(dp.CSF.tullock(2.0)))
+
+    def test_repr_shows_parameters(self):
+        self.assertIn("2.0", repr(dp.CSF.logit(2.0)))
+        self.assertIn("Logit", repr(dp.CSF.logit(2.0)))
+
+    def test_cancel_tokens_are_copied(self):
+        token = dp.CancelToken()
+        token.cancel()
+        copy = pickle.loads(pickle.dumps(token))
+        self.assertTrue(copy.cancelled)
+        # a copy has its own flag
+        self.assertNotEqual(copy, token)
+        copy.reset()
+        self.assertTrue(token.cancelled)
+
+
+if __name__ == "__main__":
+    unittest.main()